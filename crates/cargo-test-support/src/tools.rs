@@ -8,6 +8,7 @@ use std::sync::Mutex;
 lazy_static! {
     static ref ECHO_WRAPPER: Mutex<Option<PathBuf>> = Mutex::new(None);
     static ref ECHO: Mutex<Option<PathBuf>> = Mutex::new(None);
+    static ref PROBE_WRAPPER: Mutex<Option<PathBuf>> = Mutex::new(None);
 }
 
 /// Returns the path to an executable that works as a wrapper around rustc.
@@ -49,6 +50,68 @@ pub fn echo_wrapper() -> PathBuf {
     path
 }
 
+/// Returns the path to an executable that works as a wrapper around rustc,
+/// for testing how Cargo probes rustc.
+///
+/// If `WRAPPER_LOG` is set, the arguments of every call are appended to that
+/// file, one call per line. If `WRAPPER_DROP_DYLIB` is set, calls with
+/// `--print` lose `--crate-type dylib` and print the value of the variable to
+/// stderr instead, like rustc does for targets without `dylib` support.
+pub fn probe_wrapper() -> PathBuf {
+    let mut lock = PROBE_WRAPPER.lock().unwrap();
+    if let Some(path) = &*lock {
+        return path.clone();
+    }
+    let p = project()
+        .at(paths::global_root().join("rustc-probe-wrapper"))
+        .file(
+            "Cargo.toml",
+            &basic_manifest("rustc-probe-wrapper", "1.0.0"),
+        )
+        .file(
+            "src/main.rs",
+            r#"
+            use std::io::Write;
+
+            fn main() {
+                let mut args = std::env::args().skip(1);
+                let rustc = args.next().unwrap();
+                let mut args: Vec<String> = args.collect();
+                if let Some(log) = std::env::var_os("WRAPPER_LOG") {
+                    let mut log = std::fs::OpenOptions::new()
+                        .create(true)
+                        .append(true)
+                        .open(log)
+                        .unwrap();
+                    writeln!(log, "{:?}", args).unwrap();
+                }
+                if let Ok(message) = std::env::var("WRAPPER_DROP_DYLIB") {
+                    if args.iter().any(|arg| arg.starts_with("--print")) {
+                        if let Some(i) = args
+                            .windows(2)
+                            .position(|w| w[0] == "--crate-type" && w[1] == "dylib")
+                        {
+                            args.drain(i..i + 2);
+                            eprintln!("{}", message);
+                        }
+                        // Like rustc, print no file name for a dropped crate type.
+                        if !args.iter().any(|arg| arg == "--crate-type") {
+                            return;
+                        }
+                    }
+                }
+                let status = std::process::Command::new(rustc).args(&args).status().unwrap();
+                std::process::exit(status.code().unwrap_or(1));
+            }
+            "#,
+        )
+        .build();
+    p.cargo("build").run();
+    let path = p.bin("rustc-probe-wrapper");
+    *lock = Some(path.clone());
+    path
+}
+
 /// Returns the path to an executable that prints its arguments.
 ///
 /// Do not expect this to be anything fancy.
@@ -38,6 +38,13 @@ pub fn cli() -> App {
             )
             .value_name("PATH"),
         )
+        .arg(
+            opt(
+                "artifact-map",
+                "Write a JSON map of all output files to this path (unstable)",
+            )
+            .value_name("PATH"),
+        )
         .arg_manifest_path()
         .arg_ignore_rust_version()
         .arg_message_format()
@@ -68,6 +75,12 @@ pub fn exec(config: &mut Config, args: &ArgMatches) -> CliResult {
             .cli_unstable()
             .fail_if_stable_opt("--out-dir", 6790)?;
     }
+    if let Some(artifact_map) = args.value_of_path("artifact-map", config) {
+        config
+            .cli_unstable()
+            .fail_if_stable_opt_untracked("--artifact-map")?;
+        compile_opts.build_config.artifact_map = Some(artifact_map);
+    }
     ops::compile(&ws, &compile_opts)?;
     Ok(())
 }
@@ -0,0 +1,101 @@
+//! Writes the `--artifact-map` file after a build.
+//!
+//! The artifact map lists every unit of the build together with all of the
+//! files Cargo expects it to produce, so packaging and signing tools can find
+//! artifacts without parsing the streaming JSON messages.
+
+use std::path::{Path, PathBuf};
+
+use super::{CompileKind, CompileMode, Context, FileFlavor, Unit};
+use crate::core::{PackageId, Target};
+use crate::util::CargoResult;
+use anyhow::Context as _;
+use cargo_util::paths;
+
+const VERSION: u32 = 1;
+
+#[derive(serde::Serialize)]
+struct SerializedArtifactMap<'a> {
+    version: u32,
+    units: Vec<SerializedUnit<'a>>,
+}
+
+#[derive(serde::Serialize)]
+struct SerializedUnit<'a> {
+    pkg_id: PackageId,
+    target: &'a Target,
+    platform: CompileKind,
+    mode: CompileMode,
+    files: Vec<SerializedFile>,
+}
+
+#[derive(serde::Serialize)]
+struct SerializedFile {
+    path: PathBuf,
+    flavor: &'static str,
+    /// Whether the file was found on disk after the build.
+    produced: bool,
+    /// Where the file was uplifted to, if anywhere.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    uplifted: Option<PathBuf>,
+    /// Where the file was copied to with `--out-dir`, if anywhere.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    exported: Option<PathBuf>,
+}
+
+fn flavor_name(flavor: &FileFlavor) -> &'static str {
+    match flavor {
+        FileFlavor::Normal => "normal",
+        FileFlavor::Auxiliary => "auxiliary",
+        FileFlavor::Linkable => "linkable",
+        FileFlavor::Rmeta => "rmeta",
+        FileFlavor::DebugInfo => "debuginfo",
+    }
+}
+
+/// Writes the artifact map for every unit in the build to `path`.
+///
+/// Units are sorted so the output is deterministic for a given build.
+pub fn write_artifact_map(cx: &Context<'_, '_>, path: &Path) -> CargoResult<()> {
+    let mut units: Vec<&Unit> = cx.bcx.unit_graph.keys().collect();
+    units.sort_unstable();
+    let mut serialized = Vec::new();
+    for unit in units {
+        let outputs = cx.outputs(unit)?;
+        if outputs.is_empty() {
+            continue;
+        }
+        let mut files = Vec::new();
+        for output in outputs.iter() {
+            let mut produced = output.files()?;
+            if produced.is_empty() {
+                // Nothing was written for a pattern, list the pattern itself.
+                produced.push(output.clone());
+            }
+            files.extend(produced.into_iter().map(|file| SerializedFile {
+                produced: file.path.exists(),
+                path: file.path,
+                flavor: flavor_name(&file.flavor),
+                uplifted: file.hardlink,
+                exported: file.export_path,
+            }));
+        }
+        serialized.push(SerializedUnit {
+            pkg_id: unit.pkg.package_id(),
+            target: &unit.target,
+            platform: unit.kind,
+            mode: unit.mode,
+            files,
+        });
+    }
+    let map = SerializedArtifactMap {
+        version: VERSION,
+        units: serialized,
+    };
+    let json = serde_json::to_string_pretty(&map)?;
+    if let Some(parent) = path.parent() {
+        paths::create_dir_all(parent)?;
+    }
+    paths::write(path, json)
+        .with_context(|| format!("failed to write artifact map to `{}`", path.display()))
+}
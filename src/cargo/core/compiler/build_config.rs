@@ -39,6 +39,9 @@ pub struct BuildConfig {
     // Note that, although the cmd-line flag name is `out-dir`, in code we use
     // `export_dir`, to avoid confusion with out dir at `target/debug/deps`.
     pub export_dir: Option<PathBuf>,
+    /// If set, a JSON map of every unit's output files is written here after
+    /// the build finishes.
+    pub artifact_map: Option<PathBuf>,
     /// `true` to output a future incompatibility report at the end of the build
     pub future_incompat_report: bool,
     /// Which kinds of build timings to output (empty if none).
@@ -96,6 +99,7 @@ impl BuildConfig {
             primary_unit_rustc: None,
             rustfix_diagnostic_server: RefCell::new(None),
             export_dir: None,
+            artifact_map: None,
             future_incompat_report: false,
             timing_outputs: Vec::new(),
         })
@@ -11,8 +11,12 @@ use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
 
 mod target_info;
+pub(crate) use self::target_info::PARENT_ENCODED_RUSTFLAGS;
 pub use self::target_info::{
-    FileFlavor, FileType, RustDocFingerprint, RustcTargetData, TargetInfo,
+    effective_codegen_units, effective_debug_assertions, effective_overflow_checks,
+    effective_split_debuginfo, effective_strip, emits_asm, profile_settings_in_rustflags,
+    CrateTypeSupport, Endian, EnvAbi, FileFlavor, FileType, LinkHardening, RustDocFingerprint,
+    RustcTargetData, RustcVersion, TargetInfo,
 };
 
 /// The build context, containing all information about a build task.
@@ -94,11 +98,7 @@ impl<'a, 'cfg> BuildContext<'a, 'cfg> {
 
     /// Gets the user-specified linker for a particular host or target.
     pub fn linker(&self, kind: CompileKind) -> Option<PathBuf> {
-        self.target_data
-            .target_config(kind)
-            .linker
-            .as_ref()
-            .map(|l| l.val.clone().resolve_program(self.config))
+        self.target_data.linker(kind)
     }
 
     /// Gets the host architecture triple.
@@ -120,10 +120,80 @@ impl<'a, 'cfg> BuildContext<'a, 'cfg> {
         &self.target_data.info(unit.kind).rustflags
     }
 
+    /// Extra `rustc` flags configured for the crate types `unit` produces,
+    /// passed after `rustflags_args`.
+    pub fn crate_type_rustflags_args(&self, unit: &Unit) -> Vec<String> {
+        let info = self.target_data.info(unit.kind);
+        let mut args = Vec::new();
+        for crate_type in unit.target.rustc_crate_types() {
+            args.extend_from_slice(info.crate_type_rustflags(&crate_type));
+        }
+        args
+    }
+
+    /// Whether debug assertions are enabled when compiling `unit`, taking
+    /// `-Cdebug-assertions` in the rustflags into account.
+    pub fn debug_assertions(&self, unit: &Unit) -> bool {
+        let mut rustflags = self.rustflags_args(unit).to_vec();
+        rustflags.extend(self.crate_type_rustflags_args(unit));
+        effective_debug_assertions(unit.profile.debug_assertions, &rustflags)
+    }
+
+    /// Whether overflow checks are enabled when compiling `unit`, taking
+    /// `-Coverflow-checks` and `-Cdebug-assertions` in the rustflags into
+    /// account.
+    pub fn overflow_checks(&self, unit: &Unit) -> bool {
+        let mut rustflags = self.rustflags_args(unit).to_vec();
+        rustflags.extend(self.crate_type_rustflags_args(unit));
+        // Cargo only passes `-Coverflow-checks` when it differs from
+        // `debug-assertions`, otherwise rustc derives it from the latter.
+        let default = if unit.profile.overflow_checks == unit.profile.debug_assertions {
+            effective_debug_assertions(unit.profile.debug_assertions, &rustflags)
+        } else {
+            unit.profile.overflow_checks
+        };
+        effective_overflow_checks(default, &rustflags)
+    }
+
+    /// The `.s` files rustc writes for `unit` because of `--emit=asm` in the
+    /// profile's rustflags or the other rustflags, if any.
+    ///
+    /// rustc writes a single `.s` file with one codegen unit, and one per
+    /// codegen unit otherwise.
+    pub fn asm_file_type(&self, unit: &Unit) -> Option<FileType> {
+        let profile_rustflags: Vec<String> = unit
+            .profile
+            .rustflags
+            .iter()
+            .map(|flag| flag.to_string())
+            .collect();
+        let mut rustflags = self.rustflags_args(unit).to_vec();
+        rustflags.extend(self.crate_type_rustflags_args(unit));
+        // Cargo passes the profile's rustflags first, then its
+        // `codegen-units`, then the other rustflags.
+        let codegen_units = unit
+            .profile
+            .codegen_units
+            .or_else(|| effective_codegen_units(None, &profile_rustflags));
+        if !emits_asm(&profile_rustflags) && !emits_asm(&rustflags) {
+            return None;
+        }
+        match effective_codegen_units(codegen_units, &rustflags) {
+            Some(1) => Some(FileType::new_asm()),
+            _ => Some(FileType::new_asm_per_codegen_unit()),
+        }
+    }
+
     pub fn rustdocflags_args(&self, unit: &Unit) -> &[String] {
         &self.target_data.info(unit.kind).rustdocflags
     }
 
+    /// Extra `rustdoc` flags for running the doctests of `unit`, which
+    /// include `rustdocflags_args`.
+    pub fn rustdoc_test_flags_args(&self, unit: &Unit) -> &[String] {
+        &self.target_data.info(unit.kind).rustdoc_test_flags
+    }
+
     pub fn extra_args_for(&self, unit: &Unit) -> Option<&Vec<String>> {
         self.extra_compiler_args.get(unit)
     }
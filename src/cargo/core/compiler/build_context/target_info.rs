@@ -1,18 +1,32 @@
+use crate::core::compiler::compile_kind::target_spec_not_found;
 use crate::core::compiler::{
-    BuildOutput, CompileKind, CompileMode, CompileTarget, Context, CrateType,
+    apply_env_config, BuildOutput, CompileKind, CompileMode, CompileTarget, Context, CrateType,
 };
-use crate::core::{Dependency, Package, Target, TargetKind, Workspace};
-use crate::util::config::{Config, StringList, TargetConfig};
-use crate::util::{CargoResult, Rustc};
+use crate::core::profiles::{PanicStrategy, Strip};
+use crate::core::shell::Verbosity;
+use crate::core::{Dependency, Package, PackageId, Target, TargetKind, Workspace};
+use crate::util::config::{
+    Config, ConfigRelativePath, PathAndArgs, StringList, TargetConfig, Value,
+};
+use crate::util::interning::InternedString;
+use crate::util::rustc::process_fingerprint;
+use crate::util::{self, CargoResult, Rustc, RustcOutputCache, StableHasher};
 use anyhow::Context as _;
-use cargo_platform::{Cfg, CfgExpr};
-use cargo_util::{paths, ProcessBuilder};
+use cargo_platform::{Cfg, CfgExpr, Platform};
+use cargo_util::{paths, ProcessBuilder, ProcessError};
+use lazycell::LazyCell;
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
 use std::cell::RefCell;
 use std::collections::hash_map::{Entry, HashMap};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
 use std::env;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io::Write;
 use std::path::{Path, PathBuf};
 use std::str::{self, FromStr};
+use std::time::Instant;
 
 /// Information about the platform target gleaned from querying rustc.
 ///
@@ -24,15 +38,23 @@ pub struct TargetInfo {
     /// particular, this is used to determine the output filename prefix and
     /// suffix for a crate type.
     crate_type_process: ProcessBuilder,
+    /// The `rustc` output cache and fingerprint that `crate_type_process` is
+    /// run through, so types discovered lazily are remembered across runs
+    /// like the probes in `new`. `None` runs the process directly.
+    crate_type_cache: Option<(RustcOutputCache, u64)>,
     /// Cache of output filename prefixes and suffixes.
     ///
     /// The key is the crate type name (like `cdylib`) and the value is
-    /// `Some((prefix, suffix))`, for example `libcargo.so` would be
-    /// `Some(("lib", ".so")). The value is `None` if the crate type is not
-    /// supported.
-    crate_types: RefCell<HashMap<CrateType, Option<(String, String)>>>,
-    /// `cfg` information extracted from `rustc --print=cfg`.
+    /// `Some` list of `(prefix, suffix)` for each file rustc reports, main
+    /// file first, for example `libcargo.so` would be `Some(vec![("lib",
+    /// ".so")])`. The value is `None` if the crate type is not supported.
+    crate_types: RefCell<HashMap<CrateType, Option<Vec<(String, String)>>>>,
+    /// See `TargetInfo::static_lib_extension`.
+    static_lib_extension: String,
+    /// `cfg` information extracted from `rustc --print=cfg`, see `target_cfgs`.
     cfg: Vec<Cfg>,
+    /// Everything `rustc --print=cfg` printed, see `TargetInfo::raw_cfg`.
+    raw_cfg: Vec<Cfg>,
     /// Path to the sysroot.
     pub sysroot: PathBuf,
     /// Path to the "lib" or "bin" directory that rustc uses for its dynamic
@@ -41,12 +63,510 @@ pub struct TargetInfo {
     /// Path to the "lib" directory in the sysroot which rustc uses for linking
     /// target libraries.
     pub sysroot_target_libdir: PathBuf,
+    /// Path to the `self-contained` directory inside `sysroot_target_libdir`,
+    /// where some toolchains ship the C runtime objects and libraries for
+    /// targets like musl and wasi. `None` if the sysroot has no such directory.
+    pub sysroot_target_libdir_self_contained: Option<PathBuf>,
     /// Extra flags to pass to `rustc`, see `env_args`.
     pub rustflags: Vec<String>,
     /// Extra flags to pass to `rustdoc`, see `env_args`.
     pub rustdocflags: Vec<String>,
+    /// Extra flags to pass to `rustdoc` when running doctests: `rustdocflags`
+    /// followed by `build.rustdoc-test-flags` with `-Zrustdoc-test-flags`.
+    pub rustdoc_test_flags: Vec<String>,
+    /// Extra flags to pass to `rustc` only when compiling a given crate type,
+    /// see `crate_type_rustflags`.
+    crate_type_rustflags: HashMap<CrateType, Vec<String>>,
     /// Whether or not rustc supports the `-Csplit-debuginfo` flag.
     pub supports_split_debuginfo: bool,
+    /// Whether `file_types` lists the import library and export file of a
+    /// Windows DLL, see `build.import-lib`.
+    import_lib: bool,
+    /// Calling conventions from `rustc --print=calling-conventions`, only
+    /// probed on nightly.
+    calling_conventions: Option<Vec<String>>,
+    /// The value of `RUSTC_BOOTSTRAP` that rustc saw while probing, if set.
+    rustc_bootstrap: Option<String>,
+    /// Whether the target spec enables position independent executables,
+    /// from `--print=target-spec-json`. Only probed on nightly.
+    spec_pie: Option<bool>,
+    /// Whether the target spec asks for full RELRO, from
+    /// `--print=target-spec-json`. Only probed on nightly.
+    spec_full_relro: Option<bool>,
+    /// The `max-atomic-width` of the target spec, from
+    /// `--print=target-spec-json`. Only probed on nightly.
+    spec_max_atomic_width: Option<u32>,
+    /// The `data-layout` of the target spec, see `TargetInfo::data_layout`.
+    spec_data_layout: Option<String>,
+    /// The `panic-strategy` of the target spec, from
+    /// `--print=target-spec-json`. Only probed on nightly.
+    spec_panic_strategy: Option<PanicStrategy>,
+    /// The parsed `.json` file of a custom target, see
+    /// `TargetInfo::target_spec_file`.
+    spec_file: Option<serde_json::Value>,
+    /// Where to record probes, see `build.probe-trace`.
+    probe_trace: Option<ProbeTrace>,
+    /// The file that the results of probing are cached in, and the key they
+    /// are valid for, see `Probes`.
+    probe_cache: Option<(PathBuf, u64)>,
+}
+
+/// Hardening that the linker applies by default when rustc links an
+/// executable for a target.
+///
+/// This is derived from the target `cfg` values, so it reflects any
+/// `-Ctarget-feature` flags in the effective rustflags, but not extra
+/// `-Clink-arg` flags.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LinkHardening {
+    /// Whether the C runtime is linked statically (`crt-static`).
+    pub crt_static: bool,
+    /// Whether executables are position independent.
+    pub pie: bool,
+    /// Whether executables are linked with full RELRO (`-z relro -z now`).
+    pub full_relro: bool,
+}
+
+/// What `TargetInfo::new` learns from running rustc.
+struct Probes {
+    crate_types: HashMap<CrateType, Option<Vec<(String, String)>>>,
+    sysroot: PathBuf,
+    raw_cfg: Vec<Cfg>,
+    supports_split_debuginfo: bool,
+    calling_conventions: Option<Vec<String>>,
+    spec_pie: Option<bool>,
+    spec_full_relro: Option<bool>,
+    spec_max_atomic_width: Option<u32>,
+    spec_data_layout: Option<String>,
+    spec_panic_strategy: Option<PanicStrategy>,
+}
+
+/// The on-disk form of `Probes`, stored in the file given by
+/// `Rustc::target_info_cache_file`.
+///
+/// That path already depends on the rustc fingerprint, so `key` only has to
+/// cover the probe itself, see `probe_cache_key`.
+#[derive(Serialize, Deserialize)]
+struct CachedProbes {
+    key: u64,
+    crate_types: BTreeMap<String, Option<Vec<(String, String)>>>,
+    sysroot: PathBuf,
+    raw_cfg: Vec<String>,
+    supports_split_debuginfo: bool,
+    calling_conventions: Option<Vec<String>>,
+    spec_pie: Option<bool>,
+    spec_full_relro: Option<bool>,
+    spec_max_atomic_width: Option<u32>,
+    spec_data_layout: Option<String>,
+    spec_panic_strategy: Option<PanicStrategy>,
+}
+
+impl Probes {
+    /// Reads cached probes from `path`, if they exist and are for `key`.
+    fn load(path: &Path, key: u64) -> Option<Probes> {
+        let json = paths::read_bytes(path).ok()?;
+        let cached: CachedProbes = match serde_json::from_slice(&json) {
+            Ok(cached) => cached,
+            Err(e) => {
+                log::debug!("ignoring target info cache `{}`: {}", path.display(), e);
+                return None;
+            }
+        };
+        if cached.key != key {
+            return None;
+        }
+        let raw_cfg = cached
+            .raw_cfg
+            .iter()
+            .map(|cfg| cfg.parse())
+            .collect::<Result<_, _>>()
+            .ok()?;
+        Some(Probes {
+            crate_types: cached
+                .crate_types
+                .into_iter()
+                .map(|(crate_type, names)| (CrateType::from(&crate_type), names))
+                .collect(),
+            sysroot: cached.sysroot,
+            raw_cfg,
+            supports_split_debuginfo: cached.supports_split_debuginfo,
+            calling_conventions: cached.calling_conventions,
+            spec_pie: cached.spec_pie,
+            spec_full_relro: cached.spec_full_relro,
+            spec_max_atomic_width: cached.spec_max_atomic_width,
+            spec_data_layout: cached.spec_data_layout,
+            spec_panic_strategy: cached.spec_panic_strategy,
+        })
+    }
+}
+
+/// The crate types whose file names are always asked for when probing, others
+/// are discovered on demand with `TargetInfo::crate_type_process`.
+const KNOWN_CRATE_TYPES: &[CrateType] = &[
+    CrateType::Bin,
+    CrateType::Rlib,
+    CrateType::Dylib,
+    CrateType::Cdylib,
+    CrateType::Staticlib,
+    CrateType::ProcMacro,
+];
+
+/// The `rustc` invocations `TargetInfo::new` uses to learn about a target.
+///
+/// These are built separately so `RustcTargetData` can run them ahead of
+/// time for several targets at once, see `prefetch_probes`, and then hand the
+/// same commands to `TargetInfo::from_probe_commands`.
+struct ProbeCommands {
+    extra_fingerprint: u64,
+    bypass_wrapper: bool,
+    /// `--print=file-names` without any crate types.
+    crate_type_process: ProcessBuilder,
+    /// `--print=file-names` for `KNOWN_CRATE_TYPES`, to which
+    /// `TargetInfo::new` adds `--print=sysroot` and `--print=cfg`.
+    process: ProcessBuilder,
+    split_debuginfo_process: ProcessBuilder,
+    /// Only on nightly.
+    calling_conventions_process: Option<ProcessBuilder>,
+    /// Only on nightly.
+    target_spec_process: Option<ProcessBuilder>,
+    probe_trace: Option<ProbeTrace>,
+    /// Where the results are cached, see `Probes::load`.
+    cache_file: Option<PathBuf>,
+    cache_key: u64,
+}
+
+impl ProbeCommands {
+    fn new(
+        config: &Config,
+        requested_kinds: &[CompileKind],
+        rustc: &Rustc,
+        kind: CompileKind,
+    ) -> CargoResult<ProbeCommands> {
+        if let CompileKind::Target(target) = kind {
+            // The spec existed when the target was parsed, but rustc's error
+            // if it has been removed since would not say what is wrong.
+            let name = target.rustc_target();
+            if target.is_spec_file() && !Path::new(name.as_str()).exists() {
+                return Err(target_spec_not_found(&name));
+            }
+        }
+        let rustflags = env_args(
+            config,
+            requested_kinds,
+            &rustc.host,
+            None,
+            kind,
+            Flags::Rust,
+        )?;
+        let extra_fingerprint = kind.fingerprint_hash();
+        let bypass_wrapper = config.cli_unstable().probe_bypass_wrapper
+            && config.build_config()?.probe_bypass_wrapper.unwrap_or(false);
+        // rustc is normally given `-` as its input, which is never read for
+        // these queries, but some sandboxes don't allow a child to inherit
+        // stdin at all.
+        let input = if config.cli_unstable().probe_source_file
+            && config.build_config()?.probe_source_file.unwrap_or(false)
+        {
+            probe_source_file(config)?.into_os_string()
+        } else {
+            "-".into()
+        };
+        // Probe with the `[env]` config applied, so rustc sees the same
+        // environment as when compiling, for example an SDK path needed by a
+        // custom target.
+        let probe_process = || -> CargoResult<ProcessBuilder> {
+            let mut process = if bypass_wrapper {
+                rustc.process_no_wrapper()
+            } else {
+                rustc.workspace_process()
+            };
+            apply_env_config(config, &mut process)?;
+            Ok(process)
+        };
+        let print_process = |args: &[&str]| -> CargoResult<ProcessBuilder> {
+            let mut process = probe_process()?;
+            process.args(args).args(&rustflags).env_remove("RUSTC_LOG");
+            if let CompileKind::Target(target) = kind {
+                process.arg("--target").arg(target.rustc_target());
+            }
+            Ok(process)
+        };
+        let mut process = probe_process()?;
+        process
+            .arg(&input)
+            .arg("--crate-name")
+            .arg("___")
+            .arg("--print=file-names")
+            .args(&rustflags)
+            .env_remove("RUSTC_LOG");
+
+        if let CompileKind::Target(target) = kind {
+            process.arg("--target").arg(target.rustc_target());
+        }
+
+        let crate_type_process = process.clone();
+        for crate_type in KNOWN_CRATE_TYPES.iter() {
+            process.arg("--crate-type").arg(crate_type.as_str());
+        }
+        let mut split_debuginfo_process = process.clone();
+        split_debuginfo_process.arg("-Csplit-debuginfo=packed");
+        let (calling_conventions_process, target_spec_process) = if config.nightly_features_allowed
+        {
+            (
+                Some(print_process(&["--print=calling-conventions"])?),
+                Some(print_process(&[
+                    "-Zunstable-options",
+                    "--print=target-spec-json",
+                ])?),
+            )
+        } else {
+            (None, None)
+        };
+        let probe_trace = config
+            .build_config()?
+            .probe_trace
+            .as_ref()
+            .filter(|_| config.cli_unstable().probe_trace)
+            .map(|path| ProbeTrace {
+                path: path.resolve_path(config),
+                target: short_name_of(rustc, &kind).to_string(),
+            });
+        // Everything rustc reports is kept in the target directory, so later
+        // builds with the same compiler and flags don't run it at all. The
+        // cache is skipped when tracing probes, to get a complete trace.
+        let cache_file = match &probe_trace {
+            Some(_) => None,
+            None => rustc.target_info_cache_file(short_name_of(rustc, &kind)),
+        };
+        let cache_key = probe_cache_key(&process, extra_fingerprint, config);
+        Ok(ProbeCommands {
+            extra_fingerprint,
+            bypass_wrapper,
+            crate_type_process,
+            process,
+            split_debuginfo_process,
+            calling_conventions_process,
+            target_spec_process,
+            probe_trace,
+            cache_file,
+            cache_key,
+        })
+    }
+
+    /// Runs every probe through `rustc`'s output cache, so `TargetInfo::new`
+    /// finds the outputs there. Nothing is run if the results are already
+    /// cached in the target directory.
+    ///
+    /// Failures are ignored here, `TargetInfo::new` reports them when it gets
+    /// the same output from the cache.
+    fn prefetch(&self, rustc: &Rustc) {
+        if let Some(path) = &self.cache_file {
+            if Probes::load(path, self.cache_key).is_some() {
+                return;
+            }
+        }
+        let processes = [
+            Some(&self.split_debuginfo_process),
+            self.calling_conventions_process.as_ref(),
+            self.target_spec_process.as_ref(),
+        ];
+        for process in processes.into_iter().flatten() {
+            drop(rustc.cached_output(process, self.extra_fingerprint));
+        }
+        let mut process = self.process.clone();
+        process.arg("--print=sysroot").arg("--print=cfg");
+        drop(rustc.cached_output(&process, self.extra_fingerprint));
+    }
+}
+
+/// Probes `rustc` with all of `commands` at once, one thread for each target,
+/// ahead of the `TargetInfo::from_probe_commands` calls for them.
+///
+/// Those calls then only read the results from `rustc`'s output cache, so
+/// this only changes how long probing takes, not what is reported.
+fn prefetch_probes(rustc: &Rustc, commands: &HashMap<CompileKind, ProbeCommands>) {
+    crossbeam_utils::thread::scope(|scope| {
+        for commands in commands.values() {
+            scope.spawn(move |_| commands.prefetch(rustc));
+        }
+    })
+    .expect("child threads shouldn't panic");
+}
+
+/// The key cached probes are valid for: the `--print=file-names` command,
+/// which includes the target and the rustflags, and whether the probes that
+/// only run on nightly were made.
+fn probe_cache_key(process: &ProcessBuilder, extra_fingerprint: u64, config: &Config) -> u64 {
+    let mut hasher = StableHasher::new();
+    process.get_program().hash(&mut hasher);
+    process_fingerprint(process, extra_fingerprint).hash(&mut hasher);
+    config.nightly_features_allowed.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Appends what rustc reported to the file given by `build.probe-trace`, as
+/// one JSON object per line, so it can be attached to bug reports.
+#[derive(Clone, Debug)]
+struct ProbeTrace {
+    path: PathBuf,
+    /// The short name of the target being probed.
+    target: String,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "reason", rename_all = "kebab-case")]
+enum ProbeTraceRecord<'a> {
+    /// A single run of rustc, successful or not.
+    Probe {
+        target: &'a str,
+        probe: &'a str,
+        command: String,
+        success: bool,
+        stdout: Cow<'a, str>,
+        stderr: Cow<'a, str>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+    /// What Cargo took from the probes for a target.
+    Parsed {
+        target: &'a str,
+        sysroot: &'a Path,
+        sysroot_host_libdir: &'a Path,
+        sysroot_target_libdir: &'a Path,
+        sysroot_target_libdir_self_contained: Option<&'a Path>,
+        cfg: Vec<String>,
+        crate_types: BTreeMap<String, Option<Vec<(String, String)>>>,
+        supports_split_debuginfo: bool,
+        rustflags: &'a [String],
+        #[serde(skip_serializing_if = "Option::is_none")]
+        rustc_bootstrap: Option<&'a str>,
+    },
+}
+
+impl ProbeTrace {
+    fn record_probe(
+        &self,
+        probe: &str,
+        cmd: &ProcessBuilder,
+        output: Result<(&str, &str), &anyhow::Error>,
+    ) -> CargoResult<()> {
+        let record = match output {
+            Ok((stdout, stderr)) => ProbeTraceRecord::Probe {
+                target: &self.target,
+                probe,
+                command: cmd.to_string(),
+                success: true,
+                stdout: stdout.into(),
+                stderr: stderr.into(),
+                error: None,
+            },
+            Err(e) => {
+                let process_error = e.downcast_ref::<ProcessError>();
+                fn lossy(bytes: Option<&Vec<u8>>) -> Cow<'_, str> {
+                    match bytes {
+                        Some(bytes) => String::from_utf8_lossy(bytes),
+                        None => Cow::Borrowed(""),
+                    }
+                }
+                ProbeTraceRecord::Probe {
+                    target: &self.target,
+                    probe,
+                    command: cmd.to_string(),
+                    success: false,
+                    stdout: lossy(process_error.and_then(|pe| pe.stdout.as_ref())),
+                    stderr: lossy(process_error.and_then(|pe| pe.stderr.as_ref())),
+                    error: Some(format!("{:?}", e)),
+                }
+            }
+        };
+        self.write(&record)
+    }
+
+    fn record_parsed(&self, info: &TargetInfo) -> CargoResult<()> {
+        let crate_types = info
+            .crate_types
+            .borrow()
+            .iter()
+            .map(|(ty, names)| (ty.to_string(), names.clone()))
+            .collect();
+        self.write(&ProbeTraceRecord::Parsed {
+            target: &self.target,
+            sysroot: &info.sysroot,
+            sysroot_host_libdir: &info.sysroot_host_libdir,
+            sysroot_target_libdir: &info.sysroot_target_libdir,
+            sysroot_target_libdir_self_contained: info
+                .sysroot_target_libdir_self_contained
+                .as_deref(),
+            cfg: info.cfg.iter().map(ToString::to_string).collect(),
+            crate_types,
+            supports_split_debuginfo: info.supports_split_debuginfo,
+            rustflags: &info.rustflags,
+            rustc_bootstrap: info.rustc_bootstrap(),
+        })
+    }
+
+    fn write(&self, record: &ProbeTraceRecord<'_>) -> CargoResult<()> {
+        let mut line = serde_json::to_string(record)?;
+        line.push('\n');
+        if let Some(parent) = self.path.parent() {
+            paths::create_dir_all(parent)?;
+        }
+        fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .and_then(|mut file| file.write_all(line.as_bytes()))
+            .with_context(|| {
+                format!(
+                    "failed to write rustc probe trace to `{}`",
+                    self.path.display()
+                )
+            })
+    }
+}
+
+/// The environment/ABI of a target, from its `target_env` cfg.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EnvAbi {
+    Gnu,
+    Musl,
+    Msvc,
+    Sgx,
+    Newlib,
+    /// No `target_env`, for example on macOS or bare-metal targets.
+    None,
+    /// Any other `target_env` value.
+    Other(String),
+}
+
+impl EnvAbi {
+    /// Reads the `target_env` value out of a target's cfg list.
+    pub fn from_cfg(cfg: &[Cfg]) -> EnvAbi {
+        let env = cfg.iter().find_map(|cfg| match cfg {
+            Cfg::KeyPair(k, v) if k == "target_env" => Some(v.as_str()),
+            _ => None,
+        });
+        match env.unwrap_or("") {
+            "gnu" => EnvAbi::Gnu,
+            "musl" => EnvAbi::Musl,
+            "msvc" => EnvAbi::Msvc,
+            "sgx" => EnvAbi::Sgx,
+            "newlib" => EnvAbi::Newlib,
+            "" => EnvAbi::None,
+            other => EnvAbi::Other(other.to_string()),
+        }
+    }
+}
+
+/// The crate types a package asks for, and whether rustc supports each of
+/// them for one compile kind. See `RustcTargetData::crate_type_support`.
+#[derive(Debug)]
+pub struct CrateTypeSupport {
+    pub package: PackageId,
+    pub kind: CompileKind,
+    /// Every crate type declared by the package's targets, sorted, with
+    /// whether it is supported for `kind`.
+    pub crate_types: Vec<(CrateType, bool)>,
 }
 
 /// Kind of each file generated by a Unit, part of `FileType`.
@@ -83,20 +603,33 @@ pub struct FileType {
     prefix: String,
     /// Flag to convert hyphen to underscore when uplifting.
     should_replace_hyphens: bool,
+    /// Whether rustc writes one of these files per codegen unit, with the
+    /// codegen unit's name between the file stem and `suffix`.
+    per_codegen_unit: bool,
 }
 
 impl FileType {
     /// The filename for this FileType crated by rustc.
+    ///
+    /// For a FileType with one file per codegen unit, this is a pattern with
+    /// a `*` in place of the codegen unit's name, see `OutputFile::files`.
     pub fn output_filename(&self, target: &Target, metadata: Option<&str>) -> String {
         match metadata {
             Some(metadata) => format!(
-                "{}{}-{}{}",
+                "{}{}-{}{}{}",
                 self.prefix,
                 target.crate_name(),
                 metadata,
+                self.codegen_unit_pattern(),
+                self.suffix
+            ),
+            None => format!(
+                "{}{}{}{}",
+                self.prefix,
+                target.crate_name(),
+                self.codegen_unit_pattern(),
                 self.suffix
             ),
-            None => format!("{}{}{}", self.prefix, target.crate_name(), self.suffix),
         }
     }
 
@@ -115,7 +648,55 @@ impl FileType {
             }
         };
 
-        format!("{}{}{}", self.prefix, name, self.suffix)
+        format!(
+            "{}{}{}{}",
+            self.prefix,
+            name,
+            self.codegen_unit_pattern(),
+            self.suffix
+        )
+    }
+
+    /// Whether this stands for one file per codegen unit, whose names are
+    /// only known once rustc wrote them.
+    pub fn is_per_codegen_unit(&self) -> bool {
+        self.per_codegen_unit
+    }
+
+    fn codegen_unit_pattern(&self) -> &'static str {
+        if self.per_codegen_unit {
+            ".*"
+        } else {
+            ""
+        }
+    }
+
+    /// Creates a new instance representing a `.s` file from `--emit=asm`.
+    ///
+    /// rustc only writes this file with a single codegen unit, see
+    /// `FileType::new_asm_per_codegen_unit`.
+    pub fn new_asm() -> FileType {
+        FileType {
+            flavor: FileFlavor::Auxiliary,
+            crate_type: None,
+            suffix: ".s".to_string(),
+            prefix: String::new(),
+            should_replace_hyphens: true,
+            per_codegen_unit: false,
+        }
+    }
+
+    /// Creates a new instance representing the `.s` files from `--emit=asm`
+    /// with more than one codegen unit, like `foo-<hash>.<cgu>.rcgu.s`.
+    pub fn new_asm_per_codegen_unit() -> FileType {
+        FileType {
+            flavor: FileFlavor::Auxiliary,
+            crate_type: None,
+            suffix: ".rcgu.s".to_string(),
+            prefix: String::new(),
+            should_replace_hyphens: true,
+            per_codegen_unit: true,
+        }
     }
 
     /// Creates a new instance representing a `.rmeta` file.
@@ -127,10 +708,18 @@ impl FileType {
             suffix: ".rmeta".to_string(),
             prefix: "lib".to_string(),
             should_replace_hyphens: true,
+            per_codegen_unit: false,
         }
     }
 }
 
+/// The byte order of a target, from its `target_endian` cfg.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Endian {
+    Big,
+    Little,
+}
+
 impl TargetInfo {
     pub fn new(
         config: &Config,
@@ -138,101 +727,274 @@ impl TargetInfo {
         rustc: &Rustc,
         kind: CompileKind,
     ) -> CargoResult<TargetInfo> {
-        let rustflags = env_args(
-            config,
-            requested_kinds,
-            &rustc.host,
-            None,
-            kind,
-            Flags::Rust,
-        )?;
-        let extra_fingerprint = kind.fingerprint_hash();
-        let mut process = rustc.workspace_process();
-        process
-            .arg("-")
-            .arg("--crate-name")
-            .arg("___")
-            .arg("--print=file-names")
-            .args(&rustflags)
-            .env_remove("RUSTC_LOG");
+        let commands = ProbeCommands::new(config, requested_kinds, rustc, kind)?;
+        TargetInfo::from_probe_commands(config, requested_kinds, rustc, kind, commands)
+    }
 
-        if let CompileKind::Target(target) = kind {
-            process.arg("--target").arg(target.rustc_target());
-        }
+    /// Learns about `kind` by running `commands`, which were built for it by
+    /// `ProbeCommands::new`.
+    fn from_probe_commands(
+        config: &Config,
+        requested_kinds: &[CompileKind],
+        rustc: &Rustc,
+        kind: CompileKind,
+        commands: ProbeCommands,
+    ) -> CargoResult<TargetInfo> {
+        let ProbeCommands {
+            extra_fingerprint,
+            bypass_wrapper,
+            crate_type_process,
+            mut process,
+            split_debuginfo_process,
+            calling_conventions_process,
+            target_spec_process,
+            probe_trace,
+            cache_file,
+            cache_key,
+        } = commands;
 
-        let crate_type_process = process.clone();
-        const KNOWN_CRATE_TYPES: &[CrateType] = &[
-            CrateType::Bin,
-            CrateType::Rlib,
-            CrateType::Dylib,
-            CrateType::Cdylib,
-            CrateType::Staticlib,
-            CrateType::ProcMacro,
-        ];
-        for crate_type in KNOWN_CRATE_TYPES.iter() {
-            process.arg("--crate-type").arg(crate_type.as_str());
-        }
-        let supports_split_debuginfo = rustc
-            .cached_output(
-                process.clone().arg("-Csplit-debuginfo=packed"),
-                extra_fingerprint,
-            )
-            .is_ok();
+        let rustc_bootstrap = process
+            .get_env("RUSTC_BOOTSTRAP")
+            .map(|s| s.to_string_lossy().into_owned());
 
-        process.arg("--print=sysroot");
-        process.arg("--print=cfg");
+        // Log how long each probe took, since these are a common source of
+        // slow cold starts (especially behind a wrapper).
+        let timed_output = |process: &ProcessBuilder, what: &str| {
+            let start = Instant::now();
+            let result = rustc.cached_output(process, extra_fingerprint);
+            log::debug!(
+                "probing rustc for {} of `{}` took {}",
+                what,
+                short_name_of(rustc, &kind),
+                util::elapsed(start.elapsed())
+            );
+            if let Some(trace) = &probe_trace {
+                let output = result
+                    .as_ref()
+                    .map(|(out, err)| (out.as_str(), err.as_str()));
+                trace.record_probe(what, process, output)?;
+            }
+            result
+        };
+        let cached = cache_file
+            .as_deref()
+            .and_then(|path| Probes::load(path, cache_key));
+        let cache_hit = cached.is_some();
+        let Probes {
+            crate_types,
+            sysroot,
+            raw_cfg,
+            supports_split_debuginfo,
+            calling_conventions,
+            spec_pie,
+            spec_full_relro,
+            spec_max_atomic_width,
+            spec_data_layout,
+            spec_panic_strategy,
+        } = match cached {
+            Some(probes) => probes,
+            None => {
+                let supports_split_debuginfo =
+                    timed_output(&split_debuginfo_process, "`-Csplit-debuginfo` support").is_ok();
 
-        let (output, error) = rustc
-            .cached_output(&process, extra_fingerprint)
-            .with_context(|| "failed to run `rustc` to learn about target-specific information")?;
+                let calling_conventions = calling_conventions_process.and_then(|process| {
+                    timed_output(&process, "calling conventions")
+                        .ok()
+                        .map(|(output, _error)| {
+                            output
+                                .lines()
+                                .map(str::trim)
+                                .filter(|line| !line.is_empty())
+                                .map(str::to_string)
+                                .collect()
+                        })
+                });
 
-        let mut lines = output.lines();
-        let mut map = HashMap::new();
-        for crate_type in KNOWN_CRATE_TYPES {
-            let out = parse_crate_type(crate_type, &process, &output, &error, &mut lines)?;
-            map.insert(crate_type.clone(), out);
-        }
+                let target_spec: Option<serde_json::Value> =
+                    target_spec_process.and_then(|process| {
+                        timed_output(&process, "the target spec")
+                            .ok()
+                            .and_then(|(output, _error)| serde_json::from_str(&output).ok())
+                    });
+                let spec_pie = target_spec.as_ref().and_then(spec_pie);
+                let spec_full_relro = target_spec.as_ref().and_then(spec_full_relro);
+                let spec_max_atomic_width = target_spec.as_ref().and_then(spec_max_atomic_width);
+                let spec_data_layout = target_spec.as_ref().and_then(spec_data_layout);
+                let spec_panic_strategy = target_spec.as_ref().map(spec_panic_strategy);
 
-        let line = match lines.next() {
-            Some(line) => line,
-            None => anyhow::bail!(
-                "output of --print=sysroot missing when learning about \
-                 target-specific information from rustc\n{}",
-                output_err_info(&process, &output, &error)
-            ),
+                process.arg("--print=sysroot");
+                process.arg("--print=cfg");
+
+                let (output, error) = timed_output(&process, "file names, sysroot and cfg")
+                    .with_context(|| {
+                        "failed to run `rustc` to learn about target-specific information"
+                    })?;
+
+                let mut lines = output.lines();
+                let mut map = HashMap::new();
+                let add_wrapper_hint = |e: anyhow::Error| match wrapper_hint(rustc, bypass_wrapper)
+                {
+                    Some(hint) => e.context(hint),
+                    None => e,
+                };
+                // Each crate type takes its files from the output where they
+                // can be told apart by their names, and is asked about on its
+                // own otherwise, see `attribute_file_names`.
+                let file_names: Vec<&str> = output
+                    .lines()
+                    .take_while(|line| is_file_name_line(line))
+                    .collect();
+                let attributed = attribute_file_names(&file_names, &error);
+                let parsed: usize = attributed.iter().flatten().sum();
+                for (crate_type, files) in KNOWN_CRATE_TYPES.iter().zip(attributed) {
+                    let out = match files {
+                        Some(files) => parse_crate_type(
+                            crate_type, &process, &output, &error, &mut lines, files,
+                        ),
+                        None => probe_crate_type(
+                            &crate_type_process,
+                            Some(&(rustc.output_cache(), extra_fingerprint)),
+                            crate_type,
+                            probe_trace.as_ref(),
+                        ),
+                    }
+                    .map_err(add_wrapper_hint)?;
+                    for (prefix, suffix) in out.iter().flatten() {
+                        if let Some(problem) = crate_type_name_anomaly(crate_type, prefix, suffix) {
+                            let mut msg = format!(
+                                "rustc reported an unexpected file name for crate-type `{}`: {}\n{}",
+                                crate_type,
+                                problem,
+                                output_err_info(&process, &output, &error)
+                            );
+                            if let Some(hint) = wrapper_hint(rustc, bypass_wrapper) {
+                                msg.push('\n');
+                                msg.push_str(&hint);
+                            }
+                            config.shell().warn(msg)?;
+                        }
+                    }
+                    map.insert(crate_type.clone(), out);
+                }
+                lines
+                    .by_ref()
+                    .take(file_names.len() - parsed)
+                    .for_each(drop);
+
+                let (sysroot, cfg_lines) = match split_sysroot(lines) {
+                    Some(split) => split,
+                    None => {
+                        return Err(add_wrapper_hint(anyhow::format_err!(
+                            "output of --print=sysroot missing when learning about \
+                             target-specific information from rustc\n{}",
+                            output_err_info(&process, &output, &error)
+                        )))
+                    }
+                };
+                let sysroot = PathBuf::from(sysroot);
+                let raw_cfg = parse_cfg_lines(&cfg_lines)
+                    .with_context(|| {
+                        format!(
+                            "failed to parse the cfg from `rustc --print=cfg`, got:\n{}",
+                            output
+                        )
+                    })
+                    .map_err(add_wrapper_hint)?;
+                let (_, duplicates) = target_cfgs(&raw_cfg);
+                if !duplicates.is_empty() {
+                    let mut msg = format!(
+                        "`rustc --print=cfg` reported some cfg values more than once: {}\n\
+                         The duplicates were ignored.",
+                        duplicates
+                            .iter()
+                            .map(|cfg| format!("`{}`", cfg))
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    );
+                    if let Some(hint) = wrapper_hint(rustc, bypass_wrapper) {
+                        msg.push('\n');
+                        msg.push_str(&hint);
+                    }
+                    config.shell().warn(msg)?;
+                }
+
+                Probes {
+                    crate_types: map,
+                    sysroot,
+                    raw_cfg,
+                    supports_split_debuginfo,
+                    calling_conventions,
+                    spec_pie,
+                    spec_full_relro,
+                    spec_max_atomic_width,
+                    spec_data_layout,
+                    spec_panic_strategy,
+                }
+            }
+        };
+
+        let (cfg, _duplicates) = target_cfgs(&raw_cfg);
+
+        let sysroot = match &config.build_config()?.sysroot {
+            Some(path) if config.cli_unstable().sysroot => {
+                sysroot_override(path.resolve_path(config))?
+            }
+            _ => sysroot,
+        };
+        let short_name = short_name_of(rustc, &kind);
+        let host_libdir_name = if config.cli_unstable().host_libdir_name {
+            config.get::<Option<String>>(&format!("target.{}.host-libdir-name", short_name))?
+        } else {
+            None
+        };
+        let sysroot_host_libdir = match host_libdir_name {
+            Some(name) => sysroot.join(name),
+            None if cfg!(windows) => sysroot.join("bin"),
+            None => sysroot.join("lib"),
         };
-        let sysroot = PathBuf::from(line);
-        let sysroot_host_libdir = if cfg!(windows) {
-            sysroot.join("bin")
+        let rustlib_dir_name = if config.cli_unstable().rustlib_dir_name {
+            config.get::<Option<String>>(&format!("target.{}.rustlib-dir-name", short_name))?
         } else {
-            sysroot.join("lib")
+            None
         };
         let mut sysroot_target_libdir = sysroot.clone();
         sysroot_target_libdir.push("lib");
         sysroot_target_libdir.push("rustlib");
-        sysroot_target_libdir.push(match &kind {
-            CompileKind::Host => rustc.host.as_str(),
-            CompileKind::Target(target) => target.short_name(),
-        });
+        sysroot_target_libdir.push(rustlib_dir_name.as_deref().unwrap_or(short_name));
         sysroot_target_libdir.push("lib");
+        let sysroot_target_libdir_self_contained =
+            Some(sysroot_target_libdir.join("self-contained")).filter(|path| path.is_dir());
 
-        let cfg = lines
-            .map(|line| Ok(Cfg::from_str(line)?))
-            .filter(TargetInfo::not_user_specific_cfg)
-            .collect::<CargoResult<Vec<_>>>()
-            .with_context(|| {
-                format!(
-                    "failed to parse the cfg from `rustc --print=cfg`, got:\n{}",
-                    output
-                )
-            })?;
+        let import_lib =
+            !config.cli_unstable().import_lib || config.build_config()?.import_lib.unwrap_or(true);
+        let spec_file = match kind {
+            CompileKind::Target(target) => target.load_spec()?,
+            CompileKind::Host => None,
+        };
+        let rustdocflags = env_args(
+            config,
+            requested_kinds,
+            &rustc.host,
+            Some(&cfg),
+            kind,
+            Flags::Rustdoc,
+        )?;
+        let mut rustdoc_test_flags = rustdocflags.clone();
+        if config.cli_unstable().rustdoc_test_flags {
+            if let Some(flags) = &config.build_config()?.rustdoc_test_flags {
+                rustdoc_test_flags.extend_from_slice(flags.as_slice());
+            }
+        }
 
-        Ok(TargetInfo {
+        let info = TargetInfo {
             crate_type_process,
-            crate_types: RefCell::new(map),
+            crate_type_cache: Some((rustc.output_cache(), extra_fingerprint)),
+            static_lib_extension: static_lib_extension(&crate_types),
+            crate_types: RefCell::new(crate_types),
             sysroot,
             sysroot_host_libdir,
             sysroot_target_libdir,
+            sysroot_target_libdir_self_contained,
             // recalculate `rustflags` from above now that we have `cfg`
             // information
             rustflags: env_args(
@@ -243,21 +1005,35 @@ impl TargetInfo {
                 kind,
                 Flags::Rust,
             )?,
-            rustdocflags: env_args(
-                config,
-                requested_kinds,
-                &rustc.host,
-                Some(&cfg),
-                kind,
-                Flags::Rustdoc,
-            )?,
+            rustdocflags,
+            rustdoc_test_flags,
             cfg,
+            raw_cfg,
             supports_split_debuginfo,
-        })
+            import_lib,
+            calling_conventions,
+            rustc_bootstrap,
+            spec_pie,
+            spec_full_relro,
+            spec_max_atomic_width,
+            spec_data_layout,
+            spec_panic_strategy,
+            spec_file,
+            probe_trace,
+            probe_cache: cache_file.map(|path| (path, cache_key)),
+            crate_type_rustflags: crate_type_rustflags(config, requested_kinds, &rustc.host, kind)?,
+        };
+        if let Some(trace) = &info.probe_trace {
+            trace.record_parsed(&info)?;
+        }
+        if !cache_hit {
+            info.save_probe_cache(&info.crate_types.borrow());
+        }
+        Ok(info)
     }
 
-    fn not_user_specific_cfg(cfg: &CargoResult<Cfg>) -> bool {
-        if let Ok(Cfg::Name(cfg_name)) = cfg {
+    fn not_user_specific_cfg(cfg: &Cfg) -> bool {
+        if let Cfg::Name(cfg_name) = cfg {
             // This should also include "debug_assertions", but it causes
             // regressions. Maybe some day in the distant future it can be
             // added (and possibly change the warning to an error).
@@ -268,220 +1044,655 @@ impl TargetInfo {
         true
     }
 
+    /// Writes what was learned from rustc to the cache file, with
+    /// `crate_types` being the current contents of `self.crate_types`.
+    ///
+    /// Failing to write the cache is not an error, it only makes the next
+    /// build query rustc again.
+    fn save_probe_cache(&self, crate_types: &HashMap<CrateType, Option<Vec<(String, String)>>>) {
+        let (path, key) = match &self.probe_cache {
+            Some(cache) => cache,
+            None => return,
+        };
+        let cached = CachedProbes {
+            key: *key,
+            crate_types: crate_types
+                .iter()
+                .map(|(crate_type, names)| (crate_type.to_string(), names.clone()))
+                .collect(),
+            sysroot: self.sysroot.clone(),
+            raw_cfg: self.raw_cfg.iter().map(ToString::to_string).collect(),
+            supports_split_debuginfo: self.supports_split_debuginfo,
+            calling_conventions: self.calling_conventions.clone(),
+            spec_pie: self.spec_pie,
+            spec_full_relro: self.spec_full_relro,
+            spec_max_atomic_width: self.spec_max_atomic_width,
+            spec_data_layout: self.spec_data_layout.clone(),
+            spec_panic_strategy: self.spec_panic_strategy,
+        };
+        let result = serde_json::to_vec(&cached)
+            .map_err(anyhow::Error::from)
+            .and_then(|json| {
+                paths::create_dir_all(path.parent().unwrap())?;
+                paths::write(path, json)
+            });
+        if let Err(e) = result {
+            log::debug!(
+                "failed to write target info cache `{}`: {:?}",
+                path.display(),
+                e
+            );
+        }
+    }
+
+    /// A `TargetInfo` made from fixed values instead of probing rustc, for
+    /// tests of the logic built on top of it and for targets whose `cfg`
+    /// comes from `build.cfg-snapshot`.
+    ///
+    /// `raw_cfg` is what `rustc --print=cfg` would print, and `crate_types`
+    /// what the crate type probes would find, see `TargetInfo::crate_types`.
+    /// Crate types missing from it can't be discovered, as there is no rustc
+    /// to ask.
+    pub(crate) fn from_parts(
+        raw_cfg: Vec<Cfg>,
+        sysroot: PathBuf,
+        sysroot_host_libdir: PathBuf,
+        sysroot_target_libdir: PathBuf,
+        rustflags: Vec<String>,
+        crate_types: HashMap<CrateType, Option<Vec<(String, String)>>>,
+    ) -> TargetInfo {
+        let (cfg, _) = target_cfgs(&raw_cfg);
+        TargetInfo {
+            crate_type_process: ProcessBuilder::new("rustc"),
+            crate_type_cache: None,
+            static_lib_extension: static_lib_extension(&crate_types),
+            crate_types: RefCell::new(crate_types),
+            cfg,
+            raw_cfg,
+            sysroot,
+            sysroot_host_libdir,
+            sysroot_target_libdir,
+            sysroot_target_libdir_self_contained: None,
+            rustflags,
+            rustdocflags: Vec::new(),
+            rustdoc_test_flags: Vec::new(),
+            crate_type_rustflags: HashMap::new(),
+            supports_split_debuginfo: false,
+            import_lib: true,
+            calling_conventions: None,
+            rustc_bootstrap: None,
+            spec_pie: None,
+            spec_full_relro: None,
+            spec_max_atomic_width: None,
+            spec_data_layout: None,
+            spec_panic_strategy: None,
+            spec_file: None,
+            probe_trace: None,
+            probe_cache: None,
+        }
+    }
+
+    /// A `TargetInfo` for a target in `build.cfg-snapshot`. Only the `cfg`
+    /// values are known, it has no sysroot, rustflags or crate types.
+    fn from_cfg_snapshot(cfg: &[Cfg]) -> TargetInfo {
+        TargetInfo::from_parts(
+            cfg.to_vec(),
+            PathBuf::new(),
+            PathBuf::new(),
+            PathBuf::new(),
+            Vec::new(),
+            HashMap::new(),
+        )
+    }
+
     /// All the target `cfg` settings.
     pub fn cfg(&self) -> &[Cfg] {
         &self.cfg
     }
 
-    /// Returns the list of file types generated by the given crate type.
+    /// Hashes the parts of this target that `RustcTargetData::build_state_hash`
+    /// covers, with `cfg` in place of the probed values in case they come from
+    /// `build.cfg-snapshot`.
+    fn hash_build_state(&self, cfg: &[Cfg], hasher: &mut StableHasher) {
+        self.sysroot.hash(hasher);
+        self.rustflags.hash(hasher);
+        self.rustdocflags.hash(hasher);
+        self.crate_type_rustflags
+            .iter()
+            .collect::<BTreeMap<_, _>>()
+            .hash(hasher);
+        cfg.iter().collect::<BTreeSet<_>>().hash(hasher);
+        // Only the crate types probed up front, since others are added to
+        // the map as they are used.
+        self.crate_types
+            .borrow()
+            .iter()
+            .filter(|(crate_type, _)| !matches!(crate_type, CrateType::Other(_)))
+            .collect::<BTreeMap<_, _>>()
+            .hash(hasher);
+    }
+
+    /// The `cfg` settings exactly as `rustc --print=cfg` printed them, in
+    /// order.
     ///
-    /// Returns `None` if the target does not support the given crate type.
-    fn file_types(
-        &self,
-        crate_type: &CrateType,
-        flavor: FileFlavor,
-        target_triple: &str,
-    ) -> CargoResult<Option<Vec<FileType>>> {
+    /// Unlike `cfg`, this includes `proc_macro`, which rustc reports because
+    /// it is asked about the `proc-macro` crate type but which is not a
+    /// property of the target, and any value rustc printed more than once.
+    /// `debug_assertions` is in both.
+    pub fn raw_cfg(&self) -> &[Cfg] {
+        &self.raw_cfg
+    }
+
+    /// Whether `self` and `other` describe targets that rustc treats the same
+    /// way: the same `cfg` values, sysroot, flags, and output file names.
+    ///
+    /// This does not consider user config like `target.<triple>.linker`, see
+    /// `RustcTargetData::equivalent_kinds` for that.
+    pub fn is_equivalent(&self, other: &TargetInfo) -> bool {
+        self.cfg == other.cfg
+            && self.sysroot == other.sysroot
+            && self.rustflags == other.rustflags
+            && self.rustdocflags == other.rustdocflags
+            && self.crate_type_rustflags == other.crate_type_rustflags
+            && self.supports_split_debuginfo == other.supports_split_debuginfo
+            && *self.crate_types.borrow() == *other.crate_types.borrow()
+    }
+
+    /// Extra flags from `target.<triple>.crate-type.<type>.rustflags`, passed
+    /// in addition to `rustflags` when compiling `crate_type`.
+    pub fn crate_type_rustflags(&self, crate_type: &CrateType) -> &[String] {
+        self.crate_type_rustflags
+            .get(crate_type)
+            .map(Vec::as_slice)
+            .unwrap_or(&[])
+    }
+
+    /// All flags passed to rustc when compiling `crate_type`, like `"cdylib"`:
+    /// the base `rustflags` followed by `crate_type_rustflags`, so the latter
+    /// take precedence where rustc lets a later flag override an earlier one.
+    pub fn rustflags_for_crate_type(&self, crate_type: &str) -> Vec<String> {
+        let crate_type = CrateType::from(&crate_type.to_string());
+        let mut rustflags = self.rustflags.clone();
+        rustflags.extend_from_slice(self.crate_type_rustflags(&crate_type));
+        rustflags
+    }
+
+    /// The calling conventions rustc supports, such as `"C"` or `"thiscall"`.
+    ///
+    /// This is `None` when not on nightly, or if rustc does not support
+    /// `--print=calling-conventions`.
+    pub fn calling_conventions(&self) -> Option<&[String]> {
+        self.calling_conventions.as_deref()
+    }
+
+    /// The value of `RUSTC_BOOTSTRAP` when rustc was probed, if it was set.
+    ///
+    /// This can make a stable rustc accept `-Z` flags and report nightly-only
+    /// information, so it helps explain unexpected probe results.
+    pub fn rustc_bootstrap(&self) -> Option<&str> {
+        self.rustc_bootstrap.as_deref()
+    }
+
+    /// Returns the value of the first `key = "value"` cfg with the given key.
+    fn cfg_value(&self, key: &str) -> Option<&str> {
+        self.cfg.iter().find_map(|cfg| match cfg {
+            Cfg::KeyPair(k, v) if k == key => Some(v.as_str()),
+            _ => None,
+        })
+    }
+
+    /// The size of a pointer in bits, from `target_pointer_width`.
+    pub fn pointer_width(&self) -> Option<u32> {
+        self.cfg_value("target_pointer_width")?.parse().ok()
+    }
+
+    /// The operating system, from `target_os`, such as `"linux"` or `"none"`
+    /// for bare metal targets.
+    pub fn target_os(&self) -> Option<&str> {
+        self.cfg_value("target_os")
+    }
+
+    /// The family of operating systems, from `target_family`, such as
+    /// `"unix"` or `"wasm"`.
+    ///
+    /// A few targets are in more than one family, like emscripten, which is
+    /// both `unix` and `wasm`. This returns the first one rustc reported.
+    pub fn target_family(&self) -> Option<&str> {
+        self.cfg_value("target_family")
+    }
+
+    /// The byte order, from `target_endian`.
+    pub fn endian(&self) -> Option<Endian> {
+        match self.cfg_value("target_endian")? {
+            "big" => Some(Endian::Big),
+            "little" => Some(Endian::Little),
+            _ => None,
+        }
+    }
+
+    /// Whether this is a target for an Apple simulator, like
+    /// `aarch64-apple-ios-sim`, whose executables only run inside the
+    /// simulator and not directly on the host.
+    pub fn is_simulator(&self) -> bool {
+        match self.cfg_value("target_abi") {
+            Some("sim") => return true,
+            // Like `x86_64-apple-ios-macabi`, which runs on macOS.
+            Some(abi) if !abi.is_empty() => return false,
+            _ => {}
+        }
+        // Older simulator targets like `x86_64-apple-ios` predate the `sim`
+        // ABI, but x86 iOS-family executables only ever run in a simulator.
+        self.cfg_value("target_vendor") == Some("apple")
+            && matches!(
+                self.cfg_value("target_os"),
+                Some("ios" | "tvos" | "watchos")
+            )
+            && matches!(self.cfg_value("target_arch"), Some("x86" | "x86_64"))
+    }
+
+    /// Returns whether `target_feature = "<feature>"` is enabled.
+    fn has_target_feature(&self, feature: &str) -> bool {
+        self.cfg
+            .iter()
+            .any(|cfg| matches!(cfg, Cfg::KeyPair(k, v) if k == "target_feature" && v == feature))
+    }
+
+    /// Whether the standard library is available for this target.
+    ///
+    /// This looks for a prebuilt `std` in the sysroot first. A sysroot that
+    /// only has `core` means a `no_std` target. Without either, for example
+    /// when the target is not installed and `-Zbuild-std` is used, this is
+    /// guessed from the cfgs: targets with an operating system (other than
+    /// `none`) or a target family, like `wasm32-unknown-unknown`, have `std`.
+    pub fn std_available(&self) -> bool {
+        let libdir = &self.sysroot_target_libdir;
+        if has_prebuilt_rlib(libdir, &["std"]) {
+            true
+        } else if has_prebuilt_rlib(libdir, &["core"]) {
+            false
+        } else {
+            cfg_implies_std(&self.cfg)
+        }
+    }
+
+    /// The environment/ABI of this target, such as gnu, musl or msvc.
+    pub fn environment_abi(&self) -> EnvAbi {
+        EnvAbi::from_cfg(&self.cfg)
+    }
+
+    /// The linker hardening rustc applies by default for this target.
+    ///
+    /// Security tooling can compare this against its policy to decide which
+    /// extra flags are needed.
+    ///
+    /// Full RELRO comes from the `relro-level` of `--print=target-spec-json`
+    /// on nightly. Otherwise it is guessed from the target, like the PIE
+    /// default in [`TargetInfo::pie_default`].
+    pub fn link_hardening(&self) -> LinkHardening {
+        LinkHardening {
+            crt_static: self.has_target_feature("crt-static"),
+            pie: self.pie_default(),
+            full_relro: self
+                .spec_full_relro
+                .unwrap_or_else(|| is_hardened_elf_os(self.cfg_value("target_os"))),
+        }
+    }
+
+    /// Whether executables for this target are position independent, taking
+    /// `-Crelocation-model` and `-no-pie` in `-Clink-arg` or `-Clink-args`
+    /// in the resolved rustflags into account.
+    ///
+    /// The target's default comes from `--print=target-spec-json` on nightly.
+    /// Otherwise it is guessed from the target: ELF platforms like Linux and
+    /// the BSDs, as well as Apple platforms, are PIE, while others such as
+    /// Windows, wasm and bare-metal targets are not.
+    pub fn pie_default(&self) -> bool {
+        let target_default = self.spec_pie.unwrap_or_else(|| {
+            is_hardened_elf_os(self.cfg_value("target_os"))
+                || self.cfg_value("target_vendor") == Some("apple")
+        });
+        pie_with_rustflags(target_default, &self.rustflags)
+    }
+
+    /// The panic strategy the target uses when neither the profile nor the
+    /// rustflags choose one, such as `Abort` for most bare-metal targets like
+    /// `thumbv7em-none-eabi`.
+    ///
+    /// This is the target spec's `panic-strategy`, from
+    /// `--print=target-spec-json` on nightly or from the `.json` file of a
+    /// custom target. Otherwise it is rustc's `panic` cfg, unless the
+    /// rustflags contain `-Cpanic`, which changes that cfg. Then, or with a
+    /// rustc too old to report it, it is guessed: targets without an OS are
+    /// `Abort`, others are `Unwind`.
+    pub fn default_panic_strategy(&self) -> PanicStrategy {
+        if let Some(strategy) = self.spec_panic_strategy {
+            return strategy;
+        }
+        if let Some(spec) = &self.spec_file {
+            return spec_panic_strategy(spec);
+        }
+        let overridden = codegen_options(&self.rustflags).any(|o| o.starts_with("panic="));
+        match self.cfg_value("panic") {
+            Some("abort") if !overridden => PanicStrategy::Abort,
+            Some("unwind") if !overridden => PanicStrategy::Unwind,
+            _ => match self.cfg_value("target_os") {
+                Some("none" | "unknown") | None => PanicStrategy::Abort,
+                Some(_) => PanicStrategy::Unwind,
+            },
+        }
+    }
+
+    /// The widest atomic operations this target supports, in bits, such as
+    /// 64 on `x86_64-unknown-linux-gnu`.
+    ///
+    /// This is the largest `target_has_atomic` cfg, or the target spec's
+    /// `max-atomic-width` on nightly when the spec sets one. It is `None` if
+    /// the target has no atomics at all, like `thumbv6m-none-eabi`.
+    pub fn max_atomic_width(&self) -> Option<u32> {
+        match self.spec_max_atomic_width {
+            Some(0) => None,
+            Some(width) => Some(width),
+            None => max_atomic_width_from_cfg(&self.cfg),
+        }
+    }
+
+    /// The widths in bits for which this target has atomic operations, in
+    /// increasing order, such as `[8, 16, 32, 64]` on
+    /// `x86_64-unknown-linux-gnu`.
+    ///
+    /// This comes from the `target_has_atomic` cfgs, so it is empty for
+    /// targets that only have atomic loads and stores, like
+    /// `thumbv6m-none-eabi`.
+    pub fn atomic_widths(&self) -> Vec<u32> {
+        atomic_widths_from_cfg(&self.cfg)
+    }
+
+    /// The LLVM data layout of the target, like
+    /// `e-m:e-p:32:32-Fi8-i64:64-v128:64:128-a:0:32-n32-S64` for
+    /// `thumbv6m-none-eabi`.
+    ///
+    /// This comes from `--print=target-spec-json`, so it is `None` unless
+    /// nightly features are allowed.
+    pub fn data_layout(&self) -> Option<&str> {
+        self.spec_data_layout.as_deref()
+    }
+
+    /// The target specification of a custom target given as a `.json` file,
+    /// like `{"arch": "x86_64", "os": "none", ...}`, as read when this was
+    /// created. `None` for built-in targets and the host.
+    ///
+    /// Unlike `data_layout` and the other values from
+    /// `--print=target-spec-json`, this is available on stable, but only has
+    /// the fields the file sets, without the defaults rustc fills in.
+    pub fn target_spec_file(&self) -> Option<&serde_json::Value> {
+        self.spec_file.as_ref()
+    }
+
+    /// The directories libraries are looked up in when linking for this
+    /// target: the sysroot's target and host libdirs, its `self-contained`
+    /// directory if there is one, then any `-L` directories in `rustflags`,
+    /// without the `native=` style kind.
+    ///
+    /// This does not include the paths that build scripts emit with
+    /// `cargo:rustc-link-search`, which are only known once they have run.
+    pub fn link_search_paths(&self) -> Vec<PathBuf> {
+        let mut paths = vec![self.sysroot_target_libdir.clone()];
+        if self.sysroot_host_libdir != self.sysroot_target_libdir {
+            paths.push(self.sysroot_host_libdir.clone());
+        }
+        paths.extend(self.sysroot_target_libdir_self_contained.iter().cloned());
+        for dir in link_search_dirs(&self.rustflags) {
+            let dir = PathBuf::from(dir);
+            if !paths.contains(&dir) {
+                paths.push(dir);
+            }
+        }
+        paths
+    }
+
+    /// The suffix of executables on this target, for example `.exe` on
+    /// Windows, `.js` for emscripten, or an empty string on Unix-like
+    /// platforms.
+    ///
+    /// This is empty as well if the target does not support executables.
+    pub fn exe_suffix(&self) -> String {
+        // `bin` is always discovered in `new`, so this never runs rustc.
+        match self.crate_types.borrow().get(&CrateType::Bin) {
+            Some(Some(names)) => names[0].1.clone(),
+            _ => String::new(),
+        }
+    }
+
+    /// Whether rustc can produce `crate_type` for this target.
+    ///
+    /// This may run rustc for crate types that are not probed up front.
+    pub fn supports_crate_type(&self, crate_type: &CrateType) -> CargoResult<bool> {
         let crate_type = if *crate_type == CrateType::Lib {
             CrateType::Rlib
         } else {
             crate_type.clone()
         };
-
         let mut crate_types = self.crate_types.borrow_mut();
-        let entry = crate_types.entry(crate_type.clone());
-        let crate_type_info = match entry {
-            Entry::Occupied(o) => &*o.into_mut(),
+        let supported = match crate_types.entry(crate_type) {
+            Entry::Occupied(o) => o.get().is_some(),
             Entry::Vacant(v) => {
                 let value = self.discover_crate_type(v.key())?;
-                &*v.insert(value)
+                v.insert(value).is_some()
             }
         };
-        let (prefix, suffix) = match *crate_type_info {
-            Some((ref prefix, ref suffix)) => (prefix, suffix),
-            None => return Ok(None),
-        };
-        let mut ret = vec![FileType {
-            suffix: suffix.clone(),
-            prefix: prefix.clone(),
-            flavor,
-            crate_type: Some(crate_type.clone()),
-            should_replace_hyphens: crate_type != CrateType::Bin,
-        }];
-
-        // Window shared library import/export files.
-        if crate_type.is_dynamic() {
-            // Note: Custom JSON specs can alter the suffix. For now, we'll
-            // just ignore non-DLL suffixes.
-            if target_triple.ends_with("-windows-msvc") && suffix == ".dll" {
-                // See https://docs.microsoft.com/en-us/cpp/build/reference/working-with-import-libraries-and-export-files
-                // for more information about DLL import/export files.
-                ret.push(FileType {
-                    suffix: ".dll.lib".to_string(),
-                    prefix: prefix.clone(),
-                    flavor: FileFlavor::Auxiliary,
-                    crate_type: Some(crate_type.clone()),
-                    should_replace_hyphens: true,
-                });
-                // NOTE: lld does not produce these
-                ret.push(FileType {
-                    suffix: ".dll.exp".to_string(),
-                    prefix: prefix.clone(),
-                    flavor: FileFlavor::Auxiliary,
-                    crate_type: Some(crate_type.clone()),
-                    should_replace_hyphens: true,
-                });
-            } else if target_triple.ends_with("windows-gnu") && suffix == ".dll" {
-                // See https://cygwin.com/cygwin-ug-net/dll.html for more
-                // information about GNU import libraries.
-                // LD can link DLL directly, but LLD requires the import library.
-                ret.push(FileType {
-                    suffix: ".dll.a".to_string(),
-                    prefix: "lib".to_string(),
-                    flavor: FileFlavor::Auxiliary,
-                    crate_type: Some(crate_type.clone()),
-                    should_replace_hyphens: true,
-                })
-            }
-        }
-
-        if target_triple.starts_with("wasm32-") && crate_type == CrateType::Bin && suffix == ".js" {
-            // emscripten binaries generate a .js file, which loads a .wasm
-            // file.
-            ret.push(FileType {
-                suffix: ".wasm".to_string(),
-                prefix: prefix.clone(),
-                flavor: FileFlavor::Auxiliary,
-                crate_type: Some(crate_type.clone()),
-                // Name `foo-bar` will generate a `foo_bar.js` and
-                // `foo_bar.wasm`. Cargo will translate the underscore and
-                // copy `foo_bar.js` to `foo-bar.js`. However, the wasm
-                // filename is embedded in the .js file with an underscore, so
-                // it should not contain hyphens.
-                should_replace_hyphens: true,
-            });
-            // And a map file for debugging. This is only emitted with debug=2
-            // (-g4 for emcc).
-            ret.push(FileType {
-                suffix: ".wasm.map".to_string(),
-                prefix: prefix.clone(),
-                flavor: FileFlavor::DebugInfo,
-                crate_type: Some(crate_type.clone()),
-                should_replace_hyphens: true,
-            });
-        }
+        Ok(supported)
+    }
 
-        // Handle separate debug files.
-        let is_apple = target_triple.contains("-apple-");
-        if matches!(
-            crate_type,
-            CrateType::Bin | CrateType::Dylib | CrateType::Cdylib | CrateType::ProcMacro
-        ) {
-            if is_apple {
-                let suffix = if crate_type == CrateType::Bin {
-                    ".dSYM".to_string()
-                } else {
-                    ".dylib.dSYM".to_string()
-                };
-                ret.push(FileType {
-                    suffix,
-                    prefix: prefix.clone(),
-                    flavor: FileFlavor::DebugInfo,
-                    crate_type: Some(crate_type),
-                    // macOS tools like lldb use all sorts of magic to locate
-                    // dSYM files. See https://lldb.llvm.org/use/symbols.html
-                    // for some details. It seems like a `.dSYM` located next
-                    // to the executable with the same name is one method. The
-                    // dSYM should have the same hyphens as the executable for
-                    // the names to match.
-                    should_replace_hyphens: false,
-                })
-            } else if target_triple.ends_with("-msvc") {
-                ret.push(FileType {
-                    suffix: ".pdb".to_string(),
-                    prefix: prefix.clone(),
-                    flavor: FileFlavor::DebugInfo,
-                    crate_type: Some(crate_type),
-                    // The absolute path to the pdb file is embedded in the
-                    // executable. If the exe/pdb pair is moved to another
-                    // machine, then debuggers will look in the same directory
-                    // of the exe with the original pdb filename. Since the
-                    // original name contains underscores, they need to be
-                    // preserved.
-                    should_replace_hyphens: true,
-                })
+    /// The crate types out of `bin`, `rlib`, `dylib`, `cdylib`, `staticlib`
+    /// and `proc-macro` that rustc can produce for this target.
+    pub fn supported_crate_types(&self) -> CargoResult<Vec<String>> {
+        // These are always discovered in `new`, so this never runs rustc.
+        let mut supported = Vec::new();
+        for crate_type in KNOWN_CRATE_TYPES {
+            if self.supports_crate_type(crate_type)? {
+                supported.push(crate_type.to_string());
             }
         }
+        Ok(supported)
+    }
 
-        Ok(Some(ret))
+    /// The extension of static libraries on this target, without the leading
+    /// dot, for example `lib` on `-windows-msvc` and `a` on `-windows-gnu`
+    /// and Unix-like platforms.
+    ///
+    /// This comes straight from rustc, and is empty if the target does not
+    /// support `staticlib`.
+    pub fn static_lib_extension(&self) -> &str {
+        &self.static_lib_extension
     }
 
-    fn discover_crate_type(&self, crate_type: &CrateType) -> CargoResult<Option<(String, String)>> {
-        let mut process = self.crate_type_process.clone();
+    /// Returns the list of file types generated by the given crate type.
+    ///
+    /// Returns `None` if the target does not support the given crate type.
+    ///
+    /// The main file always uses the prefix and suffix reported by rustc, so
+    /// for example a `staticlib` is `foo.lib` on `-windows-msvc` and
+    /// `libfoo.a` on `-windows-gnu`.
+    fn file_types(
+        &self,
+        crate_type: &CrateType,
+        flavor: FileFlavor,
+        target_triple: &str,
+        strip: Strip,
+        split_debuginfo: Option<InternedString>,
+    ) -> CargoResult<Option<Vec<FileType>>> {
+        let mut file_types = self.file_types_multi(
+            &[(crate_type.clone(), flavor)],
+            target_triple,
+            strip,
+            split_debuginfo,
+        )?;
+        Ok(file_types.pop().unwrap().1)
+    }
 
-        process.arg("--crate-type").arg(crate_type.as_str());
+    /// Like `file_types`, but for several crate types at once, such as a
+    /// library with `crate-type = ["rlib", "cdylib"]`.
+    ///
+    /// The result has one entry per element of `crate_types`, in the same
+    /// order, which is `None` if the target does not support that crate type.
+    pub fn file_types_multi(
+        &self,
+        crate_types: &[(CrateType, FileFlavor)],
+        target_triple: &str,
+        strip: Strip,
+        split_debuginfo: Option<InternedString>,
+    ) -> CargoResult<Vec<(CrateType, Option<Vec<FileType>>)>> {
+        let mut known = self.crate_types.borrow_mut();
+        let mut discovered = false;
+        let mut ret = Vec::with_capacity(crate_types.len());
+        for (requested, flavor) in crate_types {
+            let crate_type = if *requested == CrateType::Lib {
+                CrateType::Rlib
+            } else {
+                requested.clone()
+            };
+            check_flavor(&crate_type, flavor)?;
 
-        let output = process.exec_with_output().with_context(|| {
-            format!(
-                "failed to run `rustc` to learn about crate-type {} information",
-                crate_type
-            )
-        })?;
+            let crate_type_info = match known.entry(crate_type.clone()) {
+                Entry::Occupied(o) => &*o.into_mut(),
+                Entry::Vacant(v) => {
+                    let value = self.discover_crate_type(v.key())?;
+                    discovered = true;
+                    &*v.insert(value)
+                }
+            };
+            let file_types = crate_type_info.as_ref().map(|names| {
+                let mut file_types = file_types_for(
+                    crate_type.clone(),
+                    flavor.clone(),
+                    names,
+                    target_triple,
+                    strip,
+                    split_debuginfo,
+                );
+                if !self.import_lib {
+                    // rustc still writes them, Cargo just doesn't track them.
+                    file_types.retain(|ft| !is_import_lib(ft));
+                }
+                if let Some(map_suffix) = linker_map_suffix(&self.rustflags) {
+                    if matches!(
+                        crate_type,
+                        CrateType::Bin
+                            | CrateType::Dylib
+                            | CrateType::Cdylib
+                            | CrateType::ProcMacro
+                    ) {
+                        let primary = &file_types[0];
+                        let map = FileType {
+                            flavor: FileFlavor::Auxiliary,
+                            crate_type: Some(crate_type.clone()),
+                            suffix: format!("{}{}", primary.suffix, map_suffix),
+                            prefix: primary.prefix.clone(),
+                            should_replace_hyphens: primary.should_replace_hyphens,
+                            per_codegen_unit: false,
+                        };
+                        file_types.push(map);
+                    }
+                }
+                file_types
+            });
+            ret.push((requested.clone(), file_types));
+        }
+        if discovered {
+            self.save_probe_cache(&known);
+        }
+        Ok(ret)
+    }
 
-        let error = str::from_utf8(&output.stderr).unwrap();
-        let output = str::from_utf8(&output.stdout).unwrap();
-        parse_crate_type(crate_type, &process, output, error, &mut output.lines())
+    fn discover_crate_type(
+        &self,
+        crate_type: &CrateType,
+    ) -> CargoResult<Option<Vec<(String, String)>>> {
+        // Crate types are only discovered here when `new` didn't probe them.
+        probe_crate_type(
+            &self.crate_type_process,
+            self.crate_type_cache.as_ref(),
+            crate_type,
+            self.probe_trace.as_ref(),
+        )
     }
 
     /// Returns all the file types generated by rustc for the given mode/target_kind.
     ///
     /// The first value is a Vec of file types generated, the second value is
     /// a list of CrateTypes that are not supported by the given target.
+    ///
+    /// `strip` and `split_debuginfo` are the effective settings (see
+    /// `effective_strip` and `effective_split_debuginfo`), which decide
+    /// whether separate debug info files are produced.
     pub fn rustc_outputs(
         &self,
         mode: CompileMode,
         target_kind: &TargetKind,
         target_triple: &str,
+        strip: Strip,
+        split_debuginfo: Option<InternedString>,
     ) -> CargoResult<(Vec<FileType>, Vec<CrateType>)> {
-        match mode {
-            CompileMode::Build => self.calc_rustc_outputs(target_kind, target_triple),
+        let (mut file_types, unsupported) = match mode {
+            CompileMode::Build => {
+                self.calc_rustc_outputs(target_kind, target_triple, strip, split_debuginfo)?
+            }
             CompileMode::Test | CompileMode::Bench => {
-                match self.file_types(&CrateType::Bin, FileFlavor::Normal, target_triple)? {
-                    Some(fts) => Ok((fts, Vec::new())),
-                    None => Ok((Vec::new(), vec![CrateType::Bin])),
+                match self.file_types(
+                    &CrateType::Bin,
+                    FileFlavor::Normal,
+                    target_triple,
+                    strip,
+                    split_debuginfo,
+                )? {
+                    Some(fts) => (fts, Vec::new()),
+                    None => (Vec::new(), vec![CrateType::Bin]),
                 }
             }
-            CompileMode::Check { .. } => Ok((vec![FileType::new_rmeta()], Vec::new())),
+            CompileMode::Check { .. } => return Ok((vec![FileType::new_rmeta()], Vec::new())),
             CompileMode::Doc { .. }
             | CompileMode::Doctest
             | CompileMode::Docscrape
             | CompileMode::RunCustomBuild => {
                 panic!("asked for rustc output for non-rustc mode")
             }
+        };
+        if !file_types.is_empty() && split_debuginfo.as_deref() == Some("unpacked") {
+            file_types.extend(unpacked_debuginfo_file_types(target_triple));
         }
+        Ok((file_types, unsupported))
     }
 
     fn calc_rustc_outputs(
         &self,
         target_kind: &TargetKind,
         target_triple: &str,
+        strip: Strip,
+        split_debuginfo: Option<InternedString>,
     ) -> CargoResult<(Vec<FileType>, Vec<CrateType>)> {
         let mut unsupported = Vec::new();
         let mut result = Vec::new();
         let crate_types = target_kind.rustc_crate_types();
-        for crate_type in &crate_types {
-            let flavor = if crate_type.is_linkable() {
-                FileFlavor::Linkable
-            } else {
-                FileFlavor::Normal
-            };
-            let file_types = self.file_types(crate_type, flavor, target_triple)?;
+        let requested: Vec<_> = crate_types
+            .iter()
+            .map(|crate_type| {
+                let flavor = if crate_type.is_linkable() {
+                    FileFlavor::Linkable
+                } else {
+                    FileFlavor::Normal
+                };
+                (crate_type.clone(), flavor)
+            })
+            .collect();
+        for (crate_type, file_types) in
+            self.file_types_multi(&requested, target_triple, strip, split_debuginfo)?
+        {
             match file_types {
                 Some(types) => {
                     result.extend(types);
                 }
                 None => {
-                    unsupported.push(crate_type.clone());
+                    unsupported.push(crate_type);
                 }
             }
         }
@@ -491,499 +1702,4522 @@ impl TargetInfo {
         }
         Ok((result, unsupported))
     }
-}
 
-/// Takes rustc output (using specialized command line args), and calculates the file prefix and
-/// suffix for the given crate type, or returns `None` if the type is not supported. (e.g., for a
-/// Rust library like `libcargo.rlib`, we have prefix "lib" and suffix "rlib").
-///
-/// The caller needs to ensure that the lines object is at the correct line for the given crate
-/// type: this is not checked.
-///
-/// This function can not handle more than one file per type (with wasm32-unknown-emscripten, there
-/// are two files for bin (`.wasm` and `.js`)).
-fn parse_crate_type(
-    crate_type: &CrateType,
-    cmd: &ProcessBuilder,
-    output: &str,
-    error: &str,
-    lines: &mut str::Lines<'_>,
-) -> CargoResult<Option<(String, String)>> {
-    let not_supported = error.lines().any(|line| {
-        (line.contains("unsupported crate type") || line.contains("unknown crate type"))
-            && line.contains(&format!("crate type `{}`", crate_type))
-    });
-    if not_supported {
-        return Ok(None);
+    /// Returns the main file rustc writes for `crate_type`, like `foo.dll`
+    /// for a `dylib` on `-windows-msvc`, without the import libraries, debug
+    /// info or other files that come with it.
+    ///
+    /// Returns `None` if the target does not support the given crate type.
+    pub fn primary_file_type(
+        &self,
+        crate_type: &CrateType,
+        target_triple: &str,
+    ) -> CargoResult<Option<FileType>> {
+        let flavor = if crate_type.is_linkable() {
+            FileFlavor::Linkable
+        } else {
+            FileFlavor::Normal
+        };
+        let file_types = self.file_types(crate_type, flavor, target_triple, Strip::None, None)?;
+        Ok(file_types.map(|file_types| file_types.into_iter().next().unwrap()))
     }
-    let line = match lines.next() {
-        Some(line) => line,
-        None => anyhow::bail!(
-            "malformed output when learning about crate-type {} information\n{}",
-            crate_type,
-            output_err_info(cmd, output, error)
-        ),
-    };
-    let mut parts = line.trim().split("___");
-    let prefix = parts.next().unwrap();
-    let suffix = match parts.next() {
-        Some(part) => part,
-        None => anyhow::bail!(
-            "output of --print=file-names has changed in the compiler, cannot parse\n{}",
-            output_err_info(cmd, output, error)
-        ),
-    };
-
-    Ok(Some((prefix.to_string(), suffix.to_string())))
-}
 
-/// Helper for creating an error message when parsing rustc output fails.
-fn output_err_info(cmd: &ProcessBuilder, stdout: &str, stderr: &str) -> String {
-    let mut result = format!("command was: {}\n", cmd);
-    if !stdout.is_empty() {
-        result.push_str("\n--- stdout\n");
-        result.push_str(stdout);
-    }
-    if !stderr.is_empty() {
-        result.push_str("\n--- stderr\n");
-        result.push_str(stderr);
-    }
-    if stdout.is_empty() && stderr.is_empty() {
-        result.push_str("(no output received)");
+    /// Returns the extra files uplifted alongside the primary output of
+    /// `crate_type`, such as import libraries, debug info, or wasm sidecars.
+    ///
+    /// Each entry is the flavor of the file and its uplifted filename. This
+    /// is empty if the crate type only produces a single file, or is not
+    /// supported by the target.
+    pub fn auxiliary_outputs(
+        &self,
+        crate_type: &CrateType,
+        target: &Target,
+        target_triple: &str,
+        strip: Strip,
+        split_debuginfo: Option<InternedString>,
+    ) -> CargoResult<Vec<(FileFlavor, String)>> {
+        let flavor = if crate_type.is_linkable() {
+            FileFlavor::Linkable
+        } else {
+            FileFlavor::Normal
+        };
+        let file_types =
+            match self.file_types(crate_type, flavor, target_triple, strip, split_debuginfo)? {
+                Some(file_types) => file_types,
+                None => return Ok(Vec::new()),
+            };
+        Ok(file_types
+            .into_iter()
+            .skip(1)
+            .map(|ft| {
+                let filename = ft.uplift_filename(target);
+                (ft.flavor, filename)
+            })
+            .collect())
     }
-    result
 }
 
-#[derive(Debug, Copy, Clone)]
-enum Flags {
-    Rust,
-    Rustdoc,
-}
+/// The files rustc writes for `crate_type` on `target_triple`, given the
+/// prefixes and suffixes rustc reported for it, main file first.
+fn file_types_for(
+    crate_type: CrateType,
+    flavor: FileFlavor,
+    names: &[(String, String)],
+    target_triple: &str,
+    strip: Strip,
+    split_debuginfo: Option<InternedString>,
+) -> Vec<FileType> {
+    let ((prefix, suffix), others) = names.split_first().expect("rustc reported no files");
+    // UEFI firmware only loads applications with an `.efi` extension. rustc
+    // reports it, but keep it even if rustc (or a wrapper) reports none.
+    let suffix =
+        if crate_type == CrateType::Bin && suffix.is_empty() && target_triple.ends_with("-uefi") {
+            ".efi"
+        } else {
+            suffix.as_str()
+        };
+    let mut ret = vec![FileType {
+        suffix: suffix.to_string(),
+        prefix: prefix.to_string(),
+        flavor,
+        crate_type: Some(crate_type.clone()),
+        should_replace_hyphens: crate_type != CrateType::Bin,
+        per_codegen_unit: false,
+    }];
+    // Any other files rustc reported, like the `.wasm` file loaded by an
+    // emscripten `.js`, load or embed the main file's name with underscores,
+    // so they keep them.
+    ret.extend(others.iter().map(|(prefix, suffix)| FileType {
+        suffix: suffix.to_string(),
+        prefix: prefix.to_string(),
+        flavor: FileFlavor::Auxiliary,
+        crate_type: Some(crate_type.clone()),
+        should_replace_hyphens: true,
+        per_codegen_unit: false,
+    }));
 
-impl Flags {
-    fn as_key(self) -> &'static str {
-        match self {
-            Flags::Rust => "rustflags",
-            Flags::Rustdoc => "rustdocflags",
+    // Window shared library import/export files.
+    if crate_type.is_dynamic() {
+        // Note: Custom JSON specs can alter the suffix. For now, we'll
+        // just ignore non-DLL suffixes.
+        if target_triple.ends_with("-windows-msvc") && suffix == ".dll" {
+            // See https://docs.microsoft.com/en-us/cpp/build/reference/working-with-import-libraries-and-export-files
+            // for more information about DLL import/export files.
+            ret.push(FileType {
+                suffix: ".dll.lib".to_string(),
+                prefix: prefix.to_string(),
+                flavor: FileFlavor::Auxiliary,
+                crate_type: Some(crate_type.clone()),
+                should_replace_hyphens: true,
+                per_codegen_unit: false,
+            });
+            // NOTE: lld does not produce these
+            ret.push(FileType {
+                suffix: ".dll.exp".to_string(),
+                prefix: prefix.to_string(),
+                flavor: FileFlavor::Auxiliary,
+                crate_type: Some(crate_type.clone()),
+                should_replace_hyphens: true,
+                per_codegen_unit: false,
+            });
+        } else if target_triple.ends_with("windows-gnu") && suffix == ".dll" {
+            // See https://cygwin.com/cygwin-ug-net/dll.html for more
+            // information about GNU import libraries.
+            // LD can link DLL directly, but LLD requires the import library.
+            ret.push(FileType {
+                suffix: ".dll.a".to_string(),
+                prefix: "lib".to_string(),
+                flavor: FileFlavor::Auxiliary,
+                crate_type: Some(crate_type.clone()),
+                should_replace_hyphens: true,
+                per_codegen_unit: false,
+            })
         }
     }
 
-    fn as_env(self) -> &'static str {
-        match self {
-            Flags::Rust => "RUSTFLAGS",
-            Flags::Rustdoc => "RUSTDOCFLAGS",
+    let is_wasm = target_triple.starts_with("wasm32-") || target_triple.starts_with("wasm64-");
+    if is_wasm && crate_type == CrateType::Bin && suffix == ".js" {
+        // emscripten binaries generate a .js file, which loads a .wasm
+        // file. Not every rustc reports the latter.
+        if !others.iter().any(|(_, suffix)| suffix == ".wasm") {
+            ret.push(FileType {
+                suffix: ".wasm".to_string(),
+                prefix: prefix.to_string(),
+                flavor: FileFlavor::Auxiliary,
+                crate_type: Some(crate_type.clone()),
+                // Name `foo-bar` will generate a `foo_bar.js` and
+                // `foo_bar.wasm`. Cargo will translate the underscore and
+                // copy `foo_bar.js` to `foo-bar.js`. However, the wasm
+                // filename is embedded in the .js file with an underscore, so
+                // it should not contain hyphens.
+                should_replace_hyphens: true,
+                per_codegen_unit: false,
+            });
         }
+        // And a map file for debugging. This is only emitted with debug=2
+        // (-g4 for emcc).
+        ret.push(FileType {
+            suffix: ".wasm.map".to_string(),
+            prefix: prefix.to_string(),
+            flavor: FileFlavor::DebugInfo,
+            crate_type: Some(crate_type.clone()),
+            should_replace_hyphens: true,
+            per_codegen_unit: false,
+        });
     }
-}
 
-/// Acquire extra flags to pass to the compiler from various locations.
-///
-/// The locations are:
-///
-///  - the `CARGO_ENCODED_RUSTFLAGS` environment variable
-///  - the `RUSTFLAGS` environment variable
-///
-/// then if none of those were found
-///
-///  - `target.*.rustflags` from the config (.cargo/config)
-///  - `target.cfg(..).rustflags` from the config
-///  - `host.*.rustflags` from the config if compiling a host artifact or without `--target`
-///
-/// then if none of those were found
-///
-///  - `build.rustflags` from the config
+    // Handle separate debug files.
+    let is_apple = target_triple.contains("-apple-");
+    if matches!(
+        crate_type,
+        CrateType::Bin | CrateType::Dylib | CrateType::Cdylib | CrateType::ProcMacro
+    ) {
+        if is_apple {
+            let suffix = if crate_type == CrateType::Bin {
+                ".dSYM".to_string()
+            } else {
+                ".dylib.dSYM".to_string()
+            };
+            ret.push(FileType {
+                suffix,
+                prefix: prefix.to_string(),
+                flavor: FileFlavor::DebugInfo,
+                crate_type: Some(crate_type),
+                // macOS tools like lldb use all sorts of magic to locate
+                // dSYM files. See https://lldb.llvm.org/use/symbols.html
+                // for some details. It seems like a `.dSYM` located next
+                // to the executable with the same name is one method. The
+                // dSYM should have the same hyphens as the executable for
+                // the names to match.
+                should_replace_hyphens: false,
+                per_codegen_unit: false,
+            })
+        } else if target_triple.ends_with("-msvc") && !strips_symbols(strip) {
+            // With `strip = "symbols"` rustc tells the linker not to
+            // emit a `.pdb` at all, whereas `strip = "debuginfo"` still
+            // leaves the separate debug file in place.
+            ret.push(FileType {
+                suffix: ".pdb".to_string(),
+                prefix: prefix.to_string(),
+                flavor: FileFlavor::DebugInfo,
+                crate_type: Some(crate_type),
+                // The absolute path to the pdb file is embedded in the
+                // executable. If the exe/pdb pair is moved to another
+                // machine, then debuggers will look in the same directory
+                // of the exe with the original pdb filename. Since the
+                // original name contains underscores, they need to be
+                // preserved.
+                should_replace_hyphens: true,
+                per_codegen_unit: false,
+            })
+        } else if !target_triple.contains("-windows-")
+            && split_debuginfo.as_deref() == Some("packed")
+        {
+            // rustc packs the DWARF into a `.dwp` named like the linked file
+            // with its extension replaced, like `libfoo.dwp` for
+            // `libfoo.so`. Debuggers look for `<exe>.dwp`, so an uplifted
+            // executable needs a `.dwp` with the same hyphens. For
+            // `unpacked`, see `unpacked_debuginfo_file_types`.
+            ret.push(FileType {
+                suffix: ".dwp".to_string(),
+                prefix: prefix.to_string(),
+                flavor: FileFlavor::DebugInfo,
+                crate_type: Some(crate_type.clone()),
+                should_replace_hyphens: crate_type != CrateType::Bin,
+                per_codegen_unit: false,
+            })
+        }
+    }
+
+    ret
+}
+
+/// The files rustc leaves in the output directory for each codegen unit with
+/// `split-debuginfo=unpacked` on `target_triple`, whatever the crate types.
 ///
-/// The behavior differs slightly when cross-compiling (or, specifically, when `--target` is
-/// provided) for artifacts that are always built for the host (plugins, build scripts, ...).
-/// For those artifacts, _only_ `host.*.rustflags` is respected, and no other configuration
-/// sources, _regardless of the value of `target-applies-to-host`_. This is counterintuitive, but
-/// necessary to retain bacwkards compatibility with older versions of Cargo.
-fn env_args(
-    config: &Config,
-    requested_kinds: &[CompileKind],
-    host_triple: &str,
-    target_cfg: Option<&[Cfg]>,
-    kind: CompileKind,
-    flags: Flags,
-) -> CargoResult<Vec<String>> {
-    let target_applies_to_host = config.target_applies_to_host()?;
+/// They are named after the output without its prefix and extension, like
+/// `foo-<hash>.foo.<cgu>.rcgu.dwo`. Apple targets keep the DWARF in the
+/// `.o` files, and so do other targets with `-Zsplit-dwarf-kind=single`.
+/// `split-debuginfo=unpacked` isn't supported on Windows.
+fn unpacked_debuginfo_file_types(target_triple: &str) -> Vec<FileType> {
+    let suffixes: &[&str] = if target_triple.contains("-windows-") {
+        &[]
+    } else if target_triple.contains("-apple-") {
+        &[".rcgu.o"]
+    } else {
+        &[".rcgu.o", ".rcgu.dwo"]
+    };
+    suffixes
+        .iter()
+        .map(|suffix| FileType {
+            flavor: FileFlavor::DebugInfo,
+            crate_type: None,
+            suffix: suffix.to_string(),
+            prefix: String::new(),
+            should_replace_hyphens: true,
+            per_codegen_unit: true,
+        })
+        .collect()
+}
 
-    // Host artifacts should not generally pick up rustflags from anywhere except [host].
-    //
-    // The one exception to this is if `target-applies-to-host = true`, which opts into a
-    // particular (inconsistent) past Cargo behavior where host artifacts _do_ pick up rustflags
-    // set elsewhere when `--target` isn't passed.
-    if kind.is_host() {
-        if target_applies_to_host && requested_kinds == [CompileKind::Host] {
-            // This is the past Cargo behavior where we fall back to the same logic as for other
-            // artifacts without --target.
-        } else {
-            // In all other cases, host artifacts just get flags from [host], regardless of
-            // --target. Or, phrased differently, no `--target` behaves the same as `--target
-            // <host>`, and host artifacts are always "special" (they don't pick up `RUSTFLAGS` for
-            // example).
-            return Ok(rustflags_from_host(config, flags, host_triple)?.unwrap_or_else(Vec::new));
+/// Whether `ft` is the import library or export file `file_types_for` adds
+/// for a Windows DLL.
+fn is_import_lib(ft: &FileType) -> bool {
+    ft.flavor == FileFlavor::Auxiliary
+        && matches!(ft.suffix.as_str(), ".dll.lib" | ".dll.exp" | ".dll.a")
+}
+
+/// Whether `rustflags` ask rustc to write assembly into the output directory
+/// with `--emit=asm`. An explicit path like `--emit=asm=foo.s` is not
+/// counted, since that file is not one Cargo manages.
+pub fn emits_asm(rustflags: &[String]) -> bool {
+    let mut args = rustflags.iter().map(String::as_str);
+    while let Some(arg) = args.next() {
+        let kinds = match arg {
+            "--emit" => args.next(),
+            _ => arg.strip_prefix("--emit="),
+        };
+        if let Some(kinds) = kinds {
+            if kinds.split(',').any(|kind| kind == "asm") {
+                return true;
+            }
         }
     }
+    false
+}
 
-    // All other artifacts pick up the RUSTFLAGS, [target.*], and [build], in that order.
-    // NOTE: It is impossible to have a [host] section and reach this logic with kind.is_host(),
-    // since [host] implies `target-applies-to-host = false`, which always early-returns above.
+/// The suffix the linker adds to the name of its output for a link map, if
+/// `rustflags` ask for one with `-Clink-arg=-Wl,-Map=%.map` or similar.
+///
+/// GNU ld replaces `%` with the path of the output file, and appends `.map`
+/// if nothing follows it. A map file with any other name is not tracked,
+/// since Cargo can't tell which unit it belongs to. The last `-Map` wins.
+fn linker_map_suffix(rustflags: &[String]) -> Option<String> {
+    let mut link_args = Vec::new();
+    for option in codegen_options(rustflags) {
+        if let Some(arg) = option.strip_prefix("link-arg=") {
+            link_args.push(arg);
+        } else if let Some(args) = option.strip_prefix("link-args=") {
+            link_args.extend(args.split_whitespace());
+        }
+    }
+    let mut linker_args = link_args
+        .into_iter()
+        .flat_map(|arg| match arg.strip_prefix("-Wl,") {
+            Some(args) => args.split(',').collect(),
+            None => vec![arg],
+        });
+    let mut suffix = None;
+    while let Some(arg) = linker_args.next() {
+        let path = match arg {
+            "-Map" | "--Map" => linker_args.next(),
+            _ => arg
+                .strip_prefix("-Map=")
+                .or_else(|| arg.strip_prefix("--Map=")),
+        };
+        if let Some(path) = path {
+            suffix = match path.strip_prefix('%') {
+                Some("") => Some(".map".to_string()),
+                Some(rest) => Some(rest.to_string()),
+                None => None,
+            };
+        }
+    }
+    suffix
+}
 
-    if let Some(rustflags) = rustflags_from_env(flags) {
-        Ok(rustflags)
-    } else if let Some(rustflags) =
-        rustflags_from_target(config, host_triple, target_cfg, kind, flags)?
-    {
-        Ok(rustflags)
-    } else if let Some(rustflags) = rustflags_from_build(config, flags)? {
-        Ok(rustflags)
-    } else {
-        Ok(Vec::new())
+/// Returns the `strip` setting rustc will actually use, given the one from
+/// the profile and the extra flags passed to rustc.
+///
+/// Cargo passes the profile setting before any rustflags, so the last
+/// `-Cstrip` in `rustflags` wins.
+pub fn effective_strip(profile_strip: Strip, rustflags: &[String]) -> Strip {
+    let mut strip = profile_strip;
+    for option in codegen_options(rustflags) {
+        if let Some(value) = option.strip_prefix("strip=") {
+            strip = match value {
+                "none" => Strip::None,
+                value => Strip::Named(InternedString::new(value)),
+            };
+        }
     }
+    strip
 }
 
-fn rustflags_from_env(flags: Flags) -> Option<Vec<String>> {
-    // First try CARGO_ENCODED_RUSTFLAGS from the environment.
-    // Prefer this over RUSTFLAGS since it's less prone to encoding errors.
-    if let Ok(a) = env::var(format!("CARGO_ENCODED_{}", flags.as_env())) {
-        if a.is_empty() {
-            return Some(Vec::new());
+/// Returns the `split-debuginfo` setting rustc will actually use, given the
+/// one Cargo passes for the profile and the extra flags passed to rustc.
+///
+/// `None` means rustc uses the target's default. The last
+/// `-Csplit-debuginfo` in `rustflags` wins.
+pub fn effective_split_debuginfo(
+    profile_split_debuginfo: Option<InternedString>,
+    rustflags: &[String],
+) -> Option<InternedString> {
+    let mut split_debuginfo = profile_split_debuginfo;
+    for option in codegen_options(rustflags) {
+        if let Some(value) = option.strip_prefix("split-debuginfo=") {
+            split_debuginfo = Some(InternedString::new(value));
         }
-        return Some(a.split('\x1f').map(str::to_string).collect());
     }
+    split_debuginfo
+}
 
-    // Then try RUSTFLAGS from the environment
-    if let Ok(a) = env::var(flags.as_env()) {
-        let args = a
-            .split(' ')
-            .map(str::trim)
-            .filter(|s| !s.is_empty())
-            .map(str::to_string);
-        return Some(args.collect());
+/// Returns the number of codegen units rustc will use, given the profile's
+/// `codegen-units` and the rustflags passed after it.
+///
+/// `None` means rustc picks the number itself. The last `-Ccodegen-units` in
+/// `rustflags` wins.
+pub fn effective_codegen_units(
+    profile_codegen_units: Option<u32>,
+    rustflags: &[String],
+) -> Option<u32> {
+    let mut codegen_units = profile_codegen_units;
+    for option in codegen_options(rustflags) {
+        if let Some(value) = option.strip_prefix("codegen-units=") {
+            codegen_units = value.parse().ok();
+        }
     }
+    codegen_units
+}
 
-    // No rustflags to be collected from the environment
-    None
+/// The overflow-checks setting rustc ends up using, given the profile's
+/// `overflow-checks` and the rustflags passed after it.
+///
+/// A `-Coverflow-checks` in the rustflags overrides the profile, and the last
+/// one wins.
+pub fn effective_overflow_checks(profile_overflow_checks: bool, rustflags: &[String]) -> bool {
+    codegen_switch("overflow-checks", profile_overflow_checks, rustflags)
 }
 
-fn rustflags_from_target(
-    config: &Config,
-    host_triple: &str,
-    target_cfg: Option<&[Cfg]>,
-    kind: CompileKind,
-    flag: Flags,
-) -> CargoResult<Option<Vec<String>>> {
-    let mut rustflags = Vec::new();
+/// The debug-assertions setting rustc ends up using, given the profile's
+/// `debug-assertions` and the rustflags passed after it.
+///
+/// This is what `cfg(debug_assertions)` evaluates to when compiling, which is
+/// not what `TargetInfo::cfg` reports since rustc is queried without any
+/// profile settings.
+pub fn effective_debug_assertions(profile_debug_assertions: bool, rustflags: &[String]) -> bool {
+    codegen_switch("debug-assertions", profile_debug_assertions, rustflags)
+}
 
-    // Then the target.*.rustflags value...
-    let target = match &kind {
-        CompileKind::Host => host_triple,
-        CompileKind::Target(target) => target.short_name(),
-    };
-    let key = format!("target.{}.{}", target, flag.as_key());
-    if let Some(args) = config.get::<Option<StringList>>(&key)? {
-        rustflags.extend(args.as_slice().iter().cloned());
-    }
-    // ...including target.'cfg(...)'.rustflags
-    if let Some(target_cfg) = target_cfg {
-        config
-            .target_cfgs()?
-            .iter()
-            .filter_map(|(key, cfg)| {
-                cfg.rustflags
-                    .as_ref()
-                    .map(|rustflags| (key, &rustflags.val))
-            })
-            .filter(|(key, _rustflags)| CfgExpr::matches_key(key, target_cfg))
-            .for_each(|(_key, cfg_rustflags)| {
-                rustflags.extend(cfg_rustflags.as_slice().iter().cloned());
-            });
+/// The value of the boolean codegen option `name` after applying `rustflags`
+/// on top of `default`. The last occurrence wins.
+fn codegen_switch(name: &str, default: bool, rustflags: &[String]) -> bool {
+    let mut enabled = default;
+    for option in codegen_options(rustflags) {
+        let value = match option.split_once('=') {
+            Some((key, value)) if key == name => value,
+            None if option == name => "yes",
+            _ => continue,
+        };
+        match value {
+            "y" | "yes" | "on" | "true" => enabled = true,
+            "n" | "no" | "off" | "false" => enabled = false,
+            // rustc rejects anything else.
+            _ => {}
+        }
     }
+    enabled
+}
 
-    if rustflags.is_empty() {
-        Ok(None)
-    } else {
-        Ok(Some(rustflags))
+/// The profile settings that `rustflags` also set, as the profile key (like
+/// `opt-level`), the last flag setting it (like `-Copt-level=3`), and the
+/// value that flag sets (like `3`).
+///
+/// Values are spelled the way Cargo passes them to rustc, so `-O` sets
+/// `opt-level` to `2`, and `-Clto` sets `lto` to `fat`.
+///
+/// Since the rustflags are passed after the flags for the profile, these
+/// flags take precedence over the profile.
+pub fn profile_settings_in_rustflags(rustflags: &[String]) -> Vec<(&'static str, String, String)> {
+    let mut found: Vec<(&'static str, String, String)> = Vec::new();
+    let mut record = |key: &'static str, flag: String, value: &str| {
+        found.retain(|(k, _, _)| *k != key);
+        found.push((key, flag, value.to_string()));
+    };
+    for (shorthand, option) in codegen_flags(rustflags) {
+        let (name, value) = option.split_once('=').unwrap_or((option, ""));
+        let (key, value) = match name {
+            "opt-level" => ("opt-level", value),
+            "debuginfo" => ("debug", value),
+            "lto" => match value {
+                "" | "y" | "yes" | "on" | "true" | "fat" => ("lto", "fat"),
+                "n" | "no" | "off" | "false" => ("lto", "off"),
+                _ => ("lto", value),
+            },
+            "codegen-units" => ("codegen-units", value),
+            "panic" => ("panic", value),
+            _ => continue,
+        };
+        let flag = shorthand.map_or_else(|| format!("-C{}", option), str::to_string);
+        record(key, flag, value);
     }
+    found
 }
 
-fn rustflags_from_host(
-    config: &Config,
-    flag: Flags,
-    host_triple: &str,
-) -> CargoResult<Option<Vec<String>>> {
-    let target_cfg = config.host_cfg_triple(host_triple)?;
-    let list = match flag {
-        Flags::Rust => &target_cfg.rustflags,
-        Flags::Rustdoc => {
-            // host.rustdocflags is not a thing, since it does not make sense
-            return Ok(None);
-        }
-    };
-    Ok(list.as_ref().map(|l| l.val.as_slice().to_vec()))
+/// The `key=value` arguments of every `-C`/`--codegen` option in `rustflags`,
+/// in order.
+fn codegen_options(rustflags: &[String]) -> impl Iterator<Item = &str> {
+    codegen_flags(rustflags)
+        .filter(|(shorthand, _)| shorthand.is_none())
+        .map(|(_, option)| option)
 }
 
-fn rustflags_from_build(config: &Config, flag: Flags) -> CargoResult<Option<Vec<String>>> {
-    // Then the `build.rustflags` value.
-    let build = config.build_config()?;
-    let list = match flag {
-        Flags::Rust => &build.rustflags,
-        Flags::Rustdoc => &build.rustdocflags,
-    };
-    Ok(list.as_ref().map(|l| l.as_slice().to_vec()))
+/// Like `codegen_options`, but also with `-O` and `-g` as the options they
+/// are short for, along with the shorthand itself.
+fn codegen_flags(rustflags: &[String]) -> impl Iterator<Item = (Option<&str>, &str)> {
+    let mut args = rustflags.iter().map(String::as_str);
+    std::iter::from_fn(move || loop {
+        let arg = args.next()?;
+        let option = match arg {
+            "-O" => return Some((Some(arg), "opt-level=2")),
+            "-g" => return Some((Some(arg), "debuginfo=2")),
+            "-C" | "--codegen" => args.next(),
+            _ => arg
+                .strip_prefix("-C")
+                .or_else(|| arg.strip_prefix("--codegen=")),
+        };
+        if let Some(option) = option {
+            return Some((None, option));
+        }
+    })
 }
 
-/// Collection of information about `rustc` and the host and target.
-pub struct RustcTargetData<'cfg> {
-    /// Information about `rustc` itself.
-    pub rustc: Rustc,
+/// Options of rustc and rustdoc that take their value as the next argument.
+const FLAGS_WITH_VALUE: &[&str] = &[
+    "-A",
+    "-C",
+    "-D",
+    "-F",
+    "-L",
+    "-W",
+    "-Z",
+    "-l",
+    "-o",
+    "--allow",
+    "--cap-lints",
+    "--cfg",
+    "--check-cfg",
+    "--codegen",
+    "--crate-name",
+    "--crate-type",
+    "--crate-version",
+    "--default-theme",
+    "--deny",
+    "--edition",
+    "--emit",
+    "--error-format",
+    "--extend-css",
+    "--extern",
+    "--extern-html-root-url",
+    "--forbid",
+    "--force-warn",
+    "--html-after-content",
+    "--html-before-content",
+    "--html-in-header",
+    "--index-page",
+    "--json",
+    "--markdown-css",
+    "--out-dir",
+    "--playground-url",
+    "--print",
+    "--remap-path-prefix",
+    "--resource-suffix",
+    "--static-root-path",
+    "--sysroot",
+    "--target",
+    "--theme",
+    "--warn",
+];
 
-    /// Config
-    config: &'cfg Config,
-    requested_kinds: Vec<CompileKind>,
+/// The short and long spelling of each lint level flag.
+const LINT_LEVEL_FLAGS: &[(&str, &str)] = &[
+    ("-A", "--allow"),
+    ("-W", "--warn"),
+    ("-D", "--deny"),
+    ("-F", "--forbid"),
+    ("--force-warn", "--force-warn"),
+];
 
-    /// Build information for the "host", which is information about when
-    /// `rustc` is invoked without a `--target` flag. This is used for
-    /// procedural macros, build scripts, etc.
-    host_config: TargetConfig,
-    host_info: TargetInfo,
+/// Codegen options for which rustc only uses the last value given.
+const SINGLE_VALUED_CODEGEN_OPTIONS: &[&str] = &[
+    "code-model",
+    "codegen-units",
+    "control-flow-guard",
+    "debug-assertions",
+    "debuginfo",
+    "default-linker-libraries",
+    "embed-bitcode",
+    "force-frame-pointers",
+    "force-unwind-tables",
+    "incremental",
+    "inline-threshold",
+    "instrument-coverage",
+    "link-dead-code",
+    "link-self-contained",
+    "linker",
+    "linker-flavor",
+    "lto",
+    "no-redzone",
+    "opt-level",
+    "overflow-checks",
+    "panic",
+    "prefer-dynamic",
+    "profile-generate",
+    "profile-use",
+    "relocation-model",
+    "relro-level",
+    "rpath",
+    "split-debuginfo",
+    "strip",
+    "symbol-mangling-version",
+    "target-cpu",
+];
 
-    /// Build information for targets that we're building for. This will be
-    /// empty if the `--target` flag is not passed.
-    target_config: HashMap<CompileTarget, TargetConfig>,
-    target_info: HashMap<CompileTarget, TargetInfo>,
+/// Removes flags that a later flag overrides, keeping the last occurrence.
+///
+/// Only these flags are removed, everything else is passed through as is:
+///
+/// * `-C` options that rustc only uses the last value of, even when the
+///   values differ, so `-Ctarget-cpu=a -C target-cpu=b` becomes
+///   `-C target-cpu=b`.
+/// * `--cfg` flags repeated with the same value.
+/// * Lint level flags, like `-D warnings`, repeated with the same level and
+///   lint.
+///
+/// An option and its separate value, like `--cfg foo`, count as one flag.
+fn dedup_flags(flags: Vec<String>) -> Vec<String> {
+    let mut groups = Vec::new();
+    let mut flags = flags.into_iter();
+    while let Some(flag) = flags.next() {
+        let takes_value = FLAGS_WITH_VALUE.contains(&flag.as_str());
+        let mut group = vec![flag];
+        if takes_value {
+            group.extend(flags.next());
+        }
+        groups.push(group);
+    }
+    let mut seen = HashSet::new();
+    let mut keep = vec![false; groups.len()];
+    for (i, group) in groups.iter().enumerate().rev() {
+        keep[i] = match dedup_key(group) {
+            Some(key) => seen.insert(key),
+            None => true,
+        };
+    }
+    groups
+        .into_iter()
+        .zip(keep)
+        .filter(|(_, keep)| *keep)
+        .flat_map(|(group, _)| group)
+        .collect()
 }
 
-impl<'cfg> RustcTargetData<'cfg> {
-    pub fn new(
-        ws: &Workspace<'cfg>,
-        requested_kinds: &[CompileKind],
-    ) -> CargoResult<RustcTargetData<'cfg>> {
-        let config = ws.config();
-        let rustc = config.load_global_rustc(Some(ws))?;
-        let mut target_config = HashMap::new();
-        let mut target_info = HashMap::new();
-        let target_applies_to_host = config.target_applies_to_host()?;
-        let host_info = TargetInfo::new(config, requested_kinds, &rustc, CompileKind::Host)?;
-        let host_config = if target_applies_to_host {
-            config.target_cfg_triple(&rustc.host)?
+/// What identifies a flag for `dedup_flags`, or `None` if it is never
+/// removed.
+fn dedup_key(group: &[String]) -> Option<String> {
+    let flag = group[0].as_str();
+    let value = group.get(1).map(String::as_str);
+    let option = match flag {
+        "-C" | "--codegen" => value,
+        _ => flag
+            .strip_prefix("-C")
+            .or_else(|| flag.strip_prefix("--codegen=")),
+    };
+    if let Some(option) = option {
+        let name = option.split_once('=').map_or(option, |(name, _)| name);
+        return SINGLE_VALUED_CODEGEN_OPTIONS
+            .contains(&name)
+            .then(|| format!("-C {}", name));
+    }
+    let cfg = match flag {
+        "--cfg" => value,
+        _ => flag.strip_prefix("--cfg="),
+    };
+    if let Some(cfg) = cfg {
+        return Some(format!("--cfg {}", cfg));
+    }
+    for &(short, long) in LINT_LEVEL_FLAGS {
+        let lint = if flag == short || flag == long {
+            value
+        } else if let Some(lint) = flag
+            .strip_prefix(long)
+            .and_then(|rest| rest.strip_prefix('='))
+        {
+            Some(lint)
+        } else if short.len() == 2 {
+            flag.strip_prefix(short).filter(|lint| !lint.is_empty())
         } else {
-            config.host_cfg_triple(&rustc.host)?
-        };
-
-        // This is a hack. The unit_dependency graph builder "pretends" that
-        // `CompileKind::Host` is `CompileKind::Target(host)` if the
-        // `--target` flag is not specified. Since the unit_dependency code
-        // needs access to the target config data, create a copy so that it
-        // can be found. See `rebuild_unit_graph_shared` for why this is done.
-        if requested_kinds.iter().any(CompileKind::is_host) {
-            let ct = CompileTarget::new(&rustc.host)?;
-            target_info.insert(ct, host_info.clone());
-            target_config.insert(ct, config.target_cfg_triple(&rustc.host)?);
+            None
         };
+        if let Some(lint) = lint {
+            return Some(format!("{} {}", short, lint));
+        }
+    }
+    None
+}
 
-        let mut res = RustcTargetData {
-            rustc,
-            config,
-            requested_kinds: requested_kinds.into(),
-            host_config,
-            host_info,
-            target_config,
-            target_info,
+/// The directories of the `-L` flags in `rustflags`, with any `native=`,
+/// `crate=`, `dependency=`, `framework=` or `all=` kind removed.
+fn link_search_dirs(rustflags: &[String]) -> impl Iterator<Item = &str> {
+    let mut args = rustflags.iter().map(String::as_str);
+    std::iter::from_fn(move || loop {
+        let arg = args.next()?;
+        let dir = match arg {
+            "-L" => args.next(),
+            _ => arg.strip_prefix("-L"),
         };
-
-        // Get all kinds we currently know about.
-        //
-        // For now, targets can only ever come from the root workspace
-        // units and artifact dependencies, so this
-        // correctly represents all the kinds that can happen. When we have
-        // other ways for targets to appear at places that are not the root units,
-        // we may have to revisit this.
-        fn artifact_targets(package: &Package) -> impl Iterator<Item = CompileKind> + '_ {
-            package
-                .manifest()
-                .dependencies()
-                .iter()
-                .filter_map(|d| d.artifact()?.target()?.to_compile_kind())
-        }
-        let all_kinds = requested_kinds
-            .iter()
-            .copied()
-            .chain(ws.members().flat_map(|p| {
-                p.manifest()
-                    .default_kind()
-                    .into_iter()
-                    .chain(p.manifest().forced_kind())
-                    .chain(artifact_targets(p))
-            }));
-        for kind in all_kinds {
-            res.merge_compile_kind(kind)?;
+        if let Some(dir) = dir {
+            return Some(match dir.split_once('=') {
+                Some(("native" | "crate" | "dependency" | "framework" | "all", path)) => path,
+                _ => dir,
+            });
         }
+    })
+}
 
-        Ok(res)
+/// The extension of the main `staticlib` file in `crate_types`, without the
+/// leading dot, or an empty string if `staticlib` is not supported.
+///
+/// `staticlib` is always probed in `new`, so it never has to be discovered.
+fn static_lib_extension(crate_types: &HashMap<CrateType, Option<Vec<(String, String)>>>) -> String {
+    match crate_types.get(&CrateType::Staticlib) {
+        Some(Some(names)) => names[0].1.trim_start_matches('.').to_string(),
+        _ => String::new(),
     }
+}
 
-    /// Insert `kind` into our `target_info` and `target_config` members if it isn't present yet.
-    fn merge_compile_kind(&mut self, kind: CompileKind) -> CargoResult<()> {
-        if let CompileKind::Target(target) = kind {
-            if !self.target_config.contains_key(&target) {
-                self.target_config
-                    .insert(target, self.config.target_cfg_triple(target.short_name())?);
+/// Whether `target_os` is an ELF platform whose rustc target specs enable
+/// both PIE and full RELRO by default.
+///
+/// Only used when the target spec isn't available.
+fn is_hardened_elf_os(target_os: Option<&str>) -> bool {
+    matches!(
+        target_os,
+        Some("linux" | "android" | "freebsd" | "netbsd" | "openbsd" | "dragonfly" | "fuchsia")
+    )
+}
+
+/// Applies the PIE related options in `rustflags` to the target default.
+/// The last option wins.
+fn pie_with_rustflags(target_default: bool, rustflags: &[String]) -> bool {
+    let mut pie = target_default;
+    for option in codegen_options(rustflags) {
+        if let Some(model) = option.strip_prefix("relocation-model=") {
+            // Executables can only be PIE when the code is position
+            // independent, and rustc only makes them PIE for `pic` if the
+            // target asks for it.
+            pie = match model {
+                "pie" => true,
+                "pic" => target_default,
+                _ => false,
+            };
+        } else if let Some(arg) = option.strip_prefix("link-arg=") {
+            if is_no_pie_link_arg(arg) {
+                pie = false;
             }
-            if !self.target_info.contains_key(&target) {
-                self.target_info.insert(
-                    target,
-                    TargetInfo::new(self.config, &self.requested_kinds, &self.rustc, kind)?,
-                );
+        } else if let Some(args) = option.strip_prefix("link-args=") {
+            if args.split_whitespace().any(is_no_pie_link_arg) {
+                pie = false;
             }
         }
-        Ok(())
     }
+    pie
+}
 
-    /// Returns a "short" name for the given kind, suitable for keying off
-    /// configuration in Cargo or presenting to users.
-    pub fn short_name<'a>(&'a self, kind: &'a CompileKind) -> &'a str {
-        match kind {
-            CompileKind::Host => &self.rustc.host,
-            CompileKind::Target(target) => target.short_name(),
-        }
+/// Whether `arg`, passed to the linker by `-Clink-arg`, turns PIE off. This
+/// covers `-no-pie` and OpenBSD's `-nopie` for the compiler driver, as well as
+/// the same flags forwarded to the linker itself with `-Wl,`.
+fn is_no_pie_link_arg(arg: &str) -> bool {
+    let is_no_pie = |arg: &str| matches!(arg, "-no-pie" | "--no-pie" | "-nopie");
+    match arg.strip_prefix("-Wl,") {
+        Some(args) => args.split(',').any(is_no_pie),
+        None => is_no_pie(arg),
     }
+}
 
-    /// Whether a dependency should be compiled for the host or target platform,
-    /// specified by `CompileKind`.
-    pub fn dep_platform_activated(&self, dep: &Dependency, kind: CompileKind) -> bool {
-        // If this dependency is only available for certain platforms,
-        // make sure we're only enabling it for that platform.
-        let platform = match dep.platform() {
-            Some(p) => p,
-            None => return true,
-        };
-        let name = self.short_name(&kind);
-        platform.matches(name, self.cfg(kind))
+/// Reads whether executables are position independent out of the output of
+/// `--print=target-spec-json`.
+fn spec_pie(spec: &serde_json::Value) -> Option<bool> {
+    let spec = spec.as_object()?;
+    if spec.get("relocation-model").and_then(|m| m.as_str()) == Some("static") {
+        return Some(false);
     }
+    Some(
+        spec.get("position-independent-executables")
+            .and_then(|pie| pie.as_bool())
+            .unwrap_or(false),
+    )
+}
 
-    /// Gets the list of `cfg`s printed out from the compiler for the specified kind.
-    pub fn cfg(&self, kind: CompileKind) -> &[Cfg] {
-        self.info(kind).cfg()
+/// Reads whether executables are linked with full RELRO out of the output of
+/// `--print=target-spec-json`.
+fn spec_full_relro(spec: &serde_json::Value) -> Option<bool> {
+    let spec = spec.as_object()?;
+    Some(spec.get("relro-level").and_then(|level| level.as_str()) == Some("full"))
+}
+
+/// Reads `max-atomic-width` out of the output of `--print=target-spec-json`.
+///
+/// This is `None` when the spec leaves it unset, in which case rustc uses the
+/// pointer width.
+fn spec_max_atomic_width(spec: &serde_json::Value) -> Option<u32> {
+    let width = spec.get("max-atomic-width")?.as_u64()?;
+    u32::try_from(width).ok()
+}
+
+/// Reads `data-layout` out of the output of `--print=target-spec-json`.
+fn spec_data_layout(spec: &serde_json::Value) -> Option<String> {
+    Some(spec.get("data-layout")?.as_str()?.to_string())
+}
+
+/// Reads `panic-strategy` out of a target spec, which rustc defaults to
+/// `unwind`.
+fn spec_panic_strategy(spec: &serde_json::Value) -> PanicStrategy {
+    match spec.get("panic-strategy").and_then(|s| s.as_str()) {
+        Some("abort") => PanicStrategy::Abort,
+        _ => PanicStrategy::Unwind,
     }
+}
 
-    /// Information about the given target platform, learned by querying rustc.
-    pub fn info(&self, kind: CompileKind) -> &TargetInfo {
-        match kind {
-            CompileKind::Host => &self.host_info,
-            CompileKind::Target(s) => &self.target_info[&s],
-        }
+/// The largest width in bits of the `target_has_atomic` cfgs, with `ptr`
+/// taken as `target_pointer_width`.
+fn max_atomic_width_from_cfg(cfg: &[Cfg]) -> Option<u32> {
+    atomic_widths_from_cfg(cfg).last().copied()
+}
+
+/// The widths in bits of the `target_has_atomic` cfgs, sorted and without
+/// duplicates, with `ptr` taken as `target_pointer_width`.
+fn atomic_widths_from_cfg(cfg: &[Cfg]) -> Vec<u32> {
+    fn values<'a>(cfg: &'a [Cfg], key: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        cfg.iter().filter_map(move |cfg| match cfg {
+            Cfg::KeyPair(k, v) if k == key => Some(v.as_str()),
+            _ => None,
+        })
     }
+    let pointer_width = values(cfg, "target_pointer_width").next();
+    values(cfg, "target_has_atomic")
+        .filter_map(|width| match width {
+            "ptr" => pointer_width,
+            width => Some(width),
+        })
+        .filter_map(|width| width.parse().ok())
+        .collect::<BTreeSet<u32>>()
+        .into_iter()
+        .collect()
+}
 
-    /// Gets the target configuration for a particular host or target.
-    pub fn target_config(&self, kind: CompileKind) -> &TargetConfig {
-        match kind {
-            CompileKind::Host => &self.host_config,
-            CompileKind::Target(s) => &self.target_config[&s],
-        }
+fn strips_symbols(strip: Strip) -> bool {
+    strip == Strip::Named(InternedString::new("symbols"))
+}
+
+/// Checks that `flavor` makes sense for the primary output of `crate_type`.
+///
+/// Library crate types are always `Linkable`, everything else is `Normal`.
+/// `.rmeta` files are not produced by `file_types` at all, see
+/// [`FileType::new_rmeta`].
+fn check_flavor(crate_type: &CrateType, flavor: &FileFlavor) -> CargoResult<()> {
+    let valid = match flavor {
+        FileFlavor::Linkable => crate_type.is_linkable(),
+        FileFlavor::Normal => !crate_type.is_linkable(),
+        FileFlavor::Auxiliary | FileFlavor::Rmeta | FileFlavor::DebugInfo => false,
+    };
+    if valid {
+        Ok(())
+    } else {
+        Err(crate::util::internal(format!(
+            "file flavor {:?} is not valid for crate type `{}`",
+            flavor, crate_type
+        )))
     }
+}
 
-    /// If a build script is overridden, this returns the `BuildOutput` to use.
-    ///
-    /// `lib_name` is the `links` library name and `kind` is whether it is for
-    /// Host or Target.
-    pub fn script_override(&self, lib_name: &str, kind: CompileKind) -> Option<&BuildOutput> {
-        self.target_config(kind).links_overrides.get(lib_name)
+/// Runs `crate_type_process` with only `crate_type`, so every file name rustc
+/// reports belongs to it, and parses them with `parse_crate_type`.
+///
+/// The time taken is logged along with the command.
+fn probe_crate_type(
+    crate_type_process: &ProcessBuilder,
+    output_cache: Option<&(RustcOutputCache, u64)>,
+    crate_type: &CrateType,
+    probe_trace: Option<&ProbeTrace>,
+) -> CargoResult<Option<Vec<(String, String)>>> {
+    let mut process = crate_type_process.clone();
+
+    process.arg("--crate-type").arg(crate_type.as_str());
+
+    let start = Instant::now();
+    // Failed runs are cached too and come back as the same `ProcessError`,
+    // while an unsupported crate type is still left in `stderr` for
+    // `parse_crate_type` to recognize.
+    let output = match output_cache {
+        Some((cache, extra_fingerprint)) => cache.cached_output(&process, *extra_fingerprint),
+        None => process.exec_with_output().map(|output| {
+            (
+                String::from_utf8_lossy(&output.stdout).into_owned(),
+                String::from_utf8_lossy(&output.stderr).into_owned(),
+            )
+        }),
+    };
+    if let Some(trace) = probe_trace {
+        let what = format!("crate-type {}", crate_type);
+        let output = output
+            .as_ref()
+            .map(|(out, err)| (out.as_str(), err.as_str()));
+        trace.record_probe(&what, &process, output)?;
+    }
+    let (output, error) = output.with_context(|| {
+        format!(
+            "failed to run `rustc` to learn about crate-type {} information",
+            crate_type
+        )
+    })?;
+    log::debug!(
+        "probing rustc for crate-type {} took {}: {}",
+        crate_type,
+        util::elapsed(start.elapsed()),
+        process
+    );
+
+    // Only `--print=file-names` is asked for, so no output at all means rustc
+    // dropped the crate type, whatever it printed to stderr about it.
+    if output.trim().is_empty() {
+        return Ok(None);
     }
+    let files = output
+        .lines()
+        .filter(|line| is_file_name_line(line))
+        .count();
+    parse_crate_type(
+        crate_type,
+        &process,
+        &output,
+        &error,
+        &mut output.lines(),
+        files.max(1),
+    )
 }
 
-/// Structure used to deal with Rustdoc fingerprinting
-#[derive(Debug, Serialize, Deserialize)]
-pub struct RustDocFingerprint {
-    pub rustc_vv: String,
+/// Whether rustc's `stderr` says it can't produce `crate_type`.
+///
+/// This also matches the JSON diagnostics of `--error-format=json` in
+/// rustflags, which embed the same message. Where the wording doesn't match,
+/// the crate type is still found to be unsupported by `probe_crate_type`,
+/// since rustc prints no file name for it.
+fn crate_type_unsupported(crate_type: &CrateType, error: &str) -> bool {
+    error.lines().any(|line| {
+        (line.contains("unsupported crate type") || line.contains("unknown crate type"))
+            && line.contains(&format!("crate type `{}`", crate_type))
+    })
 }
 
-impl RustDocFingerprint {
-    /// This function checks whether the latest version of `Rustc` used to compile this
-    /// `Workspace`'s docs was the same as the one is currently being used in this `cargo doc`
-    /// call.
-    ///
-    /// In case it's not, it takes care of removing the `doc/` folder as well as overwriting
-    /// the rustdoc fingerprint info in order to guarantee that we won't end up with mixed
-    /// versions of the `js/html/css` files that `rustdoc` autogenerates which do not have
-    /// any versioning.
-    pub fn check_rustdoc_fingerprint(cx: &Context<'_, '_>) -> CargoResult<()> {
-        if cx.bcx.config.cli_unstable().skip_rustdoc_fingerprint {
-            return Ok(());
+/// Whether a line of `--print=file-names` output is a file name for the
+/// `___` crate, rather than the sysroot or a cfg that follows the names.
+fn is_file_name_line(line: &str) -> bool {
+    line.contains("___") && !line.contains(|c| c == '/' || c == '\\')
+}
+
+/// Works out which of the `file_names` rustc printed for `KNOWN_CRATE_TYPES`
+/// belong to each of them, by the shape of the names.
+///
+/// rustc usually reports one file per crate type, but may report several, like
+/// `.js` and `.wasm` for an emscripten bin, or drop a crate type without the
+/// message `crate_type_unsupported` looks for. So each crate type takes the
+/// lines that look like its files: any number for `bin`, and one for the
+/// others. The result lists how many lines each crate type takes, in order,
+/// or `None` for one that has to be probed on its own with `probe_crate_type`.
+///
+/// When a crate type finds no line of its own, or some lines are left over,
+/// the library types can't be told apart, since for example a dylib and a
+/// cdylib have the same names. They are all probed on their own then, while
+/// `bin` and `rlib` keep their lines as long as the `rlib` one was found, which
+/// is where the files of `bin` end.
+fn attribute_file_names(file_names: &[&str], error: &str) -> Vec<Option<usize>> {
+    let mut attributed = Vec::with_capacity(KNOWN_CRATE_TYPES.len());
+    let mut rest = file_names;
+    for crate_type in KNOWN_CRATE_TYPES {
+        if crate_type_unsupported(crate_type, error) {
+            attributed.push(Some(0));
+            continue;
         }
-        let actual_rustdoc_target_data = RustDocFingerprint {
-            rustc_vv: cx.bcx.rustc().verbose_version.clone(),
+        let fitting = rest
+            .iter()
+            .take_while(|line| file_name_fits(crate_type, line))
+            .count();
+        let files = match crate_type {
+            CrateType::Bin => fitting,
+            _ => fitting.min(1),
         };
+        rest = &rest[files..];
+        attributed.push(Some(files).filter(|&files| files > 0));
+    }
+    if rest.is_empty() && attributed.iter().all(Option::is_some) {
+        return attributed;
+    }
+    let rlib_found = KNOWN_CRATE_TYPES
+        .iter()
+        .zip(&attributed)
+        .any(|(crate_type, files)| matches!(crate_type, CrateType::Rlib) && files.is_some());
+    KNOWN_CRATE_TYPES
+        .iter()
+        .zip(attributed)
+        .map(|(crate_type, files)| match crate_type {
+            CrateType::Bin | CrateType::Rlib if rlib_found => files,
+            // Unsupported crate types have nothing to probe.
+            _ if files == Some(0) => files,
+            _ => None,
+        })
+        .collect()
+}
 
-        let fingerprint_path = cx.files().host_root().join(".rustdoc_fingerprint.json");
-        let write_fingerprint = || -> CargoResult<()> {
-            paths::write(
-                &fingerprint_path,
-                serde_json::to_string(&actual_rustdoc_target_data)?,
-            )
-        };
-        let rustdoc_data = match paths::read(&fingerprint_path) {
-            Ok(rustdoc_data) => rustdoc_data,
-            // If the fingerprint does not exist, do not clear out the doc
-            // directories. Otherwise this ran into problems where projects
-            // like rustbuild were creating the doc directory before running
-            // `cargo doc` in a way that deleting it would break it.
-            Err(_) => return write_fingerprint(),
-        };
-        match serde_json::from_str::<RustDocFingerprint>(&rustdoc_data) {
-            Ok(fingerprint) => {
-                if fingerprint.rustc_vv == actual_rustdoc_target_data.rustc_vv {
-                    return Ok(());
-                } else {
-                    log::debug!(
-                        "doc fingerprint changed:\noriginal:\n{}\nnew:\n{}",
-                        fingerprint.rustc_vv,
-                        actual_rustdoc_target_data.rustc_vv
-                    );
-                }
-            }
-            Err(e) => {
-                log::debug!("could not deserialize {:?}: {}", fingerprint_path, e);
+/// Whether a line of `--print=file-names` output looks like the name of a
+/// file of `crate_type` on some platform.
+fn file_name_fits(crate_type: &CrateType, line: &str) -> bool {
+    let (prefix, suffix) = match line.trim().split_once("___") {
+        Some(split) => split,
+        None => return false,
+    };
+    match crate_type {
+        CrateType::Bin => prefix.is_empty() && suffix != ".rlib",
+        CrateType::Rlib => prefix == "lib" && suffix == ".rlib",
+        CrateType::Staticlib => matches!(suffix, ".a" | ".lib"),
+        _ => suffix.starts_with('.') && !matches!(suffix, ".rlib" | ".a" | ".lib"),
+    }
+}
+
+/// Finds the `--print=sysroot` line among the lines that follow the file
+/// names, returning it and the other lines, which are the `--print=cfg` ones.
+///
+/// This goes by content rather than position, so an extra line printed by a
+/// newer rustc is not mistaken for the sysroot: the sysroot is the first
+/// absolute path, wherever it is. If no line is an absolute path, the first
+/// line is taken, since that is where rustc prints the sysroot.
+fn split_sysroot<'a>(lines: impl Iterator<Item = &'a str>) -> Option<(&'a str, Vec<&'a str>)> {
+    let mut lines: Vec<&str> = lines.collect();
+    if lines.is_empty() {
+        return None;
+    }
+    let pos = lines
+        .iter()
+        .position(|line| Path::new(line).is_absolute())
+        .unwrap_or(0);
+    let sysroot = lines.remove(pos);
+    Some((sysroot, lines))
+}
+
+/// Parses the lines of `--print=cfg` output.
+///
+/// A line that isn't a valid `cfg` is an error, unless it has spaces outside
+/// of a quoted value, which a `cfg` never does. Those are taken to be
+/// informational lines from a newer rustc, and skipped.
+fn parse_cfg_lines(lines: &[&str]) -> CargoResult<Vec<Cfg>> {
+    let mut cfgs = Vec::with_capacity(lines.len());
+    for line in lines {
+        match Cfg::from_str(line) {
+            Ok(cfg) => cfgs.push(cfg),
+            Err(_) if line.trim().contains(char::is_whitespace) => {
+                log::debug!("ignoring line of rustc --print=cfg output: {}", line);
             }
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(cfgs)
+}
+
+/// Takes rustc output (using specialized command line args), and calculates the prefix and
+/// suffix of each file reported for the given crate type, or returns `None` if the type is not
+/// supported. (e.g., for a Rust library like `libcargo.rlib`, we have prefix "lib" and suffix
+/// "rlib"). The main file comes first.
+///
+/// `files` is how many lines rustc printed for this crate type, usually one, but for example
+/// two for a wasm32-unknown-emscripten bin (`.js` and `.wasm`) with some versions of rustc. The
+/// caller needs to ensure that the lines object is at the correct line for the given crate type:
+/// this is not checked.
+fn parse_crate_type(
+    crate_type: &CrateType,
+    cmd: &ProcessBuilder,
+    output: &str,
+    error: &str,
+    lines: &mut str::Lines<'_>,
+    files: usize,
+) -> CargoResult<Option<Vec<(String, String)>>> {
+    if crate_type_unsupported(crate_type, error) {
+        return Ok(None);
+    }
+    let mut names = Vec::with_capacity(files);
+    for _ in 0..files {
+        let line = match lines.next() {
+            Some(line) => line,
+            None => anyhow::bail!(
+                "malformed output when learning about crate-type {} information\n{}",
+                crate_type,
+                output_err_info(cmd, output, error)
+            ),
         };
-        // Fingerprint does not match, delete the doc directories and write a new fingerprint.
-        log::debug!(
-            "fingerprint {:?} mismatch, clearing doc directories",
-            fingerprint_path
-        );
-        cx.bcx
-            .all_kinds
+        let mut parts = line.trim().split("___");
+        let prefix = parts.next().unwrap();
+        let suffix = match parts.next() {
+            Some(part) => part,
+            None => anyhow::bail!(
+                "output of --print=file-names has changed in the compiler, cannot parse\n{}",
+                output_err_info(cmd, output, error)
+            ),
+        };
+        names.push((prefix.to_string(), suffix.to_string()));
+    }
+
+    Ok(Some(names))
+}
+
+/// The cfgs Cargo uses for a target out of everything rustc printed: without
+/// `proc_macro` (see `TargetInfo::not_user_specific_cfg`) and with only the
+/// first of any duplicates, which are returned separately.
+fn target_cfgs(raw_cfg: &[Cfg]) -> (Vec<Cfg>, Vec<Cfg>) {
+    dedup_cfgs(
+        raw_cfg
             .iter()
-            .map(|kind| cx.files().layout(*kind).doc())
-            .filter(|path| path.exists())
-            .try_for_each(|path| clean_doc(path))?;
-        write_fingerprint()?;
-        return Ok(());
+            .filter(|cfg| TargetInfo::not_user_specific_cfg(cfg))
+            .cloned()
+            .collect(),
+    )
+}
 
-        fn clean_doc(path: &Path) -> CargoResult<()> {
-            let entries = path
-                .read_dir()
-                .with_context(|| format!("failed to read directory `{}`", path.display()))?;
-            for entry in entries {
-                let entry = entry?;
-                // Don't remove hidden files. Rustdoc does not create them,
-                // but the user might have.
-                if entry
-                    .file_name()
-                    .to_str()
-                    .map_or(false, |name| name.starts_with('.'))
-                {
-                    continue;
-                }
-                let path = entry.path();
-                if entry.file_type()?.is_dir() {
-                    paths::remove_dir_all(path)?;
-                } else {
-                    paths::remove_file(path)?;
-                }
-            }
-            Ok(())
+/// Removes repeated entries from a cfg list, keeping the first occurrence of
+/// each. Returns the deduplicated list and the entries that were removed.
+fn dedup_cfgs(cfgs: Vec<Cfg>) -> (Vec<Cfg>, Vec<Cfg>) {
+    let mut seen = HashSet::new();
+    let mut duplicates = Vec::new();
+    let mut result = Vec::with_capacity(cfgs.len());
+    for cfg in cfgs {
+        if seen.contains(&cfg) {
+            duplicates.push(cfg);
+        } else {
+            seen.insert(cfg.clone());
+            result.push(cfg);
         }
     }
+    (result, duplicates)
+}
+
+/// Lets Cargo's own test suite pretend that the host is a different triple,
+/// to exercise the host/target handling in `env_args` and
+/// `dep_platform_activated` without a cross toolchain. The host is still
+/// probed with the real rustc, only its name changes.
+///
+/// This is ignored outside of the test suite.
+fn test_host_override() -> Option<InternedString> {
+    env::var_os("__CARGO_TEST_ROOT")?;
+    let host = env::var("__CARGO_TEST_HOST_OVERRIDE_DO_NOT_USE_THIS").ok()?;
+    Some(InternedString::new(&host))
+}
+
+/// The directory, relative to the target directory, that holds the output
+/// for `kind`. This matches the layout created by `Layout::new`.
+fn kind_dir(kind: &CompileKind) -> PathBuf {
+    match kind {
+        CompileKind::Host => PathBuf::new(),
+        CompileKind::Target(target) => PathBuf::from(target.short_name()),
+    }
+}
+
+/// The name of `kind` as used in `target.<triple>` config keys.
+fn short_name_of<'a>(rustc: &'a Rustc, kind: &'a CompileKind) -> &'a str {
+    match kind {
+        CompileKind::Host => rustc.host.as_str(),
+        CompileKind::Target(target) => target.short_name(),
+    }
+}
+
+/// Checks the sysroot from `build.sysroot`, which replaces the one reported
+/// by rustc, for toolchains whose standard library is installed elsewhere.
+fn sysroot_override(sysroot: PathBuf) -> CargoResult<PathBuf> {
+    if !sysroot.is_dir() {
+        anyhow::bail!(
+            "the sysroot `{}` from `build.sysroot` does not exist",
+            sysroot.display()
+        );
+    }
+    if !sysroot.join("lib").join("rustlib").is_dir() {
+        anyhow::bail!(
+            "the sysroot `{}` from `build.sysroot` has no `lib/rustlib` directory\n\
+             It should be laid out like the directory printed by `rustc --print=sysroot`.",
+            sysroot.display()
+        );
+    }
+    Ok(sysroot)
+}
+
+/// Warns that building for `target` is likely to fail, when `libdir` has no
+/// prebuilt standard library and it can't be built with `-Zbuild-std`.
+fn warn_missing_std(config: &Config, target: CompileTarget, libdir: &Path) -> CargoResult<()> {
+    if config.nightly_features_allowed
+        || config.cli_unstable().build_std.is_some()
+        || has_prebuilt_std(libdir)
+    {
+        return Ok(());
+    }
+    config.shell().warn(format!(
+        "the standard library for target `{}` was not found in `{}`\n\
+         This target may not have a prebuilt standard library on the stable \
+         channel, so building for it is likely to fail. Try installing it with \
+         `rustup target add {}`, building the standard library on nightly \
+         with `-Zbuild-std`, or using a different target.",
+        target.short_name(),
+        libdir.display(),
+        target.rustc_target(),
+    ))
+}
+
+/// Whether `libdir` contains a prebuilt `std` or `core` rlib.
+///
+/// `no_std` targets only ship `core`, which is enough for them to be usable.
+fn has_prebuilt_std(libdir: &Path) -> bool {
+    has_prebuilt_rlib(libdir, &["std", "core"])
+}
+
+/// Whether `libdir` contains a prebuilt rlib of any of the `crates`.
+fn has_prebuilt_rlib(libdir: &Path, crates: &[&str]) -> bool {
+    let entries = match fs::read_dir(libdir) {
+        Ok(entries) => entries,
+        Err(_) => return false,
+    };
+    entries.filter_map(|e| e.ok()).any(|e| {
+        let name = e.file_name();
+        let name = name.to_string_lossy();
+        crates.iter().any(|krate| {
+            name.strip_prefix("lib")
+                .and_then(|name| name.strip_prefix(krate))
+                .map_or(false, |rest| {
+                    rest.starts_with('-') && rest.ends_with(".rlib")
+                })
+        })
+    })
+}
+
+/// Guesses from the cfgs of a target whether it supports `std`, which is the
+/// case for anything with an operating system, as well as for wasm.
+fn cfg_implies_std(cfg: &[Cfg]) -> bool {
+    let value = |key: &str| {
+        cfg.iter().find_map(|cfg| match cfg {
+            Cfg::KeyPair(k, v) if k == key => Some(v.as_str()),
+            _ => None,
+        })
+    };
+    match value("target_os") {
+        None | Some("" | "none" | "cuda") => value("target_family").is_some(),
+        Some(_) => true,
+    }
+}
+
+/// Checks the prefix and suffix rustc reported for a crate type for results
+/// that cannot be right on any platform, returning a description of the
+/// problem if there is one.
+///
+/// This catches wrappers that interfere with `--print=file-names` and changes
+/// in rustc's output before they turn into nonsensical file names.
+fn crate_type_name_anomaly(
+    crate_type: &CrateType,
+    prefix: &str,
+    suffix: &str,
+) -> Option<&'static str> {
+    let bad_char = |c: char| c.is_whitespace() || c == '/' || c == '\\';
+    if prefix.contains(bad_char) || suffix.contains(bad_char) {
+        return Some("the name contains whitespace or a path separator");
+    }
+    if suffix.is_empty() && !matches!(crate_type, CrateType::Bin) {
+        // Every library type has an extension on every platform, only
+        // executables may lack one.
+        return Some("the suffix is empty");
+    }
+    if !suffix.is_empty() && !suffix.starts_with('.') {
+        return Some("the suffix does not start with `.`");
+    }
+    None
+}
+
+/// Helper for creating an error message when parsing rustc output fails.
+fn output_err_info(cmd: &ProcessBuilder, stdout: &str, stderr: &str) -> String {
+    let mut result = format!("command was: {}\n", cmd);
+    if !stdout.is_empty() {
+        result.push_str("\n--- stdout\n");
+        result.push_str(stdout);
+    }
+    if !stderr.is_empty() {
+        result.push_str("\n--- stderr\n");
+        result.push_str(stderr);
+    }
+    if stdout.is_empty() && stderr.is_empty() {
+        result.push_str("(no output received)");
+    }
+    result
+}
+
+/// If `rustc` is run through a wrapper for probing, returns a hint that the
+/// wrapper may be the reason its output could not be understood.
+fn wrapper_hint(rustc: &Rustc, bypass_wrapper: bool) -> Option<String> {
+    if bypass_wrapper {
+        return None;
+    }
+    let wrapper = rustc
+        .wrapper
+        .as_ref()
+        .or(rustc.workspace_wrapper.as_ref())?;
+    Some(format!(
+        "`rustc` was run through the wrapper `{}`, which may have changed its output\n\
+         Try setting `build.probe-bypass-wrapper = true` with `-Zprobe-bypass-wrapper` \
+         to query `rustc` directly.",
+        wrapper.display()
+    ))
+}
+
+#[derive(Debug, Copy, Clone)]
+enum Flags {
+    Rust,
+    Rustdoc,
+}
+
+impl Flags {
+    fn as_key(self) -> &'static str {
+        match self {
+            Flags::Rust => "rustflags",
+            Flags::Rustdoc => "rustdocflags",
+        }
+    }
+
+    fn as_env(self) -> &'static str {
+        match self {
+            Flags::Rust => "RUSTFLAGS",
+            Flags::Rustdoc => "RUSTDOCFLAGS",
+        }
+    }
+}
+
+/// Acquire extra flags to pass to the compiler from various locations.
+///
+/// The locations are:
+///
+///  - the `CARGO_ENCODED_RUSTFLAGS` environment variable
+///  - the `RUSTFLAGS` environment variable
+///
+/// then if none of those were found
+///
+///  - `target.*.rustflags` from the config (.cargo/config)
+///  - the output of `target.*.rustflags-command` from the config
+///  - `target.*.rustflags-when-building.*` from the config, for every other
+///    target in `requested_kinds`, with `-Zrustflags-when-building`
+///  - `target.cfg(..).rustflags` from the config
+///  - `host.*.rustflags` from the config if compiling a host artifact or without `--target`
+///
+/// then if none of those were found
+///
+///  - `build.rustflags` from the config
+///
+/// With `build.rustflags-merge = true` and `-Zrustflags-merge`, all of these
+/// sources are used instead of only the first one found, in the same order.
+/// With `build.rustflags-dedup = true` and `-Zrustflags-dedup`, the result then
+/// goes through `dedup_flags`.
+///
+/// The behavior differs slightly when cross-compiling (or, specifically, when `--target` is
+/// provided) for artifacts that are always built for the host (plugins, build scripts, ...).
+/// For those artifacts, _only_ `host.*.rustflags` is respected, and no other configuration
+/// sources, _regardless of the value of `target-applies-to-host`_. This is counterintuitive, but
+/// necessary to retain bacwkards compatibility with older versions of Cargo.
+fn env_args(
+    config: &Config,
+    requested_kinds: &[CompileKind],
+    host_triple: &str,
+    target_cfg: Option<&[Cfg]>,
+    kind: CompileKind,
+    flags: Flags,
+) -> CargoResult<Vec<String>> {
+    let args = env_args_from_sources(
+        config,
+        requested_kinds,
+        host_triple,
+        target_cfg,
+        kind,
+        flags,
+    )?;
+    if config.cli_unstable().rustflags_dedup
+        && config.build_config()?.rustflags_dedup.unwrap_or(false)
+    {
+        Ok(dedup_flags(args))
+    } else {
+        Ok(args)
+    }
+}
+
+fn env_args_from_sources(
+    config: &Config,
+    requested_kinds: &[CompileKind],
+    host_triple: &str,
+    target_cfg: Option<&[Cfg]>,
+    kind: CompileKind,
+    flags: Flags,
+) -> CargoResult<Vec<String>> {
+    let target_applies_to_host = config.target_applies_to_host()?;
+
+    // Host artifacts should not generally pick up rustflags from anywhere except [host].
+    //
+    // The one exception to this is if `target-applies-to-host = true`, which opts into a
+    // particular (inconsistent) past Cargo behavior where host artifacts _do_ pick up rustflags
+    // set elsewhere when `--target` isn't passed.
+    if kind.is_host() {
+        if target_applies_to_host && requested_kinds == [CompileKind::Host] {
+            // This is the past Cargo behavior where we fall back to the same logic as for other
+            // artifacts without --target.
+        } else {
+            // In all other cases, host artifacts just get flags from [host], regardless of
+            // --target. Or, phrased differently, no `--target` behaves the same as `--target
+            // <host>`, and host artifacts are always "special" (they don't pick up `RUSTFLAGS` for
+            // example).
+            return Ok(rustflags_from_host(config, flags, host_triple)?.unwrap_or_else(Vec::new));
+        }
+    }
+
+    // All other artifacts pick up the RUSTFLAGS, [target.*], and [build], in that order.
+    // NOTE: It is impossible to have a [host] section and reach this logic with kind.is_host(),
+    // since [host] implies `target-applies-to-host = false`, which always early-returns above.
+
+    let from_env = rustflags_from_env(flags)?;
+
+    if rustflags_merge(config)? {
+        let mut rustflags = from_env.unwrap_or_default();
+        if let Some(args) = rustflags_from_target(
+            config,
+            requested_kinds,
+            host_triple,
+            target_cfg,
+            kind,
+            flags,
+        )? {
+            rustflags.extend(args);
+        }
+        if let Some(args) = rustflags_from_build(config, flags)? {
+            rustflags.extend(args);
+        }
+        return Ok(rustflags);
+    }
+
+    if let Some(rustflags) = from_env {
+        Ok(rustflags)
+    } else if let Some(rustflags) = rustflags_from_target(
+        config,
+        requested_kinds,
+        host_triple,
+        target_cfg,
+        kind,
+        flags,
+    )? {
+        Ok(rustflags)
+    } else if let Some(rustflags) = rustflags_from_build(config, flags)? {
+        Ok(rustflags)
+    } else {
+        Ok(Vec::new())
+    }
+}
+
+/// The environment variable Cargo sets alongside `CARGO_ENCODED_RUSTFLAGS`
+/// when running build scripts, holding the same value.
+///
+/// A nested Cargo seeing both with equal values knows its rustflags came from
+/// the parent Cargo rather than from the user.
+pub(crate) const PARENT_ENCODED_RUSTFLAGS: &str = "__CARGO_PARENT_ENCODED_RUSTFLAGS";
+
+/// Tells the user when `CARGO_ENCODED_RUSTFLAGS` was set by a parent Cargo,
+/// such as when a build script runs `cargo`, since the inner build then
+/// ignores its own `build.rustflags` and `target.<triple>.rustflags`.
+///
+/// Called once per `RustcTargetData`, so at most once per build.
+fn note_inherited_rustflags(config: &Config) -> CargoResult<()> {
+    let encoded = env::var_os("CARGO_ENCODED_RUSTFLAGS");
+    if encoded.is_none() || encoded != env::var_os(PARENT_ENCODED_RUSTFLAGS) {
+        return Ok(());
+    }
+    config.shell().note(
+        "using `CARGO_ENCODED_RUSTFLAGS` inherited from a parent Cargo process, \
+         so rustflags from config are ignored\n\
+         Remove `CARGO_ENCODED_RUSTFLAGS` from the environment of this `cargo` \
+         invocation to use its own rustflags.",
+    )
+}
+
+/// With `--verbose`, warns when `RUSTFLAGS` won't reach build scripts and
+/// proc macros because they are built for the host while cross compiling.
+///
+/// Called once per `RustcTargetData`, so at most once per build.
+fn warn_host_rustflags_skipped(
+    config: &Config,
+    requested_kinds: &[CompileKind],
+    host_triple: &str,
+) -> CargoResult<()> {
+    if config.shell().verbosity() != Verbosity::Verbose {
+        return Ok(());
+    }
+    let target = requested_kinds.iter().find_map(|kind| match kind {
+        CompileKind::Target(target) if target.short_name() != host_triple => Some(target),
+        _ => None,
+    });
+    let target = match target {
+        Some(target) => target,
+        None => return Ok(()),
+    };
+    let encoded = env::var_os("CARGO_ENCODED_RUSTFLAGS");
+    if encoded.is_some() && encoded == env::var_os(PARENT_ENCODED_RUSTFLAGS) {
+        // Already explained by `note_inherited_rustflags`.
+        return Ok(());
+    }
+    if !rustflags_from_env(Flags::Rust)?.map_or(false, |flags| !flags.is_empty()) {
+        return Ok(());
+    }
+    let var = if encoded.is_some() {
+        "CARGO_ENCODED_RUSTFLAGS"
+    } else {
+        "RUSTFLAGS"
+    };
+    config.shell().warn(format!(
+        "`{var}` is not passed to build scripts and proc macros when cross compiling\n\
+         They are built for the host `{host_triple}`, while `{var}` only applies to \
+         `--target {target}`, and `build.rustflags` doesn't reach them either. \
+         Flags for host artifacts can be set with `HOST_RUSTFLAGS` or `host.rustflags` \
+         (see `-Zhost-config`).",
+        var = var,
+        host_triple = host_triple,
+        target = target.short_name(),
+    ))
+}
+
+/// With `--verbose`, warns when more than one `target.'cfg(...)'.rustflags`
+/// matches `target`, since all of their flags are passed one after another.
+///
+/// Called once per `RustcTargetData` and target.
+/// Whether `build.rustflags-merge` is set. The key is ignored unless
+/// `-Zrustflags-merge` is passed.
+fn rustflags_merge(config: &Config) -> CargoResult<bool> {
+    Ok(config.cli_unstable().rustflags_merge
+        && config.build_config()?.rustflags_merge.unwrap_or(false))
+}
+
+fn warn_overlapping_cfg_rustflags(
+    config: &Config,
+    target: &str,
+    target_cfg: &[Cfg],
+) -> CargoResult<()> {
+    if config.shell().verbosity() != Verbosity::Verbose {
+        return Ok(());
+    }
+    if !rustflags_merge(config)? && rustflags_from_env(Flags::Rust)?.is_some() {
+        // Config rustflags are ignored entirely.
+        return Ok(());
+    }
+    let mut matched = Vec::new();
+    for (key, cfg) in config.target_cfgs()? {
+        if cfg.rustflags.is_none() {
+            continue;
+        }
+        let platform = match Platform::from_str(key) {
+            Ok(platform) => platform,
+            // Already reported by `load_target_cfgs`.
+            Err(_) => continue,
+        };
+        if platform.matches(target, target_cfg) {
+            matched.push(format!("`{}`", key));
+        }
+    }
+    if matched.len() < 2 {
+        return Ok(());
+    }
+    config.shell().warn(format!(
+        "rustflags from several `target.'cfg(...)'` tables apply to `{}`: {}\n\
+         All of their flags are passed, in this order.",
+        target,
+        matched.join(", ")
+    ))
+}
+
+fn rustflags_from_env(flags: Flags) -> CargoResult<Option<Vec<String>>> {
+    // First try CARGO_ENCODED_RUSTFLAGS from the environment.
+    // Prefer this over RUSTFLAGS since it's less prone to encoding errors.
+    let encoded_env = format!("CARGO_ENCODED_{}", flags.as_env());
+    if let Ok(a) = env::var(&encoded_env) {
+        // Both being set is almost always a mistake, and silently dropping
+        // one of them is confusing.
+        if env::var_os(flags.as_env()).is_some() {
+            anyhow::bail!(
+                "both `{}` and `{}` are set in the environment\n\
+                 Only one of them may be used, remove `{}` to pass the flags \
+                 separated by `0x1f`, or `{}` to pass them separated by spaces.",
+                encoded_env,
+                flags.as_env(),
+                flags.as_env(),
+                encoded_env,
+            );
+        }
+        if a.is_empty() {
+            return Ok(Some(Vec::new()));
+        }
+        return Ok(Some(a.split('\x1f').map(str::to_string).collect()));
+    }
+
+    // Then try RUSTFLAGS from the environment
+    if let Ok(a) = env::var(flags.as_env()) {
+        return Ok(Some(split_env_flags(&a)));
+    }
+
+    // No rustflags to be collected from the environment
+    Ok(None)
+}
+
+/// Writes an empty source file for `build.probe-source-file` and returns its
+/// path.
+///
+/// The file is kept in Cargo's home directory so that its path, and with it
+/// the cached rustc output, stays the same between runs.
+fn probe_source_file(config: &Config) -> CargoResult<PathBuf> {
+    let path = config
+        .home()
+        .join("probe")
+        .into_path_unlocked()
+        .join("___.rs");
+    if !path.exists() {
+        paths::create_dir_all(path.parent().unwrap())?;
+        paths::write(&path, "")?;
+    }
+    Ok(path)
+}
+
+/// Splits the value of `RUSTFLAGS` or `RUSTDOCFLAGS` into arguments.
+///
+/// Arguments are separated by whitespace. Like a shell, a word starting with
+/// `'` or `"` runs to the matching quote, so `"-Clink-arg=/a b"` is a single
+/// argument. A `\` only escapes a quote character, and nothing inside single
+/// quotes, so Windows paths such as `C:\libs\` and `\\server\share` are kept
+/// as is whether they are quoted or not. Quotes in the middle of a word are
+/// kept as is too, since flags like `--cfg feature="foo"` have always been
+/// passed through that way.
+fn split_env_flags(s: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut chars = s.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        let mut quote = match chars.peek() {
+            None => return args,
+            Some(&q @ ('\'' | '"')) => {
+                chars.next();
+                Some(q)
+            }
+            Some(_) => None,
+        };
+        let mut arg = String::new();
+        while let Some(c) = chars.next() {
+            match (quote, c) {
+                (Some(q), c) if c == q => quote = None,
+                (Some('\''), c) => arg.push(c),
+                (Some(_), '\\') => match chars.next_if_eq(&'"') {
+                    Some(escaped) => arg.push(escaped),
+                    None => arg.push('\\'),
+                },
+                (None, '\\') => match chars.next_if(|&c| c == '\'' || c == '"') {
+                    Some(escaped) => arg.push(escaped),
+                    None => arg.push('\\'),
+                },
+                (None, c) if c.is_whitespace() => break,
+                (_, c) => arg.push(c),
+            }
+        }
+        args.push(arg);
+    }
+}
+
+fn rustflags_from_target(
+    config: &Config,
+    requested_kinds: &[CompileKind],
+    host_triple: &str,
+    target_cfg: Option<&[Cfg]>,
+    kind: CompileKind,
+    flag: Flags,
+) -> CargoResult<Option<Vec<String>>> {
+    let mut rustflags = Vec::new();
+
+    // Then the target.*.rustflags value...
+    let target = match &kind {
+        CompileKind::Host => host_triple,
+        CompileKind::Target(target) => target.short_name(),
+    };
+    let key = format!("target.{}.{}", target, flag.as_key());
+    config.check_flags_key(&key)?;
+    if let Some(args) = config.get::<Option<StringList>>(&key)? {
+        rustflags.extend(args.as_slice().iter().cloned());
+    }
+    // ...then whatever target.*.rustflags-command prints...
+    if let Flags::Rust = flag {
+        if let Some(args) = rustflags_from_command(config, target)? {
+            rustflags.extend(args);
+        }
+    }
+    // ...and target.*.rustflags-when-building.<other> for every other target
+    // that is part of the same build, with -Zrustflags-when-building...
+    if matches!(flag, Flags::Rust) && config.cli_unstable().rustflags_when_building {
+        for other in requested_kinds {
+            if let CompileKind::Target(other) = other {
+                if other.short_name() == target {
+                    continue;
+                }
+                let key = format!(
+                    "target.{}.rustflags-when-building.{}",
+                    target,
+                    other.short_name()
+                );
+                config.check_flags_key(&key)?;
+                if let Some(args) = config.get::<Option<StringList>>(&key)? {
+                    rustflags.extend(args.as_slice().iter().cloned());
+                }
+            }
+        }
+    }
+    // ...including target.'cfg(...)'.rustflags. The keys are parsed and
+    // matched like `[target.'cfg(...)'.dependencies]` in a manifest.
+    if let Some(target_cfg) = target_cfg {
+        for (key, cfg) in config.target_cfgs()? {
+            let cfg_rustflags = match &cfg.rustflags {
+                Some(cfg_rustflags) => &cfg_rustflags.val,
+                None => continue,
+            };
+            let platform = match Platform::from_str(key) {
+                Ok(platform) => platform,
+                // Already reported by `load_target_cfgs`.
+                Err(_) => continue,
+            };
+            if platform.matches(target, target_cfg) {
+                rustflags.extend(cfg_rustflags.as_slice().iter().cloned());
+            }
+        }
+    }
+
+    if rustflags.is_empty() {
+        Ok(None)
+    } else {
+        Ok(Some(rustflags))
+    }
+}
+
+/// Sets `target_config.linker` from the `target.'cfg(...)'` tables matching
+/// `target`, unless `target.<triple>.linker` is already set.
+///
+/// The cfg keys are matched the same way as for `rustflags`. Several matching
+/// tables may set a linker as long as they agree on it, otherwise this is an
+/// error since there is no meaningful order between them. The tables are
+/// ignored unless `-Zcfg-linker` is passed.
+fn apply_cfg_linker(
+    config: &Config,
+    target: &str,
+    target_cfg: &[Cfg],
+    target_config: &mut TargetConfig,
+) -> CargoResult<()> {
+    if target_config.linker.is_some() || !config.cli_unstable().cfg_linker {
+        return Ok(());
+    }
+    let mut found: Option<(&str, &Value<ConfigRelativePath>)> = None;
+    for (key, cfg) in config.target_cfgs()? {
+        let linker = match &cfg.linker {
+            Some(linker) => linker,
+            None => continue,
+        };
+        let platform = match Platform::from_str(key) {
+            Ok(platform) => platform,
+            // Already reported by `load_target_cfgs`.
+            Err(_) => continue,
+        };
+        if !platform.matches(target, target_cfg) {
+            continue;
+        }
+        match found {
+            Some((prev_key, prev)) if prev.val.raw_value() != linker.val.raw_value() => {
+                anyhow::bail!(
+                    "conflicting linkers for target `{}`\n\
+                     `target.'{}'.linker` is `{}` in {}\n\
+                     `target.'{}'.linker` is `{}` in {}\n\
+                     Set `target.{}.linker` to choose one.",
+                    target,
+                    prev_key,
+                    prev.val.raw_value(),
+                    prev.definition,
+                    key,
+                    linker.val.raw_value(),
+                    linker.definition,
+                    target
+                );
+            }
+            Some(_) => {}
+            None => found = Some((key, linker)),
+        }
+    }
+    target_config.linker = found.map(|(_, linker)| linker.clone());
+    Ok(())
+}
+
+/// Runs the program configured in `target.<triple>.rustflags-command` and
+/// splits its stdout like `RUSTFLAGS`, see `split_env_flags`. The result is
+/// cached on `Config` so the program runs at most once per session.
+///
+/// The key is ignored unless `-Zrustflags-command` is passed.
+fn rustflags_from_command(config: &Config, target: &str) -> CargoResult<Option<Vec<String>>> {
+    if !config.cli_unstable().rustflags_command {
+        return Ok(None);
+    }
+    let key = format!("target.{}.rustflags-command", target);
+    if let Some(flags) = config.rustflags_command_outputs().get(&key) {
+        return Ok(Some(flags.clone()));
+    }
+    let command = match config.get::<Option<PathAndArgs>>(&key)? {
+        Some(command) => command,
+        None => return Ok(None),
+    };
+    let program = command.path.resolve_program(config);
+    let mut process = ProcessBuilder::new(&program);
+    process.args(&command.args);
+    let output = process.exec_with_output().with_context(|| {
+        format!(
+            "failed to run {} configured in `{}` (from {})",
+            process,
+            key,
+            command.path.value().definition
+        )
+    })?;
+    let stdout = str::from_utf8(&output.stdout).with_context(|| {
+        format!(
+            "the output of {} configured in `{}` was not valid UTF-8",
+            process, key
+        )
+    })?;
+    let flags = split_env_flags(stdout);
+    config
+        .rustflags_command_outputs()
+        .insert(key, flags.clone());
+    Ok(Some(flags))
+}
+
+fn rustflags_from_host(
+    config: &Config,
+    flag: Flags,
+    host_triple: &str,
+) -> CargoResult<Option<Vec<String>>> {
+    let target_cfg = config.host_cfg_triple(host_triple)?;
+    let list = match flag {
+        Flags::Rust => {
+            // `HOST_RUSTFLAGS` takes precedence over `host.rustflags`, like
+            // `RUSTFLAGS` over `target.<triple>.rustflags`.
+            if config.cli_unstable().host_config {
+                if let Ok(a) = env::var("HOST_RUSTFLAGS") {
+                    return Ok(Some(split_env_flags(&a)));
+                }
+            }
+            &target_cfg.rustflags
+        }
+        Flags::Rustdoc => {
+            // host.rustdocflags is not a thing, since it does not make sense
+            return Ok(None);
+        }
+    };
+    Ok(list.as_ref().map(|l| l.val.as_slice().to_vec()))
+}
+
+/// Loads `target.<triple>.crate-type.<type>.rustflags` from the config.
+///
+/// These follow `target.<triple>.rustflags`: they are ignored when flags come
+/// from `RUSTFLAGS` or `CARGO_ENCODED_RUSTFLAGS`, and for host artifacts that
+/// only use `[host]` flags.
+fn crate_type_rustflags(
+    config: &Config,
+    requested_kinds: &[CompileKind],
+    host_triple: &str,
+    kind: CompileKind,
+) -> CargoResult<HashMap<CrateType, Vec<String>>> {
+    #[derive(Deserialize)]
+    struct CrateTypeConfig {
+        rustflags: Option<StringList>,
+    }
+
+    if kind.is_host()
+        && !(config.target_applies_to_host()? && requested_kinds == [CompileKind::Host])
+    {
+        return Ok(HashMap::new());
+    }
+    if rustflags_from_env(Flags::Rust)?.is_some() && !rustflags_merge(config)? {
+        return Ok(HashMap::new());
+    }
+    let target = match &kind {
+        CompileKind::Host => host_triple,
+        CompileKind::Target(target) => target.short_name(),
+    };
+    let key = format!("target.{}.crate-type", target);
+    let table = config.get::<Option<BTreeMap<String, CrateTypeConfig>>>(&key)?;
+    Ok(table
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|(crate_type, config)| {
+            let flags = config.rustflags?;
+            Some((CrateType::from(&crate_type), flags.as_slice().to_vec()))
+        })
+        .collect())
+}
+
+fn rustflags_from_build(config: &Config, flag: Flags) -> CargoResult<Option<Vec<String>>> {
+    // Then the `build.rustflags` value.
+    let build = config.build_config()?;
+    let list = match flag {
+        Flags::Rust => &build.rustflags,
+        Flags::Rustdoc => &build.rustdocflags,
+    };
+    let mut rustflags = list.as_ref().map(|l| l.as_slice().to_vec());
+    // Followed by the flags in `build.rustflags-file`, with -Zrustflags-file.
+    let file = build
+        .rustflags_file
+        .as_ref()
+        .filter(|_| config.cli_unstable().rustflags_file);
+    if let (Flags::Rust, Some(file)) = (flag, file) {
+        let path = file.resolve_path(config);
+        let contents = paths::read(&path).with_context(|| {
+            format!(
+                "failed to read the rustflags file `{}` set by `build.rustflags-file`",
+                path.display()
+            )
+        })?;
+        rustflags
+            .get_or_insert_with(Vec::new)
+            .extend(split_env_flags(&contents));
+    }
+    Ok(rustflags)
+}
+
+/// Returns the `target_feature` values referenced by `expr` that are missing
+/// from `cfg`, but only if enabling them would make `expr` match.
+fn missing_target_features(expr: &CfgExpr, cfg: &[Cfg]) -> Vec<String> {
+    fn collect(expr: &CfgExpr, cfg: &[Cfg], missing: &mut Vec<String>) {
+        match expr {
+            CfgExpr::Not(e) => collect(e, cfg, missing),
+            CfgExpr::All(e) | CfgExpr::Any(e) => e.iter().for_each(|e| collect(e, cfg, missing)),
+            CfgExpr::Value(c @ Cfg::KeyPair(k, v)) if k == "target_feature" => {
+                if !cfg.contains(c) && !missing.contains(v) {
+                    missing.push(v.clone());
+                }
+            }
+            CfgExpr::Value(_) => {}
+        }
+    }
+
+    let mut missing = Vec::new();
+    collect(expr, cfg, &mut missing);
+    if missing.is_empty() {
+        return missing;
+    }
+    let enabled: Vec<Cfg> = cfg
+        .iter()
+        .cloned()
+        .chain(
+            missing
+                .iter()
+                .map(|feature| Cfg::KeyPair("target_feature".to_string(), feature.clone())),
+        )
+        .collect();
+    if expr.matches(&enabled) {
+        missing
+    } else {
+        Vec::new()
+    }
+}
+
+/// Collection of information about `rustc` and the host and target.
+pub struct RustcTargetData<'cfg> {
+    /// Information about `rustc` itself.
+    pub rustc: Rustc,
+
+    /// Config
+    config: &'cfg Config,
+    requested_kinds: Vec<CompileKind>,
+    /// Whether this was created with `new_resolve_only`, see `reload`.
+    resolve_only: bool,
+
+    /// Build information for the "host", which is information about when
+    /// `rustc` is invoked without a `--target` flag. This is used for
+    /// procedural macros, build scripts, etc.
+    host_config: TargetConfig,
+    /// Made from `build.cfg-snapshot` if the host is in there, and filled in
+    /// on first use if created with `new_resolve_only`, see `host_info()`.
+    host_info: LazyCell<TargetInfo>,
+    /// Why querying rustc about the host on first use failed, as the chain of
+    /// error messages, outermost first. See `host_probe_result()`.
+    host_probe_error: RefCell<Option<Vec<String>>>,
+
+    /// Build information for targets that we're building for. This will be
+    /// empty if the `--target` flag is not passed.
+    ///
+    /// These are `BTreeMap`s so that anything listing the targets does so in
+    /// a stable order, sorted by name.
+    target_config: BTreeMap<CompileTarget, TargetConfig>,
+    target_info: BTreeMap<CompileTarget, TargetInfo>,
+
+    /// `cfg` values loaded from `build.cfg-snapshot`, keyed by target name.
+    /// `rustc` is not queried about these targets at all.
+    cfg_snapshot: HashMap<String, Vec<Cfg>>,
+
+    /// `rustc -vV` parsed into its fields, see `verbose_version()`.
+    verbose_version: LazyCell<RustcVersion>,
+
+    /// Dependencies for which a note about missing `target_feature` values
+    /// has already been printed, see `note_missing_target_features`.
+    target_feature_notes: RefCell<HashSet<(InternedString, CompileKind)>>,
+}
+
+impl<'cfg> RustcTargetData<'cfg> {
+    pub fn new(
+        ws: &Workspace<'cfg>,
+        requested_kinds: &[CompileKind],
+    ) -> CargoResult<RustcTargetData<'cfg>> {
+        RustcTargetData::new_inner(ws, requested_kinds, false)
+    }
+
+    /// Like `new`, but for callers that only resolve dependencies, like
+    /// `cargo metadata --filter-platform`.
+    ///
+    /// `rustc` is only queried about the host once something asks for it, if
+    /// the host is not one of the `requested_kinds`. Callers must check
+    /// `host_probe_result` once they are done.
+    ///
+    /// Targets listed in the `build.cfg-snapshot` file take their `cfg`
+    /// values from there instead of `rustc`. Their `TargetInfo` only has
+    /// those `cfg` values, so only `cfg` and `dep_platform_activated` give
+    /// meaningful answers for them.
+    pub fn new_resolve_only(
+        ws: &Workspace<'cfg>,
+        requested_kinds: &[CompileKind],
+    ) -> CargoResult<RustcTargetData<'cfg>> {
+        RustcTargetData::new_inner(ws, requested_kinds, true)
+    }
+
+    fn new_inner(
+        ws: &Workspace<'cfg>,
+        requested_kinds: &[CompileKind],
+        resolve_only: bool,
+    ) -> CargoResult<RustcTargetData<'cfg>> {
+        let config = ws.config();
+        let mut rustc = config.load_global_rustc(Some(ws))?;
+        if let Some(host) = test_host_override() {
+            rustc.host = host;
+        }
+        let mut target_config = BTreeMap::new();
+        let mut target_info = BTreeMap::new();
+        let target_applies_to_host = config.target_applies_to_host()?;
+        let cfg_snapshot = match &config.build_config()?.cfg_snapshot {
+            Some(path) if resolve_only && config.cli_unstable().cfg_snapshot => {
+                CfgSnapshot::load(&path.resolve_path(config), &rustc)?.into_cfgs()?
+            }
+            _ => HashMap::new(),
+        };
+        // The snapshot is only used when resolving, and then rustc isn't run
+        // for the host either if it is in there. Resolving for other targets
+        // only needs the host for dependencies gated on its `cfg`, so it is
+        // queried on first use instead.
+        let needs_host = !cfg_snapshot.contains_key(rustc.host.as_str())
+            && (!resolve_only || requested_kinds.iter().any(CompileKind::is_host));
+
+        // Get all kinds we currently know about.
+        //
+        // For now, targets can only ever come from the root workspace
+        // units and artifact dependencies, so this
+        // correctly represents all the kinds that can happen. When we have
+        // other ways for targets to appear at places that are not the root units,
+        // we may have to revisit this.
+        fn artifact_targets(package: &Package) -> impl Iterator<Item = CompileKind> + '_ {
+            package
+                .manifest()
+                .dependencies()
+                .iter()
+                .filter_map(|d| d.artifact()?.target()?.to_compile_kind())
+        }
+        let all_kinds: Vec<CompileKind> = requested_kinds
+            .iter()
+            .copied()
+            .chain(ws.members().flat_map(|p| {
+                p.manifest()
+                    .default_kind()
+                    .into_iter()
+                    .chain(p.manifest().forced_kind())
+                    .chain(artifact_targets(p))
+            }))
+            .collect();
+
+        // Each probe waits on several rustc processes, so for cross builds
+        // run the ones for the host and every target at the same time.
+        let mut probe_commands = HashMap::new();
+        if needs_host {
+            let commands = ProbeCommands::new(config, requested_kinds, &rustc, CompileKind::Host)?;
+            probe_commands.insert(CompileKind::Host, commands);
+        }
+        for &kind in &all_kinds {
+            if let CompileKind::Target(target) = kind {
+                if !cfg_snapshot.contains_key(target.short_name())
+                    && !probe_commands.contains_key(&kind)
+                {
+                    let commands = ProbeCommands::new(config, requested_kinds, &rustc, kind)?;
+                    probe_commands.insert(kind, commands);
+                }
+            }
+        }
+        if probe_commands.len() > 1 {
+            prefetch_probes(&rustc, &probe_commands);
+        }
+
+        let host_info = LazyCell::new();
+        if let Some(cfg) = cfg_snapshot.get(rustc.host.as_str()) {
+            // This can't fail, the cell was just created.
+            let _ = host_info.fill(TargetInfo::from_cfg_snapshot(cfg));
+        } else if let Some(commands) = probe_commands.remove(&CompileKind::Host) {
+            let info = TargetInfo::from_probe_commands(
+                config,
+                requested_kinds,
+                &rustc,
+                CompileKind::Host,
+                commands,
+            )?;
+            let _ = host_info.fill(info);
+        }
+        let mut host_config = if target_applies_to_host {
+            config.target_cfg_triple(&rustc.host)?
+        } else {
+            config.host_cfg_triple(&rustc.host)?
+        };
+        if target_applies_to_host {
+            if let Some(info) = host_info.borrow() {
+                apply_cfg_linker(config, &rustc.host, info.cfg(), &mut host_config)?;
+            }
+        }
+
+        // This is a hack. The unit_dependency graph builder "pretends" that
+        // `CompileKind::Host` is `CompileKind::Target(host)` if the
+        // `--target` flag is not specified. Since the unit_dependency code
+        // needs access to the target config data, create a copy so that it
+        // can be found. See `rebuild_unit_graph_shared` for why this is done.
+        if requested_kinds.iter().any(CompileKind::is_host) {
+            let ct = CompileTarget::new(&rustc.host)?;
+            let mut host_target_config = config.target_cfg_triple(&rustc.host)?;
+            // Requesting the host means it was probed above.
+            if let Some(info) = host_info.borrow() {
+                apply_cfg_linker(config, &rustc.host, info.cfg(), &mut host_target_config)?;
+                target_info.insert(ct, info.clone());
+            }
+            target_config.insert(ct, host_target_config);
+        };
+
+        let mut res = RustcTargetData {
+            rustc,
+            config,
+            requested_kinds: requested_kinds.into(),
+            resolve_only,
+            host_config,
+            host_info,
+            host_probe_error: RefCell::new(None),
+            target_config,
+            target_info,
+            cfg_snapshot,
+            verbose_version: LazyCell::new(),
+            target_feature_notes: RefCell::new(HashSet::new()),
+        };
+
+        for &kind in &all_kinds {
+            res.merge_compile_kind(kind, &mut probe_commands)?;
+        }
+
+        if config.extra_verbose() {
+            for &kind in requested_kinds {
+                if res.cfg_snapshot.contains_key(res.short_name(&kind))
+                    || res.info(kind).rustflags.is_empty()
+                {
+                    continue;
+                }
+                config.shell().note(format!(
+                    "resolved rustflags for `{}`: {}",
+                    res.short_name(&kind),
+                    res.rustflags_shell_quoted(kind)
+                ))?;
+            }
+        }
+        if !resolve_only {
+            note_inherited_rustflags(config)?;
+            warn_host_rustflags_skipped(config, requested_kinds, &res.rustc.host)?;
+            let targets: BTreeSet<CompileTarget> = all_kinds
+                .iter()
+                .filter_map(|kind| match kind {
+                    CompileKind::Target(target) => Some(*target),
+                    CompileKind::Host => None,
+                })
+                .collect();
+            for target in targets {
+                let info = res.info(CompileKind::Target(target));
+                warn_missing_std(config, target, &info.sysroot_target_libdir)?;
+            }
+            for &kind in requested_kinds {
+                if kind.is_host()
+                    && !(target_applies_to_host && requested_kinds == [CompileKind::Host])
+                {
+                    // Host artifacts only get `host.rustflags` then.
+                    continue;
+                }
+                warn_overlapping_cfg_rustflags(config, res.short_name(&kind), res.cfg(kind))?;
+            }
+        }
+
+        Ok(res)
+    }
+
+    /// Discards everything learned from `rustc` and queries it again.
+    ///
+    /// This is intended for embedders that keep a `RustcTargetData` alive
+    /// across a toolchain switch. Both the in-memory and the on-disk caches of
+    /// `rustc` outputs are cleared first, so no `cfg` or file name information
+    /// from the previous toolchain survives.
+    ///
+    /// The new data is created the same way as before, with `new` or with
+    /// `new_resolve_only`.
+    pub fn reload(&mut self, ws: &Workspace<'cfg>) -> CargoResult<()> {
+        self.rustc.clear_cache();
+        self.config.clear_rustc_info_cache(ws)?;
+        let requested_kinds = self.requested_kinds.clone();
+        *self = RustcTargetData::new_inner(ws, &requested_kinds, self.resolve_only)?;
+        Ok(())
+    }
+
+    /// Insert `kind` into our `target_info` and `target_config` members if it isn't present yet.
+    ///
+    /// The target is probed with its commands from `probe_commands`, if they
+    /// were built already.
+    fn merge_compile_kind(
+        &mut self,
+        kind: CompileKind,
+        probe_commands: &mut HashMap<CompileKind, ProbeCommands>,
+    ) -> CargoResult<()> {
+        if let CompileKind::Target(target) = kind {
+            if !self.target_info.contains_key(&target) {
+                let info = match self.cfg_snapshot.get(target.short_name()) {
+                    Some(cfg) => TargetInfo::from_cfg_snapshot(cfg),
+                    None => {
+                        let commands = match probe_commands.remove(&kind) {
+                            Some(commands) => commands,
+                            None => ProbeCommands::new(
+                                self.config,
+                                &self.requested_kinds,
+                                &self.rustc,
+                                kind,
+                            )?,
+                        };
+                        TargetInfo::from_probe_commands(
+                            self.config,
+                            &self.requested_kinds,
+                            &self.rustc,
+                            kind,
+                            commands,
+                        )?
+                    }
+                };
+                self.target_info.insert(target, info);
+            }
+            if !self.target_config.contains_key(&target) {
+                let mut target_config = self.config.target_cfg_triple(self.triple(&kind))?;
+                apply_cfg_linker(
+                    self.config,
+                    self.triple(&kind),
+                    self.cfg(kind),
+                    &mut target_config,
+                )?;
+                self.target_config.insert(target, target_config);
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns a "short" name for the given kind, suitable for keying off
+    /// configuration in Cargo or presenting to users. This is the same as
+    /// `triple`.
+    pub fn short_name<'a>(&'a self, kind: &'a CompileKind) -> &'a str {
+        self.triple(kind)
+    }
+
+    /// The triple of `kind` as it is used in `target.<triple>.*` config keys.
+    ///
+    /// This is the host triple for `CompileKind::Host`, and the file stem of
+    /// a target spec file, so `--target path/to/foo.json` is configured with
+    /// `[target.foo]`. It is not what `rustc` is passed with `--target`, use
+    /// `CompileTarget::rustc_target` for that.
+    pub fn triple<'a>(&'a self, kind: &'a CompileKind) -> &'a str {
+        match kind {
+            CompileKind::Host => &self.rustc.host,
+            CompileKind::Target(target) => target.short_name(),
+        }
+    }
+
+    /// Whether `kind` builds for the host, either because it is
+    /// `CompileKind::Host` or because it names the host triple, as with
+    /// `--target <host>`.
+    ///
+    /// A target spec file is named after the file, not the target it
+    /// describes, so it is instead compared by what rustc reports about it:
+    /// it counts as the host if it has the same `cfg` values. The output file
+    /// names are not compared, since crate types are discovered lazily and
+    /// either side may not know all of them yet.
+    pub fn is_host_target(&self, kind: CompileKind) -> bool {
+        match kind {
+            CompileKind::Host => true,
+            CompileKind::Target(target) if target.is_spec_file() => {
+                self.info(kind).cfg == self.host_info().cfg
+            }
+            CompileKind::Target(target) => target.short_name() == self.rustc.host.as_str(),
+        }
+    }
+
+    /// The files rustc produces for `target` in the given mode, as paths
+    /// relative to the target directory.
+    ///
+    /// Each path starts with the directory for `kind` (the triple's short name
+    /// for `--target` builds, nothing for the host, like `Layout`), followed
+    /// by `dest` such as `debug/deps`. This saves callers that handle several
+    /// kinds from assembling the per-kind directories themselves; use
+    /// `TargetInfo::rustc_outputs` for bare file types.
+    pub fn rustc_output_paths(
+        &self,
+        kind: CompileKind,
+        dest: &Path,
+        target: &Target,
+        mode: CompileMode,
+        metadata: Option<&str>,
+        strip: Strip,
+        split_debuginfo: Option<InternedString>,
+    ) -> CargoResult<Vec<(FileType, PathBuf)>> {
+        let (file_types, _unsupported) = self.info(kind).rustc_outputs(
+            mode,
+            target.kind(),
+            self.short_name(&kind),
+            strip,
+            split_debuginfo,
+        )?;
+        let dir = kind_dir(&kind).join(dest);
+        Ok(file_types
+            .into_iter()
+            .map(|ft| {
+                let path = dir.join(ft.output_filename(target, metadata));
+                (ft, path)
+            })
+            .collect())
+    }
+
+    /// For every workspace member and requested kind, which crate types the
+    /// member's targets declare and whether rustc supports them for that kind.
+    pub fn crate_type_support(&self, ws: &Workspace<'_>) -> CargoResult<Vec<CrateTypeSupport>> {
+        let mut result = Vec::new();
+        for pkg in ws.members() {
+            let crate_types: BTreeSet<CrateType> = pkg
+                .targets()
+                .iter()
+                .flat_map(|target| target.rustc_crate_types())
+                .collect();
+            for &kind in &self.requested_kinds {
+                let info = self.info(kind);
+                let crate_types = crate_types
+                    .iter()
+                    .map(|ct| Ok((ct.clone(), info.supports_crate_type(ct)?)))
+                    .collect::<CargoResult<_>>()?;
+                result.push(CrateTypeSupport {
+                    package: pkg.package_id(),
+                    kind,
+                    crate_types,
+                });
+            }
+        }
+        Ok(result)
+    }
+
+    /// Whether building the workspace for `kind` produces any executable,
+    /// that is, whether a member has a binary or example binary target and
+    /// rustc can build binaries for `kind`.
+    ///
+    /// This does not look at `required-features`, so a binary that would be
+    /// skipped because of missing features still counts.
+    pub fn produces_executable(&self, ws: &Workspace<'_>, kind: CompileKind) -> CargoResult<bool> {
+        let has_executable_target = ws
+            .members()
+            .flat_map(|pkg| pkg.targets())
+            .any(|target| target.is_bin() || target.is_exe_example());
+        if !has_executable_target {
+            return Ok(false);
+        }
+        self.info(kind).supports_crate_type(&CrateType::Bin)
+    }
+
+    /// Whether a dependency should be compiled for the host or target platform,
+    /// specified by `CompileKind`.
+    pub fn dep_platform_activated(&self, dep: &Dependency, kind: CompileKind) -> bool {
+        // If this dependency is only available for certain platforms,
+        // make sure we're only enabling it for that platform.
+        let platform = match dep.platform() {
+            Some(p) => p,
+            None => return true,
+        };
+        let name = self.short_name(&kind);
+        let activated = platform.matches(name, self.cfg(kind));
+        if !activated {
+            if let Platform::Cfg(expr) = platform {
+                self.note_missing_target_features(dep, expr, kind);
+            }
+        }
+        activated
+    }
+
+    /// Whether a dependency should be compiled for the host or for at least
+    /// one of the requested targets.
+    ///
+    /// This is the union of `dep_platform_activated` over the host and every
+    /// requested kind, for decisions that do not depend on a single kind,
+    /// such as whether a dependency belongs in the graph at all.
+    pub fn dep_platform_activated_any(&self, dep: &Dependency) -> bool {
+        let platform = match dep.platform() {
+            Some(p) => p,
+            None => return true,
+        };
+        std::iter::once(&CompileKind::Host)
+            .chain(&self.requested_kinds)
+            .any(|kind| platform.matches(self.short_name(kind), self.cfg(*kind)))
+    }
+
+    /// The requested kinds a dependency is activated for, in the order they
+    /// were requested.
+    ///
+    /// `cfg`-gated dependencies (and so the features they enable) can differ
+    /// between targets, so this lets the feature resolver tell whether a
+    /// dependency's features can be unified across all requested targets.
+    pub fn dep_activated_kinds(&self, dep: &Dependency) -> Vec<CompileKind> {
+        let platform = match dep.platform() {
+            Some(p) => p,
+            None => return self.requested_kinds.clone(),
+        };
+        self.requested_kinds
+            .iter()
+            .filter(|kind| platform.matches(self.short_name(kind), self.cfg(**kind)))
+            .copied()
+            .collect()
+    }
+
+    /// Whether a dependency is activated for either all of the requested
+    /// kinds or none of them, so its features do not vary by target.
+    pub fn dep_activation_is_uniform(&self, dep: &Dependency) -> bool {
+        let kinds = self.dep_activated_kinds(dep);
+        kinds.is_empty() || kinds.len() == self.requested_kinds.len()
+    }
+
+    /// With `--verbose`, explains once per dependency and kind when `dep` is
+    /// only inactive because of `target_feature` values that are not enabled.
+    ///
+    /// This is a common surprise with SIMD-gated dependencies, since those
+    /// features usually need to be enabled explicitly with `-Ctarget-feature`.
+    fn note_missing_target_features(&self, dep: &Dependency, expr: &CfgExpr, kind: CompileKind) {
+        if self.config.shell().verbosity() != Verbosity::Verbose {
+            return;
+        }
+        let missing = missing_target_features(expr, self.cfg(kind));
+        if missing.is_empty() {
+            return;
+        }
+        if !self
+            .target_feature_notes
+            .borrow_mut()
+            .insert((dep.package_name(), kind))
+        {
+            return;
+        }
+        let flag = missing
+            .iter()
+            .map(|feature| format!("+{}", feature))
+            .collect::<Vec<_>>()
+            .join(",");
+        drop(self.config.shell().note(format!(
+            "dependency `{}` is not used for target `{}` because it requires \
+             target features that are not enabled: {}\n\
+             If this dependency is expected, pass `-Ctarget-feature={}` in RUSTFLAGS",
+            dep.package_name(),
+            self.short_name(&kind),
+            missing.join(", "),
+            flag
+        )));
+    }
+
+    /// The resolved rustflags for `kind`, quoted so they can be pasted into a
+    /// manual `rustc` invocation in the native shell of the host.
+    pub fn rustflags_shell_quoted(&self, kind: CompileKind) -> String {
+        self.info(kind)
+            .rustflags
+            .iter()
+            .map(|flag| shell_escape::escape(Cow::Borrowed(flag.as_str())))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Gets the list of `cfg`s printed out from the compiler for the specified kind.
+    pub fn cfg(&self, kind: CompileKind) -> &[Cfg] {
+        match self.cfg_snapshot.get(self.short_name(&kind)) {
+            Some(cfg) => cfg,
+            None => self.info(kind).cfg(),
+        }
+    }
+
+    /// Every `cfg` that is set for the host or for at least one target,
+    /// deduplicated and sorted.
+    ///
+    /// This is meant for analysis that has to consider all targets of a build
+    /// at once, such as finding code that might be compiled at all.
+    pub fn cfg_union(&self) -> Vec<Cfg> {
+        let kinds = std::iter::once(CompileKind::Host)
+            .chain(self.requested_kinds.iter().copied())
+            .chain(self.target_kinds());
+        let mut cfgs = BTreeSet::new();
+        for kind in kinds {
+            cfgs.extend(self.cfg(kind).iter().cloned());
+        }
+        cfgs.into_iter().collect()
+    }
+
+    /// A hash of everything about `kind` that affects what gets built: the
+    /// `rustc -vV` output, the target name, the resolved rustflags and
+    /// rustdocflags, the `cfg` values, and the file names of each crate type
+    /// the target supports.
+    ///
+    /// This is meant as part of the key for caching build outputs across
+    /// machines, so unlike `Rustc::fingerprint` it does not depend on the
+    /// `rustc` executable itself. The sysroot is included, since it decides
+    /// which standard library is linked even for the same `rustc`. It is the
+    /// same for identical inputs on every run.
+    pub fn build_state_hash(&self, kind: CompileKind) -> u64 {
+        let mut hasher = StableHasher::new();
+        self.rustc.verbose_version.hash(&mut hasher);
+        self.short_name(&kind).hash(&mut hasher);
+        self.info(kind)
+            .hash_build_state(self.cfg(kind), &mut hasher);
+        hasher.finish()
+    }
+
+    /// Information about the given target platform, learned by querying rustc.
+    pub fn info(&self, kind: CompileKind) -> &TargetInfo {
+        match kind {
+            CompileKind::Host => self.host_info(),
+            CompileKind::Target(s) => &self.target_info[&s],
+        }
+    }
+
+    /// Information about the host, querying rustc now if that was deferred.
+    ///
+    /// Callers can't handle an error here, so if the query fails the host is
+    /// treated as having no `cfg` values, and the error is kept for
+    /// `host_probe_result` to report.
+    fn host_info(&self) -> &TargetInfo {
+        self.host_info.borrow_with(|| {
+            TargetInfo::new(
+                self.config,
+                &self.requested_kinds,
+                &self.rustc,
+                CompileKind::Host,
+            )
+            .unwrap_or_else(|e| {
+                *self.host_probe_error.borrow_mut() =
+                    Some(e.chain().map(ToString::to_string).collect());
+                TargetInfo::from_cfg_snapshot(&[])
+            })
+        })
+    }
+
+    /// Returns the error from querying rustc about the host on first use, if
+    /// that failed.
+    ///
+    /// Only `new_resolve_only` defers the host, and its callers must check
+    /// this after they are done, since anything that depended on the host
+    /// was computed without its `cfg` values. The error is returned on every
+    /// call, since the host keeps those missing values.
+    pub fn host_probe_result(&self) -> CargoResult<()> {
+        let error = self.host_probe_error.borrow();
+        let chain = match &*error {
+            Some(chain) => chain,
+            None => return Ok(()),
+        };
+        // `anyhow::Error` can't be cloned, so it is rebuilt from its messages.
+        let mut messages = chain.iter().rev();
+        let mut e = anyhow::format_err!("{}", messages.next().unwrap());
+        for message in messages {
+            e = e.context(message.clone());
+        }
+        Err(e.context("failed to learn about the host target"))
+    }
+
+    /// Whether executables built for `kind` can't be run directly on the host
+    /// and need a `target.<triple>.runner` instead.
+    ///
+    /// This only compares the architecture and OS of the target against the
+    /// host, so it may miss targets that need a runner for other reasons.
+    pub fn needs_runner(&self, kind: CompileKind) -> bool {
+        if self.is_host_target(kind) {
+            return false;
+        }
+        let host = self.host_info();
+        let target = self.info(kind);
+        let arch_compatible = match (
+            host.cfg_value("target_arch"),
+            target.cfg_value("target_arch"),
+        ) {
+            (h, t) if h == t => true,
+            // 64-bit x86 hosts can run 32-bit x86 executables natively.
+            (Some("x86_64"), Some("x86")) => true,
+            _ => false,
+        };
+        !arch_compatible || host.cfg_value("target_os") != target.cfg_value("target_os")
+    }
+
+    /// Groups the requested kinds into sets whose artifacts could be shared,
+    /// because rustc would build them identically.
+    ///
+    /// Besides `TargetInfo::is_equivalent`, kinds must use the same linker.
+    /// Kinds with `links` overrides are never grouped with another, since
+    /// the overrides can change what gets linked.
+    pub fn equivalent_kinds(&self) -> Vec<Vec<CompileKind>> {
+        let shareable = |a: CompileKind, b: CompileKind| {
+            self.info(a).is_equivalent(self.info(b))
+                && self.linker(a) == self.linker(b)
+                && self.target_config(a).links_overrides.is_empty()
+                && self.target_config(b).links_overrides.is_empty()
+        };
+        let mut groups: Vec<Vec<CompileKind>> = Vec::new();
+        for &kind in &self.requested_kinds {
+            match groups.iter_mut().find(|g| shareable(g[0], kind)) {
+                Some(group) => group.push(kind),
+                None => groups.push(vec![kind]),
+            }
+        }
+        groups
+    }
+
+    /// All the explicit targets `rustc` has been queried about, sorted by
+    /// name. This never includes `CompileKind::Host`.
+    pub fn target_kinds(&self) -> impl Iterator<Item = CompileKind> + '_ {
+        self.target_info
+            .keys()
+            .map(|target| CompileKind::Target(*target))
+    }
+
+    /// Whether a linker for `kind` can be found, without running it.
+    ///
+    /// This checks `target.<triple>.linker` if it is set, otherwise the
+    /// linker rustc uses by default: `emcc` for Emscripten, `rust-lld` from
+    /// the sysroot for other wasm targets, `<arch>-w64-mingw32-gcc` or `gcc`
+    /// for windows-gnu, and `cc` elsewhere. MSVC targets without a configured
+    /// linker are assumed to have one, since rustc finds `link.exe` through
+    /// the Visual Studio installation rather than `PATH`.
+    ///
+    /// This is a best-effort check meant for early validation, a `true` result
+    /// does not guarantee that linking will succeed.
+    pub fn linker_available(&self, kind: CompileKind) -> bool {
+        if let Some(linker) = self.linker(kind) {
+            return paths::resolve_executable(&linker)
+                .map(|path| path.is_file())
+                .unwrap_or(false);
+        }
+        let info = self.info(kind);
+        if info.environment_abi() == EnvAbi::Msvc {
+            return true;
+        }
+        if info.cfg_value("target_os") == Some("emscripten") {
+            return paths::resolve_executable(Path::new("emcc")).is_ok();
+        }
+        if info
+            .cfg_value("target_arch")
+            .map_or(false, |arch| arch.starts_with("wasm"))
+        {
+            let bundled = self
+                .host_info()
+                .sysroot
+                .join("lib/rustlib")
+                .join(self.rustc.host.as_str())
+                .join("bin/rust-lld")
+                .with_extension(env::consts::EXE_EXTENSION);
+            return bundled.is_file() || paths::resolve_executable(Path::new("rust-lld")).is_ok();
+        }
+        if info.cfg_value("target_os") == Some("windows") && info.environment_abi() == EnvAbi::Gnu {
+            let arch = match info.cfg_value("target_arch") {
+                Some("x86") => "i686",
+                arch => arch.unwrap_or(""),
+            };
+            let mingw = format!("{}-w64-mingw32-gcc", arch);
+            return paths::resolve_executable(Path::new(&mingw)).is_ok()
+                || paths::resolve_executable(Path::new("gcc")).is_ok();
+        }
+        paths::resolve_executable(Path::new("cc")).is_ok()
+    }
+
+    /// Gets the user-specified linker for a particular host or target, from
+    /// `target.<triple>.linker`, a matching `target.'cfg(...)'.linker`, or
+    /// `host.linker` for the host with `-Zhost-config`.
+    pub fn linker(&self, kind: CompileKind) -> Option<PathBuf> {
+        self.target_config(kind)
+            .linker
+            .as_ref()
+            .map(|l| l.val.resolve_program(self.config))
+    }
+
+    /// The `.json` target specification of `kind` if it is a custom target,
+    /// see `TargetInfo::target_spec_file`.
+    pub fn target_spec_file(&self, kind: CompileKind) -> Option<&serde_json::Value> {
+        self.info(kind).target_spec_file()
+    }
+
+    /// Gets the target configuration for a particular host or target.
+    pub fn target_config(&self, kind: CompileKind) -> &TargetConfig {
+        match kind {
+            CompileKind::Host => &self.host_config,
+            CompileKind::Target(s) => &self.target_config[&s],
+        }
+    }
+
+    /// If a build script is overridden, this returns the `BuildOutput` to use.
+    ///
+    /// `lib_name` is the `links` library name and `kind` is whether it is for
+    /// Host or Target.
+    pub fn script_override(&self, lib_name: &str, kind: CompileKind) -> Option<&BuildOutput> {
+        self.target_config(kind).links_overrides.get(lib_name)
+    }
+
+    /// The version of `rustc`, parsed from its `-vV` output.
+    ///
+    /// That output is already queried (and cached) when `rustc` is first
+    /// located, so this does not run `rustc` again.
+    pub fn verbose_version(&self) -> CargoResult<&RustcVersion> {
+        self.verbose_version
+            .try_borrow_with(|| RustcVersion::parse(&self.rustc.verbose_version))
+    }
+}
+
+/// The fields of `rustc -vV`, for example:
+///
+/// ```text
+/// rustc 1.64.0 (a55dd71d5 2022-09-19)
+/// binary: rustc
+/// commit-hash: a55dd71d5fb0ec5a6a3a9e8c27b2127ba491ce52
+/// commit-date: 2022-09-19
+/// host: x86_64-unknown-linux-gnu
+/// release: 1.64.0
+/// LLVM version: 14.0.6
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RustcVersion {
+    /// The `release` line, like `1.64.0` or `1.66.0-nightly`.
+    pub version: semver::Version,
+    /// The full hash of the commit `rustc` was built from, `None` if it
+    /// was reported as `unknown`.
+    pub commit_hash: Option<String>,
+    /// The date of that commit, like `2022-09-19`, `None` if unknown.
+    pub commit_date: Option<String>,
+    /// The release channel: "stable", "beta", "nightly" or "dev".
+    pub channel: String,
+}
+
+impl RustcVersion {
+    /// Parses the output of `rustc -vV`.
+    pub fn parse(verbose_version: &str) -> CargoResult<RustcVersion> {
+        let field = |name: &str| {
+            verbose_version
+                .lines()
+                .find_map(|line| line.strip_prefix(name)?.strip_prefix(": "))
+                .map(str::trim)
+        };
+        let known = |name: &str| {
+            field(name)
+                .filter(|value| *value != "unknown")
+                .map(str::to_string)
+        };
+        let release = field("release").ok_or_else(|| {
+            anyhow::format_err!(
+                "`rustc -vV` didn't have a line for `release`, got:\n{}",
+                verbose_version
+            )
+        })?;
+        let version = semver::Version::parse(release).with_context(|| {
+            format!(
+                "rustc version does not appear to be a valid semver version, from:\n{}",
+                verbose_version
+            )
+        })?;
+        let channel = match version.pre.as_str() {
+            "" => "stable",
+            pre if pre.starts_with("beta") => "beta",
+            "nightly" => "nightly",
+            _ => "dev",
+        }
+        .to_string();
+        Ok(RustcVersion {
+            version,
+            commit_hash: known("commit-hash"),
+            commit_date: known("commit-date"),
+            channel,
+        })
+    }
+}
+
+/// A file with the `cfg` values of some targets, captured ahead of time so
+/// dependencies can be resolved without querying `rustc`.
+///
+/// The file is JSON, with the `rustc -vV` output it was captured with and
+/// the `rustc --print=cfg` lines for each target:
+///
+/// ```json
+/// {
+///   "rustc": "rustc 1.64.0 (a55dd71d5 2022-09-19)\nbinary: rustc\n...",
+///   "targets": {
+///     "x86_64-unknown-linux-gnu": ["unix", "target_os=\"linux\""]
+///   }
+/// }
+/// ```
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CfgSnapshot {
+    /// The verbose version of the `rustc` the snapshot was captured with.
+    pub rustc: String,
+    /// The `--print=cfg` lines for each target name.
+    pub targets: BTreeMap<String, Vec<String>>,
+}
+
+impl CfgSnapshot {
+    /// Loads the snapshot at `path`, checking that it was captured with the
+    /// same `rustc` as the current one so stale `cfg` values aren't used.
+    pub fn load(path: &Path, rustc: &Rustc) -> CargoResult<CfgSnapshot> {
+        let contents = paths::read(path)?;
+        let snapshot: CfgSnapshot = serde_json::from_str(&contents)
+            .with_context(|| format!("failed to parse cfg snapshot `{}`", path.display()))?;
+        if snapshot.rustc != rustc.verbose_version {
+            anyhow::bail!(
+                "the cfg snapshot `{}` was captured with a different version of rustc\n\
+                 snapshot rustc:\n{}\n\
+                 current rustc:\n{}\n\
+                 Capture the snapshot again with the current rustc.",
+                path.display(),
+                snapshot.rustc.trim_end(),
+                rustc.verbose_version.trim_end()
+            );
+        }
+        Ok(snapshot)
+    }
+
+    /// Parses the `cfg` values for each target.
+    fn into_cfgs(self) -> CargoResult<HashMap<String, Vec<Cfg>>> {
+        self.targets
+            .into_iter()
+            .map(|(target, lines)| {
+                let mut cfg = lines
+                    .iter()
+                    .map(|line| Ok(Cfg::from_str(line)?))
+                    .collect::<CargoResult<Vec<_>>>()
+                    .with_context(|| {
+                        format!("failed to parse the cfg snapshot for `{}`", target)
+                    })?;
+                cfg.retain(TargetInfo::not_user_specific_cfg);
+                Ok((target, cfg))
+            })
+            .collect()
+    }
+}
+
+/// Structure used to deal with Rustdoc fingerprinting
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RustDocFingerprint {
+    pub rustc_vv: String,
+}
+
+impl RustDocFingerprint {
+    /// This function checks whether the latest version of `Rustc` used to compile this
+    /// `Workspace`'s docs was the same as the one is currently being used in this `cargo doc`
+    /// call.
+    ///
+    /// In case it's not, it takes care of removing the `doc/` folder as well as overwriting
+    /// the rustdoc fingerprint info in order to guarantee that we won't end up with mixed
+    /// versions of the `js/html/css` files that `rustdoc` autogenerates which do not have
+    /// any versioning.
+    pub fn check_rustdoc_fingerprint(cx: &Context<'_, '_>) -> CargoResult<()> {
+        if cx.bcx.config.cli_unstable().skip_rustdoc_fingerprint {
+            return Ok(());
+        }
+        let actual_rustdoc_target_data = RustDocFingerprint {
+            rustc_vv: cx.bcx.rustc().verbose_version.clone(),
+        };
+
+        let fingerprint_path = cx.files().host_root().join(".rustdoc_fingerprint.json");
+        let write_fingerprint = || -> CargoResult<()> {
+            paths::write(
+                &fingerprint_path,
+                serde_json::to_string(&actual_rustdoc_target_data)?,
+            )
+        };
+        let rustdoc_data = match paths::read(&fingerprint_path) {
+            Ok(rustdoc_data) => rustdoc_data,
+            // If the fingerprint does not exist, do not clear out the doc
+            // directories. Otherwise this ran into problems where projects
+            // like rustbuild were creating the doc directory before running
+            // `cargo doc` in a way that deleting it would break it.
+            Err(_) => return write_fingerprint(),
+        };
+        match serde_json::from_str::<RustDocFingerprint>(&rustdoc_data) {
+            Ok(fingerprint) => {
+                if fingerprint.rustc_vv == actual_rustdoc_target_data.rustc_vv {
+                    return Ok(());
+                } else {
+                    log::debug!(
+                        "doc fingerprint changed:\noriginal:\n{}\nnew:\n{}",
+                        fingerprint.rustc_vv,
+                        actual_rustdoc_target_data.rustc_vv
+                    );
+                }
+            }
+            Err(e) => {
+                log::debug!("could not deserialize {:?}: {}", fingerprint_path, e);
+            }
+        };
+        // Fingerprint does not match, delete the doc directories and write a new fingerprint.
+        log::debug!(
+            "fingerprint {:?} mismatch, clearing doc directories",
+            fingerprint_path
+        );
+        cx.bcx
+            .all_kinds
+            .iter()
+            .map(|kind| cx.files().layout(*kind).doc())
+            .filter(|path| path.exists())
+            .try_for_each(|path| clean_doc(path))?;
+        write_fingerprint()?;
+        return Ok(());
+
+        fn clean_doc(path: &Path) -> CargoResult<()> {
+            let entries = path
+                .read_dir()
+                .with_context(|| format!("failed to read directory `{}`", path.display()))?;
+            for entry in entries {
+                let entry = entry?;
+                // Don't remove hidden files. Rustdoc does not create them,
+                // but the user might have.
+                if entry
+                    .file_name()
+                    .to_str()
+                    .map_or(false, |name| name.starts_with('.'))
+                {
+                    continue;
+                }
+                let path = entry.path();
+                if entry.file_type()?.is_dir() {
+                    paths::remove_dir_all(path)?;
+                } else {
+                    paths::remove_file(path)?;
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        atomic_widths_from_cfg, attribute_file_names, check_flavor, crate_type_name_anomaly,
+        dedup_cfgs, dedup_flags, effective_codegen_units, effective_debug_assertions,
+        effective_overflow_checks, effective_split_debuginfo, effective_strip, emits_asm,
+        file_types_for, is_file_name_line, kind_dir, linker_map_suffix, max_atomic_width_from_cfg,
+        missing_target_features, parse_cfg_lines, parse_crate_type, pie_with_rustflags,
+        profile_settings_in_rustflags, spec_data_layout, spec_full_relro, spec_max_atomic_width,
+        spec_panic_strategy, spec_pie, split_env_flags, split_sysroot, target_cfgs,
+        unpacked_debuginfo_file_types,
+    };
+    use super::{Endian, EnvAbi, FileFlavor, FileType, LinkHardening, RustcVersion, TargetInfo};
+    use crate::core::compiler::CrateType;
+    use crate::core::compiler::{CompileKind, CompileTarget};
+    use crate::core::profiles::{PanicStrategy, Strip};
+    use crate::core::{Edition, Target};
+    use crate::util::interning::InternedString;
+    use crate::util::StableHasher;
+    use cargo_platform::{Cfg, CfgExpr};
+    use cargo_util::ProcessBuilder;
+    use std::collections::HashMap;
+    use std::hash::Hasher;
+    use std::path::PathBuf;
+    use std::str::FromStr;
+
+    fn flags(flags: &[&str]) -> Vec<String> {
+        flags.iter().map(|s| s.to_string()).collect()
+    }
+
+    fn cfgs(cfgs: &[&str]) -> Vec<Cfg> {
+        cfgs.iter().map(|c| c.parse().unwrap()).collect()
+    }
+
+    fn spec(json: &str) -> serde_json::Value {
+        serde_json::from_str(json).unwrap()
+    }
+
+    /// The pointer width and atomics of x86_64-unknown-linux-gnu, from
+    /// `--print=cfg`.
+    const X86_64_ATOMICS: &[&str] = &[
+        r#"target_pointer_width="64""#,
+        r#"target_has_atomic="8""#,
+        r#"target_has_atomic="16""#,
+        r#"target_has_atomic="32""#,
+        r#"target_has_atomic="64""#,
+        r#"target_has_atomic="ptr""#,
+    ];
+
+    /// A `TargetInfo` with the given `--print=cfg` lines and rustflags,
+    /// without running rustc.
+    fn info_with_cfg(cfg: &[&str], rustflags: &[&str]) -> TargetInfo {
+        TargetInfo::from_parts(
+            cfgs(cfg),
+            PathBuf::new(),
+            PathBuf::new(),
+            PathBuf::new(),
+            flags(rustflags),
+            HashMap::new(),
+        )
+    }
+
+    /// A `TargetInfo` that knows the file names of `crate_types`, without
+    /// running rustc.
+    fn info_with_crate_types(crate_types: &[(CrateType, &str, &str)]) -> TargetInfo {
+        TargetInfo::from_parts(
+            Vec::new(),
+            PathBuf::new(),
+            PathBuf::new(),
+            PathBuf::new(),
+            Vec::new(),
+            crate_types
+                .iter()
+                .map(|(ty, prefix, suffix)| {
+                    (
+                        ty.clone(),
+                        Some(vec![(prefix.to_string(), suffix.to_string())]),
+                    )
+                })
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn flavor_matches_crate_type() {
+        assert!(check_flavor(&CrateType::Rlib, &FileFlavor::Linkable).is_ok());
+        assert!(check_flavor(&CrateType::Dylib, &FileFlavor::Linkable).is_ok());
+        assert!(check_flavor(&CrateType::ProcMacro, &FileFlavor::Linkable).is_ok());
+        assert!(check_flavor(&CrateType::Bin, &FileFlavor::Normal).is_ok());
+        assert!(check_flavor(&CrateType::Cdylib, &FileFlavor::Normal).is_ok());
+        assert!(check_flavor(&CrateType::Staticlib, &FileFlavor::Normal).is_ok());
+    }
+
+    #[test]
+    fn flavor_rejects_nonsensical_combinations() {
+        assert!(check_flavor(&CrateType::Bin, &FileFlavor::Linkable).is_err());
+        assert!(check_flavor(&CrateType::Rlib, &FileFlavor::Normal).is_err());
+        assert!(check_flavor(&CrateType::Rlib, &FileFlavor::Rmeta).is_err());
+        assert!(check_flavor(&CrateType::Bin, &FileFlavor::DebugInfo).is_err());
+    }
+
+    #[test]
+    fn missing_target_features_are_reported() {
+        let cfg = vec![Cfg::KeyPair("target_arch".into(), "x86_64".into())];
+        let expr = CfgExpr::from_str(r#"target_feature = "avx2""#).unwrap();
+        assert_eq!(missing_target_features(&expr, &cfg), vec!["avx2"]);
+
+        let expr = CfgExpr::from_str(
+            r#"all(target_arch = "x86_64", target_feature = "avx2", target_feature = "fma")"#,
+        )
+        .unwrap();
+        assert_eq!(missing_target_features(&expr, &cfg), vec!["avx2", "fma"]);
+
+        // Not excluded because of target features.
+        let expr =
+            CfgExpr::from_str(r#"all(target_arch = "aarch64", target_feature = "neon")"#).unwrap();
+        assert!(missing_target_features(&expr, &cfg).is_empty());
+        let expr = CfgExpr::from_str(r#"not(target_feature = "avx2")"#).unwrap();
+        assert!(missing_target_features(&expr, &cfg).is_empty());
+    }
+
+    #[test]
+    fn simulator_targets() {
+        let is_simulator = |cfg: &[&str]| info_with_cfg(cfg, &[]).is_simulator();
+        let apple = |arch: &str, os: &str, abi: &str| {
+            let mut cfg = vec![
+                format!("target_arch=\"{}\"", arch),
+                format!("target_os=\"{}\"", os),
+                "target_vendor=\"apple\"".to_string(),
+            ];
+            if !abi.is_empty() {
+                cfg.push(format!("target_abi=\"{}\"", abi));
+            }
+            is_simulator(&cfg.iter().map(String::as_str).collect::<Vec<_>>())
+        };
+
+        // `-sim` triples, like `aarch64-apple-ios-sim`, have the `sim` ABI.
+        assert!(apple("aarch64", "ios", "sim"));
+        assert!(apple("aarch64", "watchos", "sim"));
+        assert!(apple("x86_64", "ios", "sim"));
+        // Older x86 simulator triples, like `x86_64-apple-ios`, don't.
+        assert!(apple("x86_64", "ios", ""));
+        assert!(apple("x86", "watchos", ""));
+        // Devices and macOS.
+        assert!(!apple("aarch64", "ios", ""));
+        assert!(!apple("aarch64", "ios", "macabi"));
+        assert!(!apple("x86_64", "ios", "macabi"));
+        assert!(!apple("x86_64", "macos", ""));
+        assert!(!is_simulator(&[r#"target_os="linux""#]));
+    }
+
+    #[test]
+    fn auxiliary_outputs() {
+        let name =
+            |prefix: &str, suffix: &str| Some(vec![(prefix.to_string(), suffix.to_string())]);
+        let mut info = info_with_crate_types(&[
+            (CrateType::Bin, "", ".exe"),
+            (CrateType::Cdylib, "", ".dll"),
+            (CrateType::Rlib, "lib", ".rlib"),
+        ]);
+        info.crate_types
+            .get_mut()
+            .insert(CrateType::Staticlib, None);
+        let bin = Target::bin_target(
+            "foo-bar",
+            None,
+            PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/main.rs"),
+            None,
+            Edition::Edition2021,
+        );
+        let lib = Target::lib_target(
+            "foo",
+            vec![CrateType::Cdylib, CrateType::Rlib],
+            PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/lib.rs"),
+            Edition::Edition2021,
+        );
+        let msvc = "x86_64-pc-windows-msvc";
+        let outputs = |crate_type: CrateType, target: &Target, strip: Strip| {
+            info.auxiliary_outputs(&crate_type, target, msvc, strip, None)
+                .unwrap()
+        };
+
+        // The `.pdb` keeps the underscores rustc gave it.
+        assert_eq!(
+            outputs(CrateType::Bin, &bin, Strip::None),
+            [(FileFlavor::DebugInfo, "foo_bar.pdb".to_string())]
+        );
+        assert!(outputs(
+            CrateType::Bin,
+            &bin,
+            Strip::Named(InternedString::new("symbols"))
+        )
+        .is_empty());
+        assert_eq!(
+            outputs(CrateType::Cdylib, &lib, Strip::None),
+            [
+                (FileFlavor::Auxiliary, "foo.dll.lib".to_string()),
+                (FileFlavor::Auxiliary, "foo.dll.exp".to_string()),
+                (FileFlavor::DebugInfo, "foo.pdb".to_string()),
+            ]
+        );
+        // A single file, and a crate type the target doesn't support.
+        assert!(outputs(CrateType::Rlib, &lib, Strip::None).is_empty());
+        assert!(outputs(CrateType::Staticlib, &lib, Strip::None).is_empty());
+    }
+
+    #[test]
+    fn uefi_executables() {
+        let target = Target::bin_target(
+            "foo",
+            None,
+            PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/main.rs"),
+            None,
+            Edition::Edition2021,
+        );
+        for reported in [".efi", ""] {
+            let info = info_with_crate_types(&[(CrateType::Bin, "", reported)]);
+            let file_types = info
+                .file_types(
+                    &CrateType::Bin,
+                    FileFlavor::Normal,
+                    "x86_64-unknown-uefi",
+                    Strip::None,
+                    None,
+                )
+                .unwrap()
+                .unwrap();
+            assert_eq!(file_types.len(), 1, "{:?}", file_types);
+            assert_eq!(file_types[0].flavor, FileFlavor::Normal);
+            assert_eq!(file_types[0].uplift_filename(&target), "foo.efi");
+        }
+        // Other targets keep executables without an extension.
+        let info = info_with_crate_types(&[(CrateType::Bin, "", "")]);
+        let file_types = info
+            .file_types(
+                &CrateType::Bin,
+                FileFlavor::Normal,
+                "x86_64-unknown-linux-gnu",
+                Strip::None,
+                None,
+            )
+            .unwrap()
+            .unwrap();
+        assert_eq!(file_types[0].uplift_filename(&target), "foo");
+    }
+
+    #[test]
+    fn split_debuginfo_files() {
+        let target = Target::bin_target(
+            "foo-bar",
+            None,
+            PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/main.rs"),
+            None,
+            Edition::Edition2021,
+        );
+        let files = |crate_type: CrateType, split_debuginfo: Option<&str>| {
+            let (prefix, suffix) = match crate_type {
+                CrateType::Bin => ("", ""),
+                _ => ("lib", ".so"),
+            };
+            file_types_for(
+                crate_type,
+                FileFlavor::Normal,
+                &[(prefix.to_string(), suffix.to_string())],
+                "x86_64-unknown-linux-gnu",
+                Strip::None,
+                split_debuginfo.map(InternedString::new),
+            )
+            .into_iter()
+            .map(|ft| {
+                (
+                    ft.flavor.clone(),
+                    ft.output_filename(&target, Some("abc")),
+                    ft.uplift_filename(&target),
+                )
+            })
+            .collect::<Vec<_>>()
+        };
+        let exe = (
+            FileFlavor::Normal,
+            "foo_bar-abc".to_string(),
+            "foo-bar".to_string(),
+        );
+        assert_eq!(
+            files(CrateType::Bin, Some("packed")),
+            [
+                exe.clone(),
+                (
+                    FileFlavor::DebugInfo,
+                    "foo_bar-abc.dwp".to_string(),
+                    "foo-bar.dwp".to_string()
+                )
+            ]
+        );
+        assert_eq!(
+            files(CrateType::Cdylib, Some("packed"))[1],
+            (
+                FileFlavor::DebugInfo,
+                "libfoo_bar-abc.dwp".to_string(),
+                "libfoo_bar.dwp".to_string()
+            )
+        );
+        // The files of `unpacked` don't depend on the crate type, see
+        // `unpacked_debuginfo_file_types`.
+        assert_eq!(files(CrateType::Bin, Some("unpacked")), [exe.clone()]);
+        assert_eq!(files(CrateType::Bin, Some("off")), [exe.clone()]);
+        assert_eq!(files(CrateType::Bin, None), [exe]);
+        assert_eq!(files(CrateType::Staticlib, Some("packed")).len(), 1);
+
+        let unpacked = |triple: &str| {
+            unpacked_debuginfo_file_types(triple)
+                .into_iter()
+                .map(|ft| {
+                    assert_eq!(ft.flavor, FileFlavor::DebugInfo);
+                    assert!(ft.is_per_codegen_unit());
+                    ft.output_filename(&target, Some("abc"))
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            unpacked("x86_64-unknown-linux-gnu"),
+            ["foo_bar-abc.*.rcgu.o", "foo_bar-abc.*.rcgu.dwo"]
+        );
+        assert_eq!(unpacked("aarch64-apple-darwin"), ["foo_bar-abc.*.rcgu.o"]);
+        assert!(unpacked("x86_64-pc-windows-msvc").is_empty());
+
+        let packed = Some(InternedString::new("packed"));
+        assert_eq!(effective_split_debuginfo(packed, &[]), packed);
+        assert_eq!(
+            effective_split_debuginfo(None, &flags(&["-C", "split-debuginfo=packed"])),
+            packed
+        );
+        assert_eq!(
+            effective_split_debuginfo(packed, &flags(&["-Csplit-debuginfo=off"])),
+            Some(InternedString::new("off"))
+        );
+    }
+
+    #[test]
+    fn strip_from_rustflags_overrides_profile() {
+        let symbols = Strip::Named(InternedString::new("symbols"));
+        assert_eq!(effective_strip(symbols, &[]), symbols);
+        assert_eq!(
+            effective_strip(Strip::None, &flags(&["-Cstrip=symbols"])),
+            symbols
+        );
+        assert_eq!(
+            effective_strip(Strip::None, &flags(&["-C", "strip=symbols"])),
+            symbols
+        );
+        assert_eq!(
+            effective_strip(symbols, &flags(&["-Cstrip=symbols", "-Cstrip=none"])),
+            Strip::None
+        );
+        assert_eq!(
+            effective_strip(symbols, &flags(&["-Copt-level=3"])),
+            symbols
+        );
+    }
+
+    #[test]
+    fn asm_emit_in_rustflags() {
+        assert!(emits_asm(&flags(&["--emit=asm"])));
+        assert!(emits_asm(&flags(&["--emit", "link,asm"])));
+        assert!(!emits_asm(&flags(&["--emit=asm=out.s"])));
+        assert!(!emits_asm(&flags(&["--emit=llvm-ir"])));
+        assert!(!emits_asm(&[]));
+    }
+
+    #[test]
+    fn asm_per_codegen_unit_names() {
+        let target = Target::bin_target(
+            "foo-bar",
+            None,
+            PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/main.rs"),
+            None,
+            Edition::Edition2021,
+        );
+        let single = FileType::new_asm();
+        assert!(!single.is_per_codegen_unit());
+        assert_eq!(
+            single.output_filename(&target, Some("abc")),
+            "foo_bar-abc.s"
+        );
+        assert_eq!(single.uplift_filename(&target), "foo_bar.s");
+
+        let per_cgu = FileType::new_asm_per_codegen_unit();
+        assert!(per_cgu.is_per_codegen_unit());
+        assert_eq!(
+            per_cgu.output_filename(&target, Some("abc")),
+            "foo_bar-abc.*.rcgu.s"
+        );
+        assert_eq!(per_cgu.output_filename(&target, None), "foo_bar.*.rcgu.s");
+        assert_eq!(per_cgu.uplift_filename(&target), "foo_bar.*.rcgu.s");
+    }
+
+    #[test]
+    fn codegen_units_in_rustflags() {
+        assert_eq!(effective_codegen_units(Some(4), &[]), Some(4));
+        assert_eq!(
+            effective_codegen_units(Some(4), &flags(&["-C", "codegen-units=1"])),
+            Some(1)
+        );
+        assert_eq!(
+            effective_codegen_units(None, &flags(&["-Ccodegen-units=1", "-Ccodegen-units=8"])),
+            Some(8)
+        );
+        assert_eq!(
+            effective_codegen_units(None, &flags(&["-Copt-level=3"])),
+            None
+        );
+    }
+
+    #[test]
+    fn crate_type_name_anomalies() {
+        assert_eq!(
+            crate_type_name_anomaly(&CrateType::Dylib, "lib", ".so"),
+            None
+        );
+        assert_eq!(crate_type_name_anomaly(&CrateType::Bin, "", ""), None);
+        assert_eq!(crate_type_name_anomaly(&CrateType::Bin, "", ".exe"), None);
+        assert_eq!(
+            crate_type_name_anomaly(&CrateType::Dylib, "", ""),
+            Some("the suffix is empty")
+        );
+        assert_eq!(
+            crate_type_name_anomaly(&CrateType::Rlib, "lib", "rlib"),
+            Some("the suffix does not start with `.`")
+        );
+        assert_eq!(
+            crate_type_name_anomaly(&CrateType::Staticlib, "lib", ".a warning: x"),
+            Some("the name contains whitespace or a path separator")
+        );
+    }
+
+    #[test]
+    fn supports_crate_type() {
+        let info = info_with_crate_types(&[(CrateType::Rlib, "lib", ".rlib")]);
+        info.crate_types
+            .borrow_mut()
+            .insert(CrateType::Cdylib, None);
+        assert!(info.supports_crate_type(&CrateType::Rlib).unwrap());
+        assert!(info.supports_crate_type(&CrateType::Lib).unwrap());
+        assert!(!info.supports_crate_type(&CrateType::Cdylib).unwrap());
+    }
+
+    #[test]
+    fn supported_crate_types() {
+        // wasm32-unknown-unknown
+        let info = info_with_crate_types(&[
+            (CrateType::Bin, "", ".wasm"),
+            (CrateType::Rlib, "lib", ".rlib"),
+            (CrateType::Cdylib, "", ".wasm"),
+            (CrateType::Staticlib, "lib", ".a"),
+        ]);
+        info.crate_types
+            .borrow_mut()
+            .extend([(CrateType::Dylib, None), (CrateType::ProcMacro, None)]);
+        assert_eq!(
+            info.supported_crate_types().unwrap(),
+            ["bin", "rlib", "cdylib", "staticlib"]
+        );
+    }
+
+    #[test]
+    fn env_flags_splitting() {
+        let split = split_env_flags;
+        assert_eq!(split(""), Vec::<String>::new());
+        assert_eq!(
+            split("  -Copt-level=3   --cfg foo "),
+            ["-Copt-level=3", "--cfg", "foo"]
+        );
+        assert_eq!(
+            split(r#""-Clink-arg=-Wl,-rpath,/a b""#),
+            ["-Clink-arg=-Wl,-rpath,/a b"]
+        );
+        assert_eq!(
+            split("--remap-path-prefix '/my dir=/src' -g"),
+            ["--remap-path-prefix", "/my dir=/src", "-g"]
+        );
+        assert_eq!(split(r#"\"a b\""#), [r#""a"#, r#"b""#]);
+        assert_eq!(split(r#""a \"b\" \\ \c""#), [r#"a "b" \\ \c"#]);
+        assert_eq!(split(r"'a \' b"), [r"a \", "b"]);
+        // Quotes in the middle of a word and Windows paths are unchanged.
+        assert_eq!(
+            split(r#"--cfg feature="foo""#),
+            ["--cfg", r#"feature="foo""#]
+        );
+        assert_eq!(split(r"-LC:\libs\x64"), [r"-LC:\libs\x64"]);
+        assert_eq!(split(r"-L\\server\libs"), [r"-L\\server\libs"]);
+        assert_eq!(split(r"-LC:\libs\ -Cfoo"), [r"-LC:\libs\", "-Cfoo"]);
+        assert_eq!(
+            split(r#""-L\\server\my libs" -Cfoo"#),
+            [r"-L\\server\my libs", "-Cfoo"]
+        );
+        // An unterminated quote runs to the end.
+        assert_eq!(split("'a b"), ["a b"]);
+    }
+
+    #[test]
+    fn several_files_per_crate_type() {
+        let cmd = ProcessBuilder::new("rustc");
+        // An emscripten bin, probed on its own.
+        let output = "___.js\n___.wasm\n";
+        let files = output
+            .lines()
+            .filter(|line| is_file_name_line(line))
+            .count();
+        let names = parse_crate_type(
+            &CrateType::Bin,
+            &cmd,
+            output,
+            "",
+            &mut output.lines(),
+            files,
+        )
+        .unwrap()
+        .unwrap();
+        let owned = |names: &[(&str, &str)]| -> Vec<(String, String)> {
+            names
+                .iter()
+                .map(|(prefix, suffix)| (prefix.to_string(), suffix.to_string()))
+                .collect()
+        };
+        assert_eq!(names, owned(&[("", ".js"), ("", ".wasm")]));
+
+        // The same files whether or not rustc reports the `.wasm` file.
+        let triple = "wasm32-unknown-emscripten";
+        let summary = |names: &[(String, String)]| -> Vec<(String, FileFlavor)> {
+            file_types_for(
+                CrateType::Bin,
+                FileFlavor::Normal,
+                names,
+                triple,
+                Strip::None,
+                None,
+            )
+            .into_iter()
+            .map(|ft| (ft.suffix, ft.flavor))
+            .collect()
+        };
+        let expected = vec![
+            (".js".to_string(), FileFlavor::Normal),
+            (".wasm".to_string(), FileFlavor::Auxiliary),
+            (".wasm.map".to_string(), FileFlavor::DebugInfo),
+        ];
+        assert_eq!(summary(&names), expected);
+        assert_eq!(summary(&owned(&[("", ".js")])), expected);
+
+        // The sysroot and cfg after the names are not file names.
+        assert!(is_file_name_line("lib___.rlib"));
+        assert!(!is_file_name_line("/home/me/.rustup/toolchains/stable"));
+        assert!(!is_file_name_line(r"C:\Users\me\my___toolchain"));
+        assert!(!is_file_name_line("unix"));
+        // Not enough lines for the crate type.
+        let output = "___.js\n";
+        assert!(
+            parse_crate_type(&CrateType::Bin, &cmd, output, "", &mut output.lines(), 2).is_err()
+        );
+
+        // Each crate type takes the lines that look like its files.
+        let linux = [
+            "___",
+            "lib___.rlib",
+            "lib___.so",
+            "lib___.so",
+            "lib___.a",
+            "lib___.so",
+        ];
+        let one_each = vec![Some(1); 6];
+        assert_eq!(attribute_file_names(&linux, ""), one_each);
+        let msvc = [
+            "___.exe",
+            "lib___.rlib",
+            "___.dll",
+            "___.dll",
+            "___.lib",
+            "___.dll",
+        ];
+        assert_eq!(attribute_file_names(&msvc, ""), one_each);
+        // An emscripten bin with two files, and the dylib reported as
+        // unsupported.
+        let error = "warning: dropping unsupported crate type `dylib` \
+                     for target `wasm32-unknown-emscripten`";
+        assert_eq!(
+            attribute_file_names(
+                &[
+                    "___.js",
+                    "___.wasm",
+                    "lib___.rlib",
+                    "___.wasm",
+                    "lib___.a",
+                    "___.wasm"
+                ],
+                error
+            ),
+            [Some(2), Some(1), Some(0), Some(1), Some(1), Some(1)]
+        );
+        // A silently dropped dylib would shift the cdylib's line to it, so the
+        // library types are probed on their own.
+        let only_bin_and_rlib = [Some(1), Some(1), None, None, None, None];
+        assert_eq!(
+            attribute_file_names(
+                &["___", "lib___.rlib", "lib___.so", "lib___.a", "lib___.so"],
+                ""
+            ),
+            only_bin_and_rlib
+        );
+        // The same goes for an extra line.
+        assert_eq!(
+            attribute_file_names(&[&linux[..], &["lib___.so"]].concat(), ""),
+            only_bin_and_rlib
+        );
+        // Without the rlib, it isn't known where the bin's files end either.
+        assert_eq!(
+            attribute_file_names(&["___", "___.rlib", "lib___.so"], ""),
+            vec![None; 6]
+        );
+    }
+
+    #[test]
+    fn file_types_multi() {
+        let info = info_with_crate_types(&[
+            (CrateType::Rlib, "lib", ".rlib"),
+            (CrateType::Cdylib, "", ".dll"),
+        ]);
+        info.crate_types
+            .borrow_mut()
+            .insert(CrateType::ProcMacro, None);
+        let results = info
+            .file_types_multi(
+                &[
+                    (CrateType::Lib, FileFlavor::Linkable),
+                    (CrateType::ProcMacro, FileFlavor::Linkable),
+                    (CrateType::Cdylib, FileFlavor::Normal),
+                ],
+                "x86_64-pc-windows-msvc",
+                Strip::None,
+                None,
+            )
+            .unwrap();
+        let summary: Vec<_> = results
+            .iter()
+            .map(|(crate_type, file_types)| {
+                let suffixes = file_types.as_ref().map(|file_types| {
+                    file_types
+                        .iter()
+                        .map(|ft| ft.suffix.as_str())
+                        .collect::<Vec<_>>()
+                });
+                (crate_type.clone(), suffixes)
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (CrateType::Lib, Some(vec![".rlib"])),
+                (CrateType::ProcMacro, None),
+                (
+                    CrateType::Cdylib,
+                    Some(vec![".dll", ".dll.lib", ".dll.exp", ".pdb"])
+                ),
+            ]
+        );
+        // `Lib` is reported as the `Rlib` it produces.
+        let (_, lib) = &results[0];
+        assert_eq!(lib.as_ref().unwrap()[0].crate_type, Some(CrateType::Rlib));
+    }
+
+    #[test]
+    fn primary_file_type_only() {
+        let info = info_with_crate_types(&[
+            (CrateType::Dylib, "", ".dll"),
+            (CrateType::Cdylib, "", ".dll"),
+        ]);
+        info.crate_types
+            .borrow_mut()
+            .insert(CrateType::ProcMacro, None);
+        let dylib = info
+            .primary_file_type(&CrateType::Dylib, "x86_64-pc-windows-msvc")
+            .unwrap()
+            .unwrap();
+        assert_eq!(dylib.suffix, ".dll");
+        assert_eq!(dylib.flavor, FileFlavor::Linkable);
+        assert_eq!(dylib.crate_type, Some(CrateType::Dylib));
+        let cdylib = info
+            .primary_file_type(&CrateType::Cdylib, "x86_64-pc-windows-msvc")
+            .unwrap()
+            .unwrap();
+        assert_eq!(cdylib.suffix, ".dll");
+        assert_eq!(cdylib.flavor, FileFlavor::Normal);
+        assert!(info
+            .primary_file_type(&CrateType::ProcMacro, "x86_64-pc-windows-msvc")
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn staticlib_suffix_follows_abi() {
+        let target = Target::lib_target(
+            "foo",
+            vec![CrateType::Staticlib],
+            PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/lib.rs"),
+            Edition::Edition2021,
+        );
+        for (triple, prefix, suffix, name) in [
+            ("x86_64-pc-windows-msvc", "", ".lib", "foo.lib"),
+            ("x86_64-pc-windows-gnu", "lib", ".a", "libfoo.a"),
+            ("x86_64-unknown-linux-gnu", "lib", ".a", "libfoo.a"),
+        ] {
+            let info = info_with_crate_types(&[(CrateType::Staticlib, prefix, suffix)]);
+            assert_eq!(info.static_lib_extension(), suffix.trim_start_matches('.'));
+            let file_types = info
+                .file_types(
+                    &CrateType::Staticlib,
+                    FileFlavor::Normal,
+                    triple,
+                    Strip::None,
+                    None,
+                )
+                .unwrap()
+                .unwrap();
+            // No import libraries or debug files for static libraries.
+            assert_eq!(file_types.len(), 1, "{}", triple);
+            assert_eq!(file_types[0].output_filename(&target, None), name);
+        }
+        let info = info_with_crate_types(&[]);
+        assert_eq!(info.static_lib_extension(), "");
+    }
+
+    #[test]
+    fn raw_cfgs_keep_proc_macro() {
+        let raw = cfgs(&["unix", "proc_macro", "debug_assertions", "unix"]);
+        let (cfg, duplicates) = target_cfgs(&raw);
+        let names = |cfgs: &[Cfg]| cfgs.iter().map(ToString::to_string).collect::<Vec<_>>();
+        assert_eq!(names(&cfg), ["unix", "debug_assertions"]);
+        assert_eq!(names(&duplicates), ["unix"]);
+
+        let mut info = info_with_crate_types(&[]);
+        info.raw_cfg = raw;
+        info.cfg = cfg;
+        assert!(info
+            .raw_cfg()
+            .contains(&Cfg::Name("proc_macro".to_string())));
+        assert!(!info.cfg().contains(&Cfg::Name("proc_macro".to_string())));
+    }
+
+    #[test]
+    fn dedup_cfgs_keeps_first_occurrence() {
+        let raw = cfgs(&[
+            "unix",
+            "target_os=\"linux\"",
+            "unix",
+            "target_os=\"macos\"",
+            "target_os=\"linux\"",
+            "target_os",
+        ]);
+        let (cfgs, duplicates) = dedup_cfgs(raw);
+        let cfgs: Vec<String> = cfgs.iter().map(ToString::to_string).collect();
+        let duplicates: Vec<String> = duplicates.iter().map(ToString::to_string).collect();
+        // A name and a key/value pair with the same key are distinct.
+        assert_eq!(
+            cfgs,
+            [
+                "unix",
+                "target_os = \"linux\"",
+                "target_os = \"macos\"",
+                "target_os"
+            ]
+        );
+        assert_eq!(duplicates, ["unix", "target_os = \"linux\""]);
+    }
+
+    #[test]
+    fn overflow_checks_overrides() {
+        assert!(effective_overflow_checks(true, &[]));
+        assert!(!effective_overflow_checks(false, &[]));
+        assert!(!effective_overflow_checks(
+            true,
+            &flags(&["-C", "overflow-checks=off"])
+        ));
+        assert!(effective_overflow_checks(
+            false,
+            &flags(&["-Coverflow-checks"])
+        ));
+        assert!(effective_overflow_checks(
+            false,
+            &flags(&["-Coverflow-checks=no", "--codegen=overflow-checks=yes"])
+        ));
+        assert!(effective_overflow_checks(
+            true,
+            &flags(&["-Cdebug-assertions=off"])
+        ));
+    }
+
+    #[test]
+    fn profile_settings_overridden_by_rustflags() {
+        let found = |f: &[&str]| profile_settings_in_rustflags(&flags(f));
+        let setting =
+            |key: &'static str, flag: &str, value: &str| (key, flag.to_string(), value.to_string());
+        assert!(found(&["--cfg", "foo", "-Ctarget-cpu=native"]).is_empty());
+        assert_eq!(
+            found(&[
+                "-C",
+                "opt-level=1",
+                "-g",
+                "--codegen=lto=thin",
+                "-Ccodegen-units=4",
+                "-Cpanic=abort"
+            ]),
+            [
+                setting("opt-level", "-Copt-level=1", "1"),
+                setting("debug", "-g", "2"),
+                setting("lto", "-Clto=thin", "thin"),
+                setting("codegen-units", "-Ccodegen-units=4", "4"),
+                setting("panic", "-Cpanic=abort", "abort"),
+            ]
+        );
+        // The last flag for a setting is the one that counts.
+        assert_eq!(
+            found(&["-Copt-level=3", "-Cdebuginfo=1", "-O"]),
+            [
+                setting("debug", "-Cdebuginfo=1", "1"),
+                setting("opt-level", "-O", "2"),
+            ]
+        );
+        // The spellings of `lto` are normalized.
+        assert_eq!(found(&["-Clto"]), [setting("lto", "-Clto", "fat")]);
+        assert_eq!(found(&["-Clto=yes"]), [setting("lto", "-Clto=yes", "fat")]);
+        assert_eq!(found(&["-Clto=no"]), [setting("lto", "-Clto=no", "off")]);
+    }
+
+    #[test]
+    fn debug_assertions_overrides() {
+        assert!(effective_debug_assertions(true, &[]));
+        assert!(!effective_debug_assertions(
+            true,
+            &flags(&["-C", "debug-assertions=n"])
+        ));
+        assert!(effective_debug_assertions(
+            false,
+            &flags(&["-Cdebug-assertions"])
+        ));
+        assert!(!effective_debug_assertions(
+            false,
+            &flags(&["-Coverflow-checks=on"])
+        ));
+    }
+
+    #[test]
+    fn link_hardening() {
+        let hardening = |crt_static, pie, full_relro| LinkHardening {
+            crt_static,
+            pie,
+            full_relro,
+        };
+
+        let linux = [r#"target_os="linux""#, r#"target_env="gnu""#];
+        assert_eq!(
+            info_with_cfg(&linux, &[]).link_hardening(),
+            hardening(false, true, true)
+        );
+        assert_eq!(
+            info_with_cfg(&linux, &["-Crelocation-model=static"]).link_hardening(),
+            hardening(false, false, true)
+        );
+        assert_eq!(
+            info_with_cfg(
+                &[
+                    r#"target_os="linux""#,
+                    r#"target_env="musl""#,
+                    r#"target_feature="crt-static""#
+                ],
+                &[]
+            )
+            .link_hardening(),
+            hardening(true, true, true)
+        );
+        // Apple platforms are PIE, but not ELF.
+        assert_eq!(
+            info_with_cfg(&[r#"target_os="macos""#, r#"target_vendor="apple""#], &[])
+                .link_hardening(),
+            hardening(false, true, false)
+        );
+        assert_eq!(
+            info_with_cfg(&[r#"target_os="windows""#], &[]).link_hardening(),
+            hardening(false, false, false)
+        );
+        assert_eq!(
+            info_with_cfg(&[r#"target_os="none""#], &[]).link_hardening(),
+            hardening(false, false, false)
+        );
+
+        // The target spec takes precedence over the guess from the OS.
+        let mut info = info_with_cfg(&linux, &[]);
+        info.spec_full_relro = Some(false);
+        assert_eq!(info.link_hardening(), hardening(false, true, false));
+        let mut info = info_with_cfg(&[r#"target_os="none""#], &[]);
+        info.spec_full_relro = Some(true);
+        assert_eq!(info.link_hardening(), hardening(false, false, true));
+
+        assert_eq!(
+            spec_full_relro(&spec(r#"{"relro-level": "full"}"#)),
+            Some(true)
+        );
+        assert_eq!(
+            spec_full_relro(&spec(r#"{"relro-level": "partial"}"#)),
+            Some(false)
+        );
+        assert_eq!(spec_full_relro(&spec(r#"{"os": "none"}"#)), Some(false));
+        assert_eq!(spec_full_relro(&spec("[]")), None);
+    }
+
+    #[test]
+    fn pie_overrides() {
+        assert!(pie_with_rustflags(true, &[]));
+        assert!(!pie_with_rustflags(false, &[]));
+        assert!(!pie_with_rustflags(
+            true,
+            &flags(&["-Crelocation-model=static"])
+        ));
+        assert!(!pie_with_rustflags(
+            true,
+            &flags(&["-C", "link-arg=-no-pie"])
+        ));
+        assert!(!pie_with_rustflags(
+            true,
+            &flags(&["-Clink-args=-lfoo -no-pie"])
+        ));
+        assert!(pie_with_rustflags(
+            true,
+            &flags(&["-Clink-args=-lfoo -no-pie-fallback"])
+        ));
+        assert!(!pie_with_rustflags(
+            true,
+            &flags(&["-Clink-arg=-Wl,-no-pie"])
+        ));
+        assert!(!pie_with_rustflags(
+            true,
+            &flags(&["-Clink-arg=-Wl,--as-needed,--no-pie"])
+        ));
+        assert!(!pie_with_rustflags(true, &flags(&["-Clink-arg=-nopie"])));
+        assert!(pie_with_rustflags(true, &flags(&["-Clink-arg=-pie"])));
+        assert!(pie_with_rustflags(
+            false,
+            &flags(&["-C", "relocation-model=pie"])
+        ));
+        assert!(pie_with_rustflags(
+            true,
+            &flags(&["--codegen=relocation-model=pic"])
+        ));
+        assert!(!pie_with_rustflags(
+            false,
+            &flags(&["-Crelocation-model=pic"])
+        ));
+        // The last option wins.
+        assert!(pie_with_rustflags(
+            true,
+            &flags(&["-Crelocation-model=static", "-Crelocation-model=pie"])
+        ));
+
+        assert_eq!(
+            spec_pie(&spec(r#"{"position-independent-executables": true}"#)),
+            Some(true)
+        );
+        assert_eq!(spec_pie(&spec(r#"{"os": "none"}"#)), Some(false));
+        assert_eq!(
+            spec_pie(&spec(
+                r#"{"position-independent-executables": true, "relocation-model": "static"}"#
+            )),
+            Some(false)
+        );
+        assert_eq!(spec_pie(&spec("[]")), None);
+    }
+
+    #[test]
+    fn max_atomic_width() {
+        let x86_64 = cfgs(X86_64_ATOMICS);
+        assert_eq!(max_atomic_width_from_cfg(&x86_64), Some(64));
+        // Only `ptr`, which follows the pointer width.
+        let ptr_only = cfgs(&["target_pointer_width=\"32\"", "target_has_atomic=\"ptr\""]);
+        assert_eq!(max_atomic_width_from_cfg(&ptr_only), Some(32));
+        // thumbv6m-none-eabi
+        let thumbv6m = cfgs(&["target_pointer_width=\"32\"", "target_os=\"none\""]);
+        assert_eq!(max_atomic_width_from_cfg(&thumbv6m), None);
+
+        assert_eq!(
+            spec_max_atomic_width(&spec(r#"{"max-atomic-width": 128}"#)),
+            Some(128)
+        );
+        assert_eq!(spec_max_atomic_width(&spec(r#"{"os": "none"}"#)), None);
+    }
+
+    #[test]
+    fn atomic_widths_and_data_layout() {
+        let x86_64 = cfgs(X86_64_ATOMICS);
+        assert_eq!(atomic_widths_from_cfg(&x86_64), [8, 16, 32, 64]);
+        // thumbv7m-none-eabi, without 64-bit atomics.
+        let thumbv7m = cfgs(&[
+            "target_pointer_width=\"32\"",
+            "target_has_atomic=\"8\"",
+            "target_has_atomic=\"16\"",
+            "target_has_atomic=\"32\"",
+            "target_has_atomic=\"ptr\"",
+        ]);
+        assert_eq!(atomic_widths_from_cfg(&thumbv7m), [8, 16, 32]);
+        // thumbv6m-none-eabi only has atomic loads and stores.
+        let thumbv6m = cfgs(&[
+            "target_pointer_width=\"32\"",
+            "target_has_atomic_load_store=\"8\"",
+            "target_has_atomic_load_store=\"32\"",
+        ]);
+        assert_eq!(atomic_widths_from_cfg(&thumbv6m), Vec::<u32>::new());
+
+        let thumbv7m_spec = spec(
+            r#"{"arch": "arm", "data-layout": "e-m:e-p:32:32-Fi8-i64:64-v128:64:128-a:0:32-n32-S64"}"#,
+        );
+        assert_eq!(
+            spec_data_layout(&thumbv7m_spec).as_deref(),
+            Some("e-m:e-p:32:32-Fi8-i64:64-v128:64:128-a:0:32-n32-S64")
+        );
+        assert_eq!(spec_data_layout(&spec("{}")), None);
+    }
+
+    #[test]
+    fn kind_dirs() {
+        assert_eq!(kind_dir(&CompileKind::Host), PathBuf::new());
+        let target = CompileTarget::new("aarch64-unknown-linux-gnu").unwrap();
+        assert_eq!(
+            kind_dir(&CompileKind::Target(target)).join("debug/deps"),
+            PathBuf::from("aarch64-unknown-linux-gnu/debug/deps")
+        );
+    }
+
+    #[test]
+    fn typed_cfg_values() {
+        let info = |cfg: &[&str]| info_with_cfg(cfg, &[]);
+        // x86_64-unknown-linux-gnu
+        let linux = info(&[
+            "target_pointer_width=\"64\"",
+            "target_os=\"linux\"",
+            "target_family=\"unix\"",
+            "target_endian=\"little\"",
+            "unix",
+        ]);
+        assert_eq!(linux.pointer_width(), Some(64));
+        assert_eq!(linux.target_os(), Some("linux"));
+        assert_eq!(linux.target_family(), Some("unix"));
+        assert_eq!(linux.endian(), Some(Endian::Little));
+        // powerpc-unknown-linux-gnu
+        let powerpc = info(&["target_pointer_width=\"32\"", "target_endian=\"big\""]);
+        assert_eq!(powerpc.pointer_width(), Some(32));
+        assert_eq!(powerpc.endian(), Some(Endian::Big));
+        // thumbv7em-none-eabihf has an OS of `none`, and no family.
+        let thumb = info(&["target_os=\"none\""]);
+        assert_eq!(thumb.target_os(), Some("none"));
+        assert_eq!(thumb.target_family(), None);
+
+        let empty = info(&[]);
+        assert_eq!(empty.pointer_width(), None);
+        assert_eq!(empty.target_os(), None);
+        assert_eq!(empty.target_family(), None);
+        assert_eq!(empty.endian(), None);
+        let odd = info(&["target_pointer_width=\"wide\"", "target_endian=\"middle\""]);
+        assert_eq!(odd.pointer_width(), None);
+        assert_eq!(odd.endian(), None);
+    }
+
+    #[test]
+    fn std_available_without_sysroot() {
+        let std_available = |cfg: &[&str]| info_with_cfg(cfg, &[]).std_available();
+        // x86_64-unknown-linux-gnu
+        assert!(std_available(&[
+            "target_os=\"linux\"",
+            "target_family=\"unix\"",
+            "unix"
+        ]));
+        // wasm32-unknown-unknown
+        assert!(std_available(&[
+            "target_os=\"unknown\"",
+            "target_family=\"wasm\""
+        ]));
+        // thumbv7em-none-eabihf
+        assert!(!std_available(&["target_os=\"none\"", "target_env=\"\""]));
+        // nvptx64-nvidia-cuda
+        assert!(!std_available(&["target_os=\"cuda\""]));
+        assert!(!std_available(&[]));
+    }
+
+    #[test]
+    fn environment_abi() {
+        let abi = |cfg: &[&str]| EnvAbi::from_cfg(&cfgs(cfg));
+        // x86_64-unknown-linux-gnu
+        assert_eq!(
+            abi(&["target_os=\"linux\"", "target_env=\"gnu\""]),
+            EnvAbi::Gnu
+        );
+        // x86_64-unknown-linux-musl
+        assert_eq!(
+            abi(&["target_os=\"linux\"", "target_env=\"musl\""]),
+            EnvAbi::Musl
+        );
+        // x86_64-pc-windows-msvc
+        assert_eq!(
+            abi(&["target_os=\"windows\"", "target_env=\"msvc\""]),
+            EnvAbi::Msvc
+        );
+        // thumbv7em-none-eabihf
+        assert_eq!(
+            abi(&["target_os=\"none\"", "target_env=\"\""]),
+            EnvAbi::None
+        );
+        assert_eq!(abi(&["target_os=\"none\""]), EnvAbi::None);
+        assert_eq!(
+            abi(&["target_env=\"uclibc\""]),
+            EnvAbi::Other("uclibc".to_string())
+        );
+    }
+
+    #[test]
+    fn parse_rustc_verbose_version() {
+        let version = RustcVersion::parse(
+            "rustc 1.66.0-nightly (6b3ede3f7 2022-10-13)\n\
+             binary: rustc\n\
+             commit-hash: 6b3ede3f7bc502eba7bbd202b4b9312d812adcd7\n\
+             commit-date: 2022-10-13\n\
+             host: x86_64-unknown-linux-gnu\n\
+             release: 1.66.0-nightly\n\
+             LLVM version: 15.0.2\n",
+        )
+        .unwrap();
+        assert_eq!(
+            version.version,
+            semver::Version::parse("1.66.0-nightly").unwrap()
+        );
+        assert_eq!(
+            version.commit_hash.as_deref(),
+            Some("6b3ede3f7bc502eba7bbd202b4b9312d812adcd7")
+        );
+        assert_eq!(version.commit_date.as_deref(), Some("2022-10-13"));
+        assert_eq!(version.channel, "nightly");
+
+        // A `rustc` built from a source tarball doesn't know its commit.
+        let version = RustcVersion::parse(
+            "rustc 1.64.0\n\
+             binary: rustc\n\
+             commit-hash: unknown\n\
+             commit-date: unknown\n\
+             host: x86_64-unknown-linux-gnu\n\
+             release: 1.64.0\n",
+        )
+        .unwrap();
+        assert_eq!(version.commit_hash, None);
+        assert_eq!(version.commit_date, None);
+        assert_eq!(version.channel, "stable");
+
+        assert!(RustcVersion::parse("rustc 1.64.0\nbinary: rustc\n").is_err());
+    }
+
+    #[test]
+    fn rustflags_layered_per_crate_type() {
+        let mut info = info_with_crate_types(&[]);
+        info.rustflags = flags(&["-Copt-level=1"]);
+        info.crate_type_rustflags
+            .insert(CrateType::Cdylib, flags(&["-Csymbol-mangling-version=v0"]));
+        assert_eq!(
+            info.rustflags_for_crate_type("cdylib"),
+            ["-Copt-level=1", "-Csymbol-mangling-version=v0"]
+        );
+        assert_eq!(info.rustflags_for_crate_type("rlib"), ["-Copt-level=1"]);
+        assert_eq!(info.rustflags_for_crate_type("lib"), ["-Copt-level=1"]);
+    }
+
+    #[test]
+    fn sysroot_and_cfg_by_content() {
+        let sysroot = std::env::temp_dir().join(format!("cargo-sysroot-{}", std::process::id()));
+        let sysroot = sysroot.to_str().unwrap();
+        // An extra informational line before the sysroot, and cfg values on
+        // both sides of it.
+        let output = format!(
+            "debug_assertions\n\
+             some future print: 1\n\
+             {}\n\
+             unix\n\
+             target_os=\"linux\"\n",
+            sysroot
+        );
+        let (found, rest) = split_sysroot(output.lines()).unwrap();
+        assert_eq!(found, sysroot);
+        let cfgs = parse_cfg_lines(&rest).unwrap();
+        assert_eq!(
+            cfgs,
+            [
+                Cfg::from_str("debug_assertions").unwrap(),
+                Cfg::from_str("unix").unwrap(),
+                Cfg::from_str("target_os=\"linux\"").unwrap(),
+            ]
+        );
+
+        // Without an absolute path, the first line is the sysroot.
+        let (found, rest) = split_sysroot("sysroot\nunix".lines()).unwrap();
+        assert_eq!(found, "sysroot");
+        assert_eq!(rest, ["unix"]);
+        assert!(split_sysroot("".lines()).is_none());
+        // Anything else that isn't a cfg is still an error.
+        assert!(parse_cfg_lines(&["unix", "123"]).is_err());
+    }
+
+    #[test]
+    fn from_parts_without_rustc() {
+        let raw_cfg = cfgs(&[
+            "unix",
+            "proc_macro",
+            "debug_assertions",
+            r#"target_os="linux""#,
+            r#"target_family="unix""#,
+            r#"target_pointer_width="64""#,
+            r#"target_endian="little""#,
+        ]);
+        let crate_types = HashMap::from([
+            (
+                CrateType::Rlib,
+                Some(vec![("lib".to_string(), ".rlib".to_string())]),
+            ),
+            (
+                CrateType::Cdylib,
+                Some(vec![("lib".to_string(), ".so".to_string())]),
+            ),
+            (CrateType::Dylib, None),
+        ]);
+        let info = TargetInfo::from_parts(
+            raw_cfg,
+            PathBuf::from("/sysroot"),
+            PathBuf::from("/sysroot/lib"),
+            PathBuf::from("/sysroot/lib/rustlib/x86_64-unknown-linux-gnu/lib"),
+            flags(&["-Copt-level=1"]),
+            crate_types,
+        );
+
+        assert_eq!(info.target_os(), Some("linux"));
+        assert_eq!(info.target_family(), Some("unix"));
+        assert_eq!(info.pointer_width(), Some(64));
+        assert_eq!(info.endian(), Some(Endian::Little));
+        assert!(info
+            .raw_cfg()
+            .contains(&Cfg::Name("proc_macro".to_string())));
+        assert!(!info.cfg().contains(&Cfg::Name("proc_macro".to_string())));
+        assert_eq!(info.sysroot, PathBuf::from("/sysroot"));
+        assert_eq!(info.rustflags, ["-Copt-level=1"]);
+
+        let file_types = |crate_type: CrateType, flavor| {
+            info.file_types(
+                &crate_type,
+                flavor,
+                "x86_64-unknown-linux-gnu",
+                Strip::None,
+                None,
+            )
+            .unwrap()
+        };
+        let lib = file_types(CrateType::Lib, FileFlavor::Linkable).unwrap();
+        assert_eq!(
+            lib[0].uplift_filename(&Target::lib_target(
+                "foo",
+                vec![CrateType::Lib],
+                PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/lib.rs"),
+                Edition::Edition2021,
+            )),
+            "libfoo.rlib"
+        );
+        let cdylib = file_types(CrateType::Cdylib, FileFlavor::Normal).unwrap();
+        assert_eq!(cdylib[0].suffix, ".so");
+        assert!(file_types(CrateType::Dylib, FileFlavor::Linkable).is_none());
+    }
+
+    #[test]
+    fn build_state_hash_covers_inputs() {
+        let make = |sysroot: &str, rustflags: &[&str], cfg: &[&str]| {
+            TargetInfo::from_parts(
+                cfgs(cfg),
+                PathBuf::from(sysroot),
+                PathBuf::from(sysroot).join("lib"),
+                PathBuf::from(sysroot).join("lib/rustlib/x86_64-unknown-linux-gnu/lib"),
+                flags(rustflags),
+                HashMap::from([(
+                    CrateType::Rlib,
+                    Some(vec![("lib".to_string(), ".rlib".to_string())]),
+                )]),
+            )
+        };
+        let hash = |info: &TargetInfo| {
+            let mut hasher = StableHasher::new();
+            info.hash_build_state(info.cfg(), &mut hasher);
+            hasher.finish()
+        };
+
+        let base = hash(&make("/sysroot", &["-Copt-level=1"], &["unix"]));
+        // Identical inputs give an identical hash.
+        assert_eq!(base, hash(&make("/sysroot", &["-Copt-level=1"], &["unix"])));
+        // Changing any input changes it.
+        assert_ne!(base, hash(&make("/other", &["-Copt-level=1"], &["unix"])));
+        assert_ne!(base, hash(&make("/sysroot", &["-Copt-level=2"], &["unix"])));
+        assert_ne!(
+            base,
+            hash(&make("/sysroot", &["-Copt-level=1"], &["windows"]))
+        );
+        let mut info = make("/sysroot", &["-Copt-level=1"], &["unix"]);
+        info.crate_types.get_mut().insert(CrateType::Dylib, None);
+        assert_ne!(base, hash(&info));
+    }
+
+    #[test]
+    fn wasm_bin_file_types() {
+        let summary = |triple: &str, suffix: &str| -> Vec<(String, FileFlavor)> {
+            let names = [(String::new(), suffix.to_string())];
+            file_types_for(
+                CrateType::Bin,
+                FileFlavor::Normal,
+                &names,
+                triple,
+                Strip::None,
+                None,
+            )
+            .into_iter()
+            .map(|ft| (ft.suffix, ft.flavor))
+            .collect()
+        };
+        for triple in ["wasm32-unknown-unknown", "wasm64-unknown-unknown"] {
+            assert_eq!(
+                summary(triple, ".wasm"),
+                [(".wasm".to_string(), FileFlavor::Normal)]
+            );
+        }
+        for triple in ["wasm32-unknown-emscripten", "wasm64-unknown-emscripten"] {
+            assert_eq!(
+                summary(triple, ".js"),
+                [
+                    (".js".to_string(), FileFlavor::Normal),
+                    (".wasm".to_string(), FileFlavor::Auxiliary),
+                    (".wasm.map".to_string(), FileFlavor::DebugInfo),
+                ]
+            );
+        }
+        // Only wasm targets load a `.wasm` file from a `.js` one.
+        assert_eq!(
+            summary("x86_64-unknown-linux-gnu", ".js"),
+            [(".js".to_string(), FileFlavor::Normal)]
+        );
+    }
+
+    #[test]
+    fn import_lib_can_be_left_out() {
+        let mut info = info_with_crate_types(&[(CrateType::Cdylib, "", ".dll")]);
+        let suffixes = |info: &TargetInfo, triple: &str| -> Vec<String> {
+            info.file_types(
+                &CrateType::Cdylib,
+                FileFlavor::Normal,
+                triple,
+                Strip::None,
+                None,
+            )
+            .unwrap()
+            .unwrap()
+            .into_iter()
+            .map(|ft| ft.suffix)
+            .collect()
+        };
+        assert_eq!(
+            suffixes(&info, "x86_64-pc-windows-msvc"),
+            [".dll", ".dll.lib", ".dll.exp", ".pdb"]
+        );
+        assert_eq!(suffixes(&info, "x86_64-pc-windows-gnu"), [".dll", ".dll.a"]);
+
+        info.import_lib = false;
+        assert_eq!(suffixes(&info, "x86_64-pc-windows-msvc"), [".dll", ".pdb"]);
+        assert_eq!(suffixes(&info, "x86_64-pc-windows-gnu"), [".dll"]);
+    }
+
+    #[test]
+    fn link_search_paths_include_rustflags() {
+        let rustflags = [
+            "-L",
+            "native=/foo",
+            "-L/bar",
+            "-Ldependency=/baz",
+            "-Lnative=/foo",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+        let info = TargetInfo::from_parts(
+            Vec::new(),
+            PathBuf::from("/sysroot"),
+            PathBuf::from("/sysroot/lib"),
+            PathBuf::from("/sysroot/lib/rustlib/x86_64-unknown-linux-gnu/lib"),
+            rustflags,
+            HashMap::new(),
+        );
+        assert_eq!(
+            info.link_search_paths(),
+            [
+                PathBuf::from("/sysroot/lib/rustlib/x86_64-unknown-linux-gnu/lib"),
+                PathBuf::from("/sysroot/lib"),
+                PathBuf::from("/foo"),
+                PathBuf::from("/bar"),
+                PathBuf::from("/baz"),
+            ]
+        );
+    }
+
+    #[test]
+    fn dedup_flags_keeps_last() {
+        let dedup = |f: &[&str]| dedup_flags(flags(f));
+
+        // Simple flags and options with a separate value.
+        assert_eq!(
+            dedup(&[
+                "--cfg",
+                "foo",
+                "-Dwarnings",
+                "--cfg",
+                "bar",
+                "--cfg",
+                "foo",
+                "-Dwarnings"
+            ]),
+            ["--cfg", "bar", "--cfg", "foo", "-Dwarnings"]
+        );
+        // Lints keep their relative order.
+        assert_eq!(
+            dedup(&["-A", "unused", "-D", "unused", "-A", "unused"]),
+            ["-D", "unused", "-A", "unused"]
+        );
+        // The last `-C target-cpu` wins, however it is spelled.
+        assert_eq!(
+            dedup(&[
+                "-Ctarget-cpu=native",
+                "--cfg",
+                "a",
+                "-C",
+                "target-cpu=x86-64-v2",
+                "--codegen=target-cpu=native",
+            ]),
+            ["--cfg", "a", "--codegen=target-cpu=native"]
+        );
+        // Lint flags are the same however they are spelled.
+        assert_eq!(
+            dedup(&[
+                "-D",
+                "warnings",
+                "-Dwarnings",
+                "-Wunused",
+                "--deny=warnings"
+            ]),
+            ["-Wunused", "--deny=warnings"]
+        );
+        // Options that accumulate are left alone, even when repeated.
+        assert_eq!(
+            dedup(&[
+                "-Ctarget-feature=+avx",
+                "-C",
+                "target-feature=+sse4.1",
+                "-C",
+                "target-feature=+avx",
+            ]),
+            [
+                "-Ctarget-feature=+avx",
+                "-C",
+                "target-feature=+sse4.1",
+                "-C",
+                "target-feature=+avx"
+            ]
+        );
+        // So are link arguments, where position matters.
+        let link_args = [
+            "-l",
+            "static=a",
+            "-Clink-arg=-Wl,--whole-archive",
+            "-l",
+            "static=b",
+            "-Clink-arg=-Wl,--whole-archive",
+            "-l",
+            "static=a",
+        ];
+        assert_eq!(dedup(&link_args), link_args);
+        // And rustdoc options, whose values are not mistaken for flags.
+        let rustdoc_args = [
+            "--html-in-header",
+            "a.html",
+            "--html-in-header",
+            "a.html",
+            "--extend-css",
+            "--cfg",
+            "--crate-version",
+            "1.0",
+            "--default-theme",
+            "ayu",
+            "--cfg",
+            "doc",
+        ];
+        assert_eq!(dedup(&rustdoc_args), rustdoc_args);
+        // A value that looks like a `-C` option is not one.
+        assert_eq!(
+            dedup(&["--cfg", "-Copt-level=1", "-Copt-level=3"]),
+            ["--cfg", "-Copt-level=1", "-Copt-level=3"]
+        );
+    }
+
+    #[test]
+    fn thumbv7em_defaults_to_panic_abort() {
+        // From `rustc --print=cfg --target thumbv7em-none-eabi`.
+        let mut raw_cfg = [
+            r#"panic="abort""#,
+            r#"target_abi="eabi""#,
+            r#"target_arch="arm""#,
+            r#"target_endian="little""#,
+            r#"target_has_atomic="32""#,
+            r#"target_has_atomic="ptr""#,
+            r#"target_os="none""#,
+            r#"target_pointer_width="32""#,
+            r#"target_vendor="unknown""#,
+        ];
+        let thumbv7em = info_with_cfg(&raw_cfg, &[]);
+        assert_eq!(thumbv7em.default_panic_strategy(), PanicStrategy::Abort);
+
+        // `-Cpanic` changes the cfg, but not the target's default.
+        raw_cfg[0] = r#"panic="unwind""#;
+        let with_unwind = info_with_cfg(&raw_cfg, &["-Cpanic=unwind"]);
+        assert_eq!(with_unwind.default_panic_strategy(), PanicStrategy::Abort);
+
+        let linux = info_with_cfg(&[r#"panic="unwind""#, r#"target_os="linux""#], &[]);
+        assert_eq!(linux.default_panic_strategy(), PanicStrategy::Unwind);
+
+        assert_eq!(
+            spec_panic_strategy(&spec(r#"{"panic-strategy": "abort"}"#)),
+            PanicStrategy::Abort
+        );
+        assert_eq!(
+            spec_panic_strategy(&spec(r#"{"os": "none"}"#)),
+            PanicStrategy::Unwind
+        );
+    }
+
+    #[test]
+    fn linker_map_file_types() {
+        assert_eq!(
+            linker_map_suffix(&flags(&["-C", "link-arg=-Wl,-Map=%.map"])),
+            Some(".map".to_string())
+        );
+        assert_eq!(
+            linker_map_suffix(&flags(&["-Clink-arg=-Wl,--gc-sections,-Map,%"])),
+            Some(".map".to_string())
+        );
+        assert_eq!(
+            linker_map_suffix(&flags(&["-C", "link-args=--Map=%.txt -s"])),
+            Some(".txt".to_string())
+        );
+        // A fixed path can't be told apart between units.
+        assert_eq!(
+            linker_map_suffix(&flags(&[
+                "-Clink-arg=-Wl,-Map=%.map",
+                "-Clink-arg=-Map=out.map"
+            ])),
+            None
+        );
+        assert_eq!(linker_map_suffix(&flags(&["--cfg", "-Map=%"])), None);
+
+        let mut info = info_with_crate_types(&[
+            (CrateType::Bin, "", ".exe"),
+            (CrateType::Rlib, "lib", ".rlib"),
+        ]);
+        info.rustflags = flags(&["-C", "link-arg=-Wl,-Map=%.map"]);
+        let summary =
+            |crate_type: CrateType, flavor: FileFlavor| -> Vec<(String, String, FileFlavor)> {
+                info.file_types(
+                    &crate_type,
+                    flavor,
+                    "x86_64-pc-windows-gnu",
+                    Strip::None,
+                    None,
+                )
+                .unwrap()
+                .unwrap()
+                .into_iter()
+                .map(|ft| (ft.prefix, ft.suffix, ft.flavor))
+                .collect()
+            };
+        assert_eq!(
+            summary(CrateType::Bin, FileFlavor::Normal),
+            [
+                (String::new(), ".exe".to_string(), FileFlavor::Normal),
+                (String::new(), ".exe.map".to_string(), FileFlavor::Auxiliary),
+            ]
+        );
+        // Nothing is linked for an rlib.
+        assert_eq!(
+            summary(CrateType::Rlib, FileFlavor::Linkable),
+            [("lib".to_string(), ".rlib".to_string(), FileFlavor::Linkable)]
+        );
+    }
 }
@@ -1,3 +1,4 @@
+use std::cell::RefCell;
 use std::collections::{BTreeSet, HashMap};
 use std::env;
 use std::ffi::{OsStr, OsString};
@@ -102,6 +103,10 @@ pub struct Compilation<'cfg> {
     primary_rustc_process: Option<ProcessBuilder>,
 
     target_runners: HashMap<CompileKind, Option<(PathBuf, Vec<String>)>>,
+    /// Targets that can't run natively on the host and have no runner
+    /// configured, with whether the target is a simulator. A warning is
+    /// emitted the first time one of these is run.
+    missing_runners: RefCell<HashMap<CompileKind, (String, bool)>>,
 }
 
 impl<'cfg> Compilation<'cfg> {
@@ -157,6 +162,18 @@ impl<'cfg> Compilation<'cfg> {
                 .chain(Some(&CompileKind::Host))
                 .map(|kind| Ok((*kind, target_runner(bcx, *kind)?)))
                 .collect::<CargoResult<HashMap<_, _>>>()?,
+            missing_runners: RefCell::new(
+                bcx.build_config
+                    .requested_kinds
+                    .iter()
+                    .filter(|kind| bcx.target_data.needs_runner(**kind))
+                    .map(|kind| {
+                        let name = bcx.target_data.short_name(kind).to_string();
+                        let is_simulator = bcx.target_data.info(*kind).is_simulator();
+                        (*kind, (name, is_simulator))
+                    })
+                    .collect(),
+            ),
         })
     }
 
@@ -248,6 +265,19 @@ impl<'cfg> Compilation<'cfg> {
             builder.arg(cmd);
             builder
         } else {
+            if let Some((target, is_simulator)) = self.missing_runners.borrow_mut().remove(&kind) {
+                let mut msg = format!(
+                    "executables built for `{}` probably cannot run on the host `{}`\n\
+                     configure a runner with `target.{}.runner` to run them",
+                    target, self.host, target
+                );
+                if is_simulator {
+                    msg.push_str(
+                        ", such as a script that installs and launches them in a simulator",
+                    );
+                }
+                self.config.shell().warn(msg)?;
+            }
             ProcessBuilder::new(cmd)
         };
         self.fill_env(builder, pkg, script_meta, kind, false)
@@ -353,20 +383,28 @@ impl<'cfg> Compilation<'cfg> {
             )
             .cwd(pkg.root());
 
-        // Apply any environment variables from the config
-        for (key, value) in self.config.env_config()?.iter() {
-            // never override a value that has already been set by cargo
-            if cmd.get_envs().contains_key(key) {
-                continue;
-            }
+        apply_env_config(self.config, &mut cmd)?;
 
-            if value.is_force() || env::var_os(key).is_none() {
-                cmd.env(key, value.resolve(self.config));
-            }
+        Ok(cmd)
+    }
+}
+
+/// Applies the variables from the `[env]` config table to `cmd`.
+///
+/// This is used both for running build steps and for probing rustc about a
+/// target, so that both see the same environment.
+pub fn apply_env_config(config: &Config, cmd: &mut ProcessBuilder) -> CargoResult<()> {
+    for (key, value) in config.env_config()?.iter() {
+        // never override a value that has already been set by cargo
+        if cmd.get_envs().contains_key(key) {
+            continue;
         }
 
-        Ok(cmd)
+        if value.is_force() || env::var_os(key).is_none() {
+            cmd.env(key, value.resolve(config));
+        }
     }
+    Ok(())
 }
 
 /// Prepares a rustc_tool process with additional environment variables
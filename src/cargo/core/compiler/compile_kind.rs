@@ -139,7 +139,11 @@ impl CompileTarget {
         // If `name` ends in `.json` then it's likely a custom target
         // specification. Canonicalize the path to ensure that different builds
         // with different paths always produce the same result.
-        let path = Path::new(name)
+        let path = Path::new(name);
+        if !path.exists() {
+            return Err(target_spec_not_found(name));
+        }
+        let path = path
             .canonicalize()
             .with_context(|| format!("target path {:?} is not a valid file", name))?;
 
@@ -150,6 +154,28 @@ impl CompileTarget {
         Ok(CompileTarget { name: name.into() })
     }
 
+    /// Whether this is a custom target given as the path to a `.json` target
+    /// specification, rather than a target name.
+    pub fn is_spec_file(&self) -> bool {
+        self.name.ends_with(".json")
+    }
+
+    /// Reads and parses the target specification of a custom target given as
+    /// a `.json` file. Returns `None` for a target name.
+    ///
+    /// This reads the file every time, see `TargetInfo::target_spec_file` for
+    /// a parsed copy that is kept.
+    pub fn load_spec(&self) -> CargoResult<Option<serde_json::Value>> {
+        if !self.is_spec_file() {
+            return Ok(None);
+        }
+        let contents = fs::read_to_string(self.name)
+            .with_context(|| format!("failed to read target specification `{}`", self.name))?;
+        let spec = serde_json::from_str(&contents)
+            .with_context(|| format!("failed to parse target specification `{}`", self.name))?;
+        Ok(Some(spec))
+    }
+
     /// Returns the full unqualified name of this target, suitable for passing
     /// to `rustc` directly.
     ///
@@ -200,3 +226,20 @@ impl CompileTarget {
         hasher.finish()
     }
 }
+
+/// The error for a `--target` that names a `.json` target specification
+/// which does not exist.
+pub(crate) fn target_spec_not_found(path: &str) -> anyhow::Error {
+    let stem = Path::new(path)
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or(path);
+    anyhow::format_err!(
+        "target specification file `{}` does not exist\n\
+         A target ending in `.json` must be the path to a target specification. \
+         To use a custom target from a directory listed in `RUST_TARGET_PATH`, \
+         pass its name without `.json`, like `--target {}`.",
+        path,
+        stem
+    )
+}
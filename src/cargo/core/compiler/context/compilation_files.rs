@@ -1,15 +1,22 @@
 use std::collections::HashMap;
 use std::env;
 use std::fmt;
+use std::fs;
 use std::hash::{Hash, Hasher};
+use std::io;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
+use anyhow::Context as _;
 use lazycell::LazyCell;
 use log::debug;
 
 use super::{BuildContext, CompileKind, Context, FileFlavor, Layout};
-use crate::core::compiler::{CompileMode, CompileTarget, CrateType, FileType, Unit};
+use crate::core::compiler::{
+    effective_split_debuginfo, effective_strip, CompileMode, CompileTarget, CrateType, FileType,
+    Unit,
+};
+use crate::core::profiles::Strip;
 use crate::core::{Target, TargetKind, Workspace};
 use crate::util::{self, CargoResult, StableHasher};
 
@@ -111,7 +118,7 @@ pub struct CompilationFiles<'a, 'cfg> {
 }
 
 /// Info about a single file emitted by the compiler.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct OutputFile {
     /// Absolute path to the file that will be produced by the build process.
     pub path: PathBuf,
@@ -122,6 +129,10 @@ pub struct OutputFile {
     pub export_path: Option<PathBuf>,
     /// Type of the file (library / debug symbol / else).
     pub flavor: FileFlavor,
+    /// Whether this stands for one file per codegen unit, in which case the
+    /// file names above have a `*` in place of the codegen unit's name. See
+    /// `OutputFile::files`.
+    pub per_codegen_unit: bool,
 }
 
 impl OutputFile {
@@ -132,6 +143,56 @@ impl OutputFile {
             None => &self.path,
         }
     }
+
+    /// The files on disk this output stands for.
+    ///
+    /// An output with one file per codegen unit stands for every file rustc
+    /// wrote matching its `path`, each with its own `hardlink` and
+    /// `export_path`, sorted by path. Any other output stands for itself,
+    /// whether it exists or not.
+    pub fn files(&self) -> CargoResult<Vec<OutputFile>> {
+        if !self.per_codegen_unit {
+            return Ok(vec![self.clone()]);
+        }
+        let file_name = |path: &Path| path.file_name().unwrap().to_str().unwrap().to_string();
+        let pattern = file_name(&self.path);
+        let (head, tail) = pattern.split_once('*').unwrap();
+        let dir = self.path.parent().unwrap();
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => {
+                return Err(e)
+                    .with_context(|| format!("failed to read directory `{}`", dir.display()))
+            }
+        };
+        let with_codegen_unit =
+            |path: &PathBuf, cgu: &str| path.with_file_name(file_name(path).replacen('*', cgu, 1));
+        let mut files = Vec::new();
+        for entry in entries {
+            let name = entry?.file_name();
+            let name = match name.to_str() {
+                Some(name) => name,
+                None => continue,
+            };
+            if name.len() <= head.len() + tail.len()
+                || !name.starts_with(head)
+                || !name.ends_with(tail)
+            {
+                continue;
+            }
+            let cgu = &name[head.len()..name.len() - tail.len()];
+            files.push(OutputFile {
+                path: dir.join(name),
+                hardlink: self.hardlink.as_ref().map(|p| with_codegen_unit(p, cgu)),
+                export_path: self.export_path.as_ref().map(|p| with_codegen_unit(p, cgu)),
+                flavor: self.flavor.clone(),
+                per_codegen_unit: false,
+            });
+        }
+        files.sort_unstable_by(|a, b| a.path.cmp(&b.path));
+        Ok(files)
+    }
 }
 
 impl<'a, 'cfg: 'a> CompilationFiles<'a, 'cfg> {
@@ -346,6 +407,8 @@ impl<'a, 'cfg: 'a> CompilationFiles<'a, 'cfg> {
                 CompileMode::Build,
                 &TargetKind::Bin,
                 bcx.target_data.short_name(&kind),
+                Strip::None,
+                None,
             )
             .expect("target must support `bin`");
 
@@ -386,6 +449,12 @@ impl<'a, 'cfg: 'a> CompilationFiles<'a, 'cfg> {
             return None;
         }
 
+        // The linked file refers to the debug info of each codegen unit by
+        // its path in the output directory.
+        if file_type.flavor == FileFlavor::DebugInfo && file_type.is_per_codegen_unit() {
+            return None;
+        }
+
         // - Binaries: The user always wants to see these, even if they are
         //   implicitly built (for example for integration tests).
         // - dylibs: This ensures that the dynamic linker pulls in all the
@@ -437,6 +506,7 @@ impl<'a, 'cfg: 'a> CompilationFiles<'a, 'cfg> {
                     hardlink: None,
                     export_path: None,
                     flavor: FileFlavor::Normal,
+                    per_codegen_unit: false,
                 }]
             }
             CompileMode::RunCustomBuild => {
@@ -459,6 +529,7 @@ impl<'a, 'cfg: 'a> CompilationFiles<'a, 'cfg> {
                     hardlink: None,
                     export_path: None,
                     flavor: FileFlavor::Normal,
+                    per_codegen_unit: false,
                 }]
             }
             CompileMode::Test
@@ -484,8 +555,23 @@ impl<'a, 'cfg: 'a> CompilationFiles<'a, 'cfg> {
 
         let info = bcx.target_data.info(unit.kind);
         let triple = bcx.target_data.short_name(&unit.kind);
-        let (file_types, unsupported) =
-            info.rustc_outputs(unit.mode, unit.target.kind(), triple)?;
+        let mut rustflags = bcx.rustflags_args(unit).to_vec();
+        rustflags.extend(bcx.crate_type_rustflags_args(unit));
+        let strip = effective_strip(unit.profile.strip, &rustflags);
+        // Cargo only passes the profile's `split-debuginfo` if rustc
+        // supports it, see `build_base_args`.
+        let profile_split_debuginfo = unit
+            .profile
+            .split_debuginfo
+            .filter(|_| info.supports_split_debuginfo);
+        let split_debuginfo = effective_split_debuginfo(profile_split_debuginfo, &rustflags);
+        let (mut file_types, unsupported) = info.rustc_outputs(
+            unit.mode,
+            unit.target.kind(),
+            triple,
+            strip,
+            split_debuginfo,
+        )?;
         if file_types.is_empty() {
             if !unsupported.is_empty() {
                 let unsupported_strs: Vec<_> = unsupported.iter().map(|ct| ct.as_str()).collect();
@@ -505,6 +591,10 @@ impl<'a, 'cfg: 'a> CompilationFiles<'a, 'cfg> {
             );
         }
 
+        if !unit.mode.is_check() {
+            file_types.extend(bcx.asm_file_type(unit));
+        }
+
         // Convert FileType to OutputFile.
         let mut outputs = Vec::new();
         for file_type in file_types {
@@ -528,6 +618,7 @@ impl<'a, 'cfg: 'a> CompilationFiles<'a, 'cfg> {
                 path,
                 hardlink,
                 export_path,
+                per_codegen_unit: file_type.is_per_codegen_unit(),
                 flavor: file_type.flavor,
             });
         }
@@ -696,7 +787,11 @@ fn should_use_metadata(bcx: &BuildContext<'_, '_>, unit: &Unit) -> bool {
     let short_name = bcx.target_data.short_name(&unit.kind);
     if (unit.target.is_dylib()
         || unit.target.is_cdylib()
-        || (unit.target.is_executable() && short_name == "wasm32-unknown-emscripten")
+        || (unit.target.is_executable()
+            && matches!(
+                short_name,
+                "wasm32-unknown-emscripten" | "wasm64-unknown-emscripten"
+            ))
         || (unit.target.is_executable() && short_name.contains("msvc")))
         && unit.pkg.package_id().source_id().is_path()
         && env::var("__CARGO_DEFAULT_LIB_METADATA").is_err()
@@ -11,6 +11,7 @@ use anyhow::{bail, Context as _};
 use filetime::FileTime;
 use jobserver::Client;
 
+use super::artifact_map::write_artifact_map;
 use super::build_plan::BuildPlan;
 use super::custom_build::{self, BuildDeps, BuildScriptOutputs, BuildScripts};
 use super::fingerprint::Fingerprint;
@@ -169,6 +170,8 @@ impl<'a, 'cfg> Context<'a, 'cfg> {
         if build_plan {
             plan.set_inputs(self.build_plan_inputs()?);
             plan.output_plan(self.bcx.config);
+        } else if let Some(path) = &self.bcx.build_config.artifact_map {
+            write_artifact_map(&self, path)?;
         }
 
         // Collect the result of the build into `self.compilation`.
@@ -251,7 +254,12 @@ impl<'a, 'cfg> Context<'a, 'cfg> {
                         }
                     }
                 }
-                args.extend(self.bcx.rustdocflags_args(unit).iter().map(Into::into));
+                args.extend(
+                    self.bcx
+                        .rustdoc_test_flags_args(unit)
+                        .iter()
+                        .map(Into::into),
+                );
 
                 use super::MessageFormat;
                 let format = match self.bcx.build_config.message_format {
@@ -1,3 +1,4 @@
+use super::build_context::PARENT_ENCODED_RUSTFLAGS;
 use super::job::{Freshness, Job, Work};
 use super::{fingerprint, Context, LinkType, Unit};
 use crate::core::compiler::artifact;
@@ -272,10 +273,9 @@ fn build_work(cx: &mut Context<'_, '_>, unit: &Unit) -> CargoResult<Job> {
             cmd.env("RUSTC_WORKSPACE_WRAPPER", wrapper);
         }
     }
-    cmd.env(
-        "CARGO_ENCODED_RUSTFLAGS",
-        bcx.rustflags_args(unit).join("\x1f"),
-    );
+    let encoded_rustflags = bcx.rustflags_args(unit).join("\x1f");
+    cmd.env("CARGO_ENCODED_RUSTFLAGS", &encoded_rustflags);
+    cmd.env(PARENT_ENCODED_RUSTFLAGS, &encoded_rustflags);
     cmd.env_remove("RUSTFLAGS");
 
     // Gather the set of native dependencies that this package has along with
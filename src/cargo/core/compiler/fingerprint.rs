@@ -1303,11 +1303,12 @@ fn calculate_normal(cx: &mut Context<'_, '_>, unit: &Unit) -> CargoResult<Finger
     // hashed to take up less space on disk as we just need to know when things
     // change.
     let extra_flags = if unit.mode.is_doc() {
-        cx.bcx.rustdocflags_args(unit)
+        cx.bcx.rustdocflags_args(unit).to_vec()
     } else {
-        cx.bcx.rustflags_args(unit)
-    }
-    .to_vec();
+        let mut flags = cx.bcx.rustflags_args(unit).to_vec();
+        flags.extend(cx.bcx.crate_type_rustflags_args(unit));
+        flags
+    };
 
     let profile_hash = util::hash_u64((
         &unit.profile,
@@ -307,6 +307,7 @@ enum Message {
     BuildPlanMsg(String, ProcessBuilder, Arc<Vec<OutputFile>>),
     Stdout(String),
     Stderr(String),
+    Warning(String),
     Diagnostic {
         id: JobId,
         level: String,
@@ -363,6 +364,17 @@ impl<'a, 'cfg> JobState<'a, 'cfg> {
         Ok(())
     }
 
+    /// Prints a warning from Cargo itself about this job, rather than one
+    /// from the compiler.
+    pub fn warning(&self, warning: String) -> CargoResult<()> {
+        if let Some(dedupe) = self.output {
+            dedupe.config.shell().warn(warning)?;
+        } else {
+            self.messages.push_bounded(Message::Warning(warning));
+        }
+        Ok(())
+    }
+
     pub fn emit_diag(&self, level: String, diag: String) -> CargoResult<()> {
         if let Some(dedupe) = self.output {
             let emitted = dedupe.emit_diag(&diag)?;
@@ -669,6 +681,9 @@ impl<'cfg> DrainState<'cfg> {
                 shell.print_ansi_stderr(err.as_bytes())?;
                 shell.err().write_all(b"\n")?;
             }
+            Message::Warning(warning) => {
+                cx.bcx.config.shell().warn(warning)?;
+            }
             Message::Diagnostic { id, level, diag } => {
                 let emitted = self.diag_dedupe.emit_diag(&diag)?;
                 if level == "warning" {
@@ -1,4 +1,5 @@
 pub mod artifact;
+mod artifact_map;
 mod build_config;
 mod build_context;
 mod build_plan;
@@ -36,11 +37,15 @@ use log::{debug, trace};
 
 pub use self::build_config::{BuildConfig, CompileMode, MessageFormat, TimingOutput};
 pub use self::build_context::{
-    BuildContext, FileFlavor, FileType, RustDocFingerprint, RustcTargetData, TargetInfo,
+    effective_debug_assertions, effective_overflow_checks, effective_split_debuginfo,
+    effective_strip, profile_settings_in_rustflags, BuildContext, CrateTypeSupport, Endian, EnvAbi,
+    FileFlavor, FileType, LinkHardening, RustDocFingerprint, RustcTargetData, RustcVersion,
+    TargetInfo,
 };
 use self::build_plan::BuildPlan;
-pub use self::compilation::{Compilation, Doctest, UnitOutput};
+pub use self::compilation::{apply_env_config, Compilation, Doctest, UnitOutput};
 pub use self::compile_kind::{CompileKind, CompileTarget};
+use self::context::OutputFile;
 pub use self::context::{Context, Metadata};
 pub use self::crate_type::CrateType;
 pub use self::custom_build::{BuildOutput, BuildScriptOutputs, BuildScripts};
@@ -170,6 +175,7 @@ fn compile<'cfg>(
             let work = if unit.mode.is_doc() || unit.mode.is_doc_scrape() {
                 rustdoc(cx, unit)?
             } else {
+                check_linker(cx, unit)?;
                 rustc(cx, unit, exec)?
             };
             work.then(link_targets(cx, unit, false)?)
@@ -206,6 +212,49 @@ fn compile<'cfg>(
     Ok(())
 }
 
+/// Checks that the `linker` configured for a unit that is about to be built
+/// is either an existing file or a program on `PATH`, if the unit links.
+///
+/// This runs while the jobs are prepared, so the error comes before anything
+/// is compiled instead of as a failure to spawn the linker. Units that are
+/// fresh or don't link, like libraries, don't need the linker.
+fn check_linker(cx: &Context<'_, '_>, unit: &Unit) -> CargoResult<()> {
+    let links = match unit.mode {
+        CompileMode::Test | CompileMode::Bench => true,
+        CompileMode::Build => unit.target.rustc_crate_types().iter().any(|ct| {
+            matches!(
+                ct,
+                CrateType::Bin | CrateType::Dylib | CrateType::Cdylib | CrateType::ProcMacro
+            )
+        }),
+        _ => false,
+    };
+    if !links {
+        return Ok(());
+    }
+    let target_data = &cx.bcx.target_data;
+    let linker = match target_data.linker(unit.kind) {
+        Some(linker) => linker,
+        None => return Ok(()),
+    };
+    if target_data.linker_available(unit.kind) {
+        return Ok(());
+    }
+    let definition = &target_data
+        .target_config(unit.kind)
+        .linker
+        .as_ref()
+        .expect("linker is configured")
+        .definition;
+    anyhow::bail!(
+        "configured linker `{}` not found\n\
+         It is set for `{}` in {}.",
+        linker.display(),
+        target_data.short_name(&unit.kind),
+        definition
+    )
+}
+
 fn rustc(cx: &mut Context<'_, '_>, unit: &Unit, exec: &Arc<dyn Executor>) -> CargoResult<Work> {
     let mut rustc = prepare_rustc(cx, &unit.target.rustc_crate_types(), unit)?;
     let build_plan = cx.bcx.build_config.build_plan;
@@ -241,6 +290,7 @@ fn rustc(cx: &mut Context<'_, '_>, unit: &Unit, exec: &Arc<dyn Executor>) -> Car
     let dep_info_loc = fingerprint::dep_info_loc(cx, unit);
 
     rustc.args(cx.bcx.rustflags_args(unit));
+    rustc.args(&cx.bcx.crate_type_rustflags_args(unit));
     if cx.bcx.config.cli_unstable().binary_dep_depinfo {
         rustc.arg("-Z").arg("binary-dep-depinfo");
     }
@@ -263,6 +313,8 @@ fn rustc(cx: &mut Context<'_, '_>, unit: &Unit, exec: &Arc<dyn Executor>) -> Car
     let script_metadata = cx.find_build_script_metadata(unit);
     let is_local = unit.is_local();
     let artifact = unit.artifact;
+    // Extra `cargo rustc` arguments can change what rustc writes, on purpose.
+    let verify_outputs = cx.bcx.extra_args_for(unit).is_none();
 
     return Ok(Work::new(move |state| {
         // Artifacts are in a different location than typical units,
@@ -305,6 +357,13 @@ fn rustc(cx: &mut Context<'_, '_>, unit: &Unit, exec: &Arc<dyn Executor>) -> Car
                     paths::remove_file(&dst)?;
                 }
             }
+            // The codegen units can change between builds, so remove the
+            // files of the old ones to not uplift them again.
+            if output.per_codegen_unit {
+                for file in output.files()? {
+                    paths::remove_file(&file.path)?;
+                }
+            }
         }
 
         fn verbose_if_simple_exit_code(err: Error) -> Error {
@@ -359,6 +418,14 @@ fn rustc(cx: &mut Context<'_, '_>, unit: &Unit, exec: &Arc<dyn Executor>) -> Car
             })?;
             // Exec should never return with success *and* generate an error.
             debug_assert_eq!(output_options.errors_seen, 0);
+
+            // An executor that doesn't pass on rustc's messages leaves
+            // nothing to check against.
+            if verify_outputs && !output_options.artifacts.is_empty() {
+                if let Err(e) = verify_rustc_outputs(&name, &outputs, &output_options.artifacts) {
+                    state.warning(e.to_string())?;
+                }
+            }
         }
 
         if rustc_dep_info_loc.exists() {
@@ -456,7 +523,11 @@ fn link_targets(cx: &mut Context<'_, '_>, unit: &Unit, fresh: bool) -> CargoResu
         // above. This means that `cargo build` will produce binaries in
         // `target/debug` which one probably expects.
         let mut destinations = vec![];
+        let mut files = Vec::new();
         for output in outputs.iter() {
+            files.extend(output.files()?);
+        }
+        for output in files.iter() {
             let src = &output.path;
             // This may have been a `cargo rustc` command which changes the
             // output, so the source may not actually exist.
@@ -1290,6 +1361,9 @@ struct OutputOptions {
     show_diagnostics: bool,
     warnings_seen: usize,
     errors_seen: usize,
+    /// The files rustc reported writing in its artifact notifications, with
+    /// the kind of output each one is, see `verify_rustc_outputs`.
+    artifacts: Vec<(String, PathBuf)>,
 }
 
 impl OutputOptions {
@@ -1306,6 +1380,7 @@ impl OutputOptions {
             show_diagnostics: true,
             warnings_seen: 0,
             errors_seen: 0,
+            artifacts: Vec::new(),
         }
     }
 }
@@ -1341,6 +1416,72 @@ fn on_stderr_line(
     Ok(())
 }
 
+/// Checks the files rustc reported writing in its artifact notifications
+/// against the `expected` outputs of a unit of package `name`.
+///
+/// Only the files that uplifting and dependent units rely on are compared.
+/// rustc doesn't report auxiliary and debug info files, and the `.rmeta` it
+/// reports for a pipelined library is only one of Cargo's outputs in a check
+/// build. A difference means Cargo's idea of what rustc writes has drifted,
+/// which otherwise only shows up later as a missing or stray file.
+fn verify_rustc_outputs(
+    name: &str,
+    expected: &[OutputFile],
+    reported: &[(String, PathBuf)],
+) -> CargoResult<()> {
+    let file_name = |path: &Path| {
+        path.file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    };
+    let check = expected.iter().any(|o| o.flavor == FileFlavor::Rmeta);
+    let expected: Vec<String> = expected
+        .iter()
+        .filter(|o| {
+            matches!(
+                o.flavor,
+                FileFlavor::Normal | FileFlavor::Linkable | FileFlavor::Rmeta
+            )
+        })
+        .map(|o| file_name(&o.path))
+        .collect();
+    let reported: Vec<String> = reported
+        .iter()
+        .filter(|(emit, _)| emit == "link" || (check && emit == "metadata"))
+        .map(|(_, path)| file_name(path))
+        .collect();
+    let missing: Vec<&str> = expected
+        .iter()
+        .filter(|name| !reported.contains(name))
+        .map(String::as_str)
+        .collect();
+    let unexpected: Vec<&str> = reported
+        .iter()
+        .filter(|name| !expected.contains(name))
+        .map(String::as_str)
+        .collect();
+    if missing.is_empty() && unexpected.is_empty() {
+        return Ok(());
+    }
+    let mut msg = format!(
+        "the files rustc wrote for `{}` do not match the ones Cargo expected",
+        name
+    );
+    if !missing.is_empty() {
+        msg.push_str(&format!(
+            "\nexpected but not written: {}",
+            missing.join(", ")
+        ));
+    }
+    if !unexpected.is_empty() {
+        msg.push_str(&format!(
+            "\nwritten but not expected: {}",
+            unexpected.join(", ")
+        ));
+    }
+    anyhow::bail!(msg)
+}
+
 /// Returns true if the line should be cached.
 fn on_stderr_line_inner(
     state: &JobState<'_, '_>,
@@ -1471,6 +1612,8 @@ fn on_stderr_line_inner(
     #[derive(serde::Deserialize)]
     struct ArtifactNotification {
         artifact: String,
+        #[serde(default)]
+        emit: String,
     }
 
     if let Ok(artifact) = serde_json::from_str::<ArtifactNotification>(compiler_message.get()) {
@@ -1479,6 +1622,9 @@ fn on_stderr_line_inner(
             debug!("looks like metadata finished early!");
             state.rmeta_produced();
         }
+        options
+            .artifacts
+            .push((artifact.emit, PathBuf::from(artifact.artifact)));
         return Ok(false);
     }
 
@@ -1555,6 +1701,7 @@ fn replay_output_cache(
         show_diagnostics,
         warnings_seen: 0,
         errors_seen: 0,
+        artifacts: Vec::new(),
     };
     Work::new(move |state| {
         if !path.exists() {
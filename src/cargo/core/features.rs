@@ -639,6 +639,8 @@ unstable_cli_options!(
     #[serde(deserialize_with = "deserialize_build_std")]
     build_std: Option<Vec<String>>  = ("Enable Cargo to compile the standard library itself as part of a crate graph compilation"),
     build_std_features: Option<Vec<String>>  = ("Configure features enabled for the standard library itself when building the standard library"),
+    cfg_linker: bool = ("Enable the `linker` key in `target.'cfg(...)'` config tables"),
+    cfg_snapshot: bool = ("Enable the `build.cfg-snapshot` config key"),
     config_include: bool = ("Enable the `include` key in config files"),
     credential_process: bool = ("Add a config setting to fetch registry authentication tokens by calling an external process"),
     #[serde(deserialize_with = "deserialize_check_cfg")]
@@ -653,10 +655,23 @@ unstable_cli_options!(
     multitarget: bool = ("Allow passing multiple `--target` flags to the cargo subcommand selected"),
     no_index_update: bool = ("Do not update the registry index even if the cache is outdated"),
     panic_abort_tests: bool = ("Enable support to run tests with -Cpanic=abort"),
+    probe_bypass_wrapper: bool = ("Enable the `build.probe-bypass-wrapper` config key"),
+    probe_source_file: bool = ("Enable the `build.probe-source-file` config key"),
+    probe_trace: bool = ("Enable the `build.probe-trace` config key"),
     host_config: bool = ("Enable the [host] section in the .cargo/config.toml file"),
+    host_libdir_name: bool = ("Enable the `target.<triple>.host-libdir-name` config key"),
+    import_lib: bool = ("Enable the `build.import-lib` config key"),
     sparse_registry: bool = ("Support plain-HTTP-based crate registries"),
+    sysroot: bool = ("Enable the `build.sysroot` config key"),
     target_applies_to_host: bool = ("Enable the `target-applies-to-host` key in the .cargo/config.toml file"),
     rustdoc_map: bool = ("Allow passing external documentation mappings to rustdoc"),
+    rustdoc_test_flags: bool = ("Enable the `build.rustdoc-test-flags` config key"),
+    rustflags_command: bool = ("Enable the `target.<triple>.rustflags-command` config key"),
+    rustflags_dedup: bool = ("Enable the `build.rustflags-dedup` config key"),
+    rustflags_file: bool = ("Enable the `build.rustflags-file` config key"),
+    rustflags_merge: bool = ("Enable the `build.rustflags-merge` config key"),
+    rustflags_when_building: bool = ("Enable the `target.<triple>.rustflags-when-building` config table"),
+    rustlib_dir_name: bool = ("Enable the `target.<triple>.rustlib-dir-name` config key"),
     separate_nightlies: bool = (HIDDEN),
     terminal_width: Option<Option<usize>>  = ("Provide a terminal width to rustc for error truncation"),
     unstable_options: bool = ("Allow the usage of unstable options"),
@@ -896,10 +911,17 @@ impl CliUnstable {
             }
             "build-std-features" => self.build_std_features = Some(parse_features(v)),
             "doctest-xcompile" => self.doctest_xcompile = parse_empty(k, v)?,
+            "cfg-linker" => self.cfg_linker = parse_empty(k, v)?,
+            "cfg-snapshot" => self.cfg_snapshot = parse_empty(k, v)?,
             "doctest-in-workspace" => self.doctest_in_workspace = parse_empty(k, v)?,
             "panic-abort-tests" => self.panic_abort_tests = parse_empty(k, v)?,
+            "probe-bypass-wrapper" => self.probe_bypass_wrapper = parse_empty(k, v)?,
+            "probe-source-file" => self.probe_source_file = parse_empty(k, v)?,
+            "probe-trace" => self.probe_trace = parse_empty(k, v)?,
             "jobserver-per-rustc" => self.jobserver_per_rustc = parse_empty(k, v)?,
             "host-config" => self.host_config = parse_empty(k, v)?,
+            "host-libdir-name" => self.host_libdir_name = parse_empty(k, v)?,
+            "import-lib" => self.import_lib = parse_empty(k, v)?,
             "target-applies-to-host" => self.target_applies_to_host = parse_empty(k, v)?,
             "features" => {
                 // For now this is still allowed (there are still some
@@ -922,8 +944,16 @@ impl CliUnstable {
             "separate-nightlies" => self.separate_nightlies = parse_empty(k, v)?,
             "multitarget" => self.multitarget = parse_empty(k, v)?,
             "rustdoc-map" => self.rustdoc_map = parse_empty(k, v)?,
+            "rustdoc-test-flags" => self.rustdoc_test_flags = parse_empty(k, v)?,
+            "rustflags-command" => self.rustflags_command = parse_empty(k, v)?,
+            "rustflags-dedup" => self.rustflags_dedup = parse_empty(k, v)?,
+            "rustflags-file" => self.rustflags_file = parse_empty(k, v)?,
+            "rustflags-merge" => self.rustflags_merge = parse_empty(k, v)?,
+            "rustflags-when-building" => self.rustflags_when_building = parse_empty(k, v)?,
+            "rustlib-dir-name" => self.rustlib_dir_name = parse_empty(k, v)?,
             "terminal-width" => self.terminal_width = Some(parse_usize_opt(v)?),
             "sparse-registry" => self.sparse_registry = parse_empty(k, v)?,
+            "sysroot" => self.sysroot = parse_empty(k, v)?,
             "namespaced-features" => stabilized_warn(k, "1.60", STABILISED_NAMESPACED_FEATURES),
             "weak-dep-features" => stabilized_warn(k, "1.60", STABILIZED_WEAK_DEP_FEATURES),
             "credential-process" => self.credential_process = parse_empty(k, v)?,
@@ -960,18 +990,29 @@ impl CliUnstable {
     /// Generates an error if `-Z unstable-options` was not used for a new,
     /// unstable command-line flag.
     pub fn fail_if_stable_opt(&self, flag: &str, issue: u32) -> CargoResult<()> {
+        self.fail_if_stable_opt_issue(flag, Some(issue))
+    }
+
+    /// Like `fail_if_stable_opt`, for a flag that has no tracking issue yet.
+    pub fn fail_if_stable_opt_untracked(&self, flag: &str) -> CargoResult<()> {
+        self.fail_if_stable_opt_issue(flag, None)
+    }
+
+    fn fail_if_stable_opt_issue(&self, flag: &str, issue: Option<u32>) -> CargoResult<()> {
         if !self.unstable_options {
-            let see = format!(
-                "See https://github.com/rust-lang/cargo/issues/{} for more \
-                 information about the `{}` flag.",
-                issue, flag
-            );
+            let see = match issue {
+                Some(issue) => format!(
+                    "\nSee https://github.com/rust-lang/cargo/issues/{} for more \
+                     information about the `{}` flag.",
+                    issue, flag
+                ),
+                None => String::new(),
+            };
             // NOTE: a `config` isn't available here, check the channel directly
             let channel = channel();
             if channel == "nightly" || channel == "dev" {
                 bail!(
-                    "the `{}` flag is unstable, pass `-Z unstable-options` to enable it\n\
-                     {}",
+                    "the `{}` flag is unstable, pass `-Z unstable-options` to enable it{}",
                     flag,
                     see
                 );
@@ -979,8 +1020,7 @@ impl CliUnstable {
                 bail!(
                     "the `{}` flag is unstable, and only available on the nightly channel \
                      of Cargo, but this is the `{}` channel\n\
-                     {}\n\
-                     {}",
+                     {}{}",
                     flag,
                     channel,
                     SEE_CHANNELS,
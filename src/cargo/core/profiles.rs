@@ -344,6 +344,16 @@ impl Profiles {
         Ok(())
     }
 
+    /// Whether the user sets `key`, a profile setting like `opt-level`, in
+    /// profile `name` or a profile it inherits from, including in its
+    /// `build-override` and `package` tables.
+    pub fn sets(&self, name: InternedString, key: &str) -> bool {
+        self.by_name
+            .get(&name)
+            .and_then(|maker| maker.toml.as_ref())
+            .map_or(false, |toml| toml_profile_sets(toml, key))
+    }
+
     /// Returns the profile maker for the given profile name.
     fn get_profile_maker(&self, name: InternedString) -> CargoResult<&ProfileMaker> {
         self.by_name
@@ -421,6 +431,31 @@ impl ProfileMaker {
     }
 }
 
+/// Whether `toml` or any of its overrides sets `key`, see `Profiles::sets`.
+fn toml_profile_sets(toml: &TomlProfile, key: &str) -> bool {
+    let sets = match key {
+        "opt-level" => toml.opt_level.is_some(),
+        "debug" => toml.debug.is_some(),
+        "lto" => toml.lto.is_some(),
+        "codegen-units" => toml.codegen_units.is_some(),
+        "panic" => toml.panic.is_some(),
+        "debug-assertions" => toml.debug_assertions.is_some(),
+        "overflow-checks" => toml.overflow_checks.is_some(),
+        _ => false,
+    };
+    sets || toml
+        .build_override
+        .as_deref()
+        .map_or(false, |build_override| {
+            toml_profile_sets(build_override, key)
+        })
+        || toml.package.as_ref().map_or(false, |overrides| {
+            overrides
+                .values()
+                .any(|spec_profile| toml_profile_sets(spec_profile, key))
+        })
+}
+
 /// Merge package and build overrides from the given TOML profile into the given `Profile`.
 fn merge_toml_overrides(
     pkg_id: Option<PackageId>,
@@ -704,7 +739,9 @@ impl serde::ser::Serialize for Lto {
 }
 
 /// The `panic` setting.
-#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash, PartialOrd, Ord, serde::Serialize)]
+#[derive(
+    Clone, Copy, PartialEq, Eq, Debug, Hash, PartialOrd, Ord, serde::Serialize, serde::Deserialize,
+)]
 #[serde(rename_all = "lowercase")]
 pub enum PanicStrategy {
     Unwind,
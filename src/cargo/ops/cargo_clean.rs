@@ -1,5 +1,5 @@
-use crate::core::compiler::{CompileKind, CompileMode, Layout, RustcTargetData};
-use crate::core::profiles::Profiles;
+use crate::core::compiler::{CompileKind, CompileMode, FileType, Layout, RustcTargetData};
+use crate::core::profiles::{Profiles, Strip};
 use crate::core::{PackageIdSpec, TargetKind, Workspace};
 use crate::ops;
 use crate::util::errors::CargoResult;
@@ -162,9 +162,30 @@ pub fn clean(ws: &Workspace<'_>, opts: &CleanOptions<'_>) -> CargoResult<()> {
                 for (compile_kind, layout) in &layouts {
                     let triple = target_data.short_name(compile_kind);
 
-                    let (file_types, _unsupported) = target_data
-                        .info(*compile_kind)
-                        .rustc_outputs(mode, target.kind(), triple)?;
+                    // Use the settings that list the most debug info files,
+                    // which takes both kinds of split debuginfo.
+                    let info = target_data.info(*compile_kind);
+                    let (mut file_types, _unsupported) = info.rustc_outputs(
+                        mode,
+                        target.kind(),
+                        triple,
+                        Strip::None,
+                        Some(InternedString::new("packed")),
+                    )?;
+                    let (unpacked, _unsupported) = info.rustc_outputs(
+                        mode,
+                        target.kind(),
+                        triple,
+                        Strip::None,
+                        Some(InternedString::new("unpacked")),
+                    )?;
+                    file_types.extend(unpacked.into_iter().filter(|ft| ft.is_per_codegen_unit()));
+                    // The `.s` files from `--emit=asm` depend on the rustflags
+                    // of each unit, so always look for them.
+                    if !mode.is_check() && !file_types.is_empty() {
+                        file_types.push(FileType::new_asm());
+                        file_types.push(FileType::new_asm_per_codegen_unit());
+                    }
                     let (dir, uplift_dir) = match target.kind() {
                         TargetKind::ExampleBin | TargetKind::ExampleLib(..) => {
                             (layout.examples(), Some(layout.examples()))
@@ -181,7 +202,11 @@ pub fn clean(ws: &Workspace<'_>, opts: &CleanOptions<'_>) -> CargoResult<()> {
                         let dir_glob = Path::new(&dir_glob);
 
                         rm_rf_glob(&dir_glob.join(&hashed_name), config, &mut progress)?;
-                        rm_rf(&dir.join(&unhashed_name), config, &mut progress)?;
+                        if file_type.is_per_codegen_unit() {
+                            rm_rf_glob(&dir_glob.join(&unhashed_name), config, &mut progress)?;
+                        } else {
+                            rm_rf(&dir.join(&unhashed_name), config, &mut progress)?;
+                        }
                         // Remove dep-info file generated by rustc. It is not tracked in
                         // file_types. It does not have a prefix.
                         let hashed_dep_info = dir_glob.join(format!("{}-*.d", crate_name));
@@ -196,6 +221,13 @@ pub fn clean(ws: &Workspace<'_>, opts: &CleanOptions<'_>) -> CargoResult<()> {
 
                         // Remove the uplifted copy.
                         if let Some(uplift_dir) = uplift_dir {
+                            if file_type.is_per_codegen_unit() {
+                                let uplift_glob = escape_glob_path(uplift_dir)?;
+                                let uplifted =
+                                    Path::new(&uplift_glob).join(file_type.uplift_filename(target));
+                                rm_rf_glob(&uplifted, config, &mut progress)?;
+                                continue;
+                            }
                             let uplifted_path = uplift_dir.join(file_type.uplift_filename(target));
                             rm_rf(&uplifted_path, config, &mut progress)?;
                             // Dep-info generated by Cargo itself.
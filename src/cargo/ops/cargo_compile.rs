@@ -28,11 +28,11 @@ use std::sync::Arc;
 
 use crate::core::compiler::unit_dependencies::{build_unit_dependencies, IsArtifact};
 use crate::core::compiler::unit_graph::{self, UnitDep, UnitGraph};
-use crate::core::compiler::{standard_lib, CrateType, TargetInfo};
+use crate::core::compiler::{profile_settings_in_rustflags, standard_lib, CrateType, TargetInfo};
 use crate::core::compiler::{BuildConfig, BuildContext, Compilation, Context};
 use crate::core::compiler::{CompileKind, CompileMode, CompileTarget, RustcTargetData, Unit};
 use crate::core::compiler::{DefaultExecutor, Executor, UnitInterner};
-use crate::core::profiles::{Profiles, UnitFor};
+use crate::core::profiles::{Lto, Profile, Profiles, UnitFor};
 use crate::core::resolver::features::{self, CliFeatures, FeaturesFor};
 use crate::core::resolver::{HasDevUnits, Resolve};
 use crate::core::{FeatureValue, Package, PackageSet, Shell, Summary, Target};
@@ -317,7 +317,7 @@ pub fn print<'a>(
             process.args(args);
         }
         if let CompileKind::Target(t) = kind {
-            process.arg("--target").arg(t.short_name());
+            process.arg("--target").arg(t.rustc_target());
         }
         process.arg("--print").arg(print_opt_value);
         process.exec()?;
@@ -497,7 +497,7 @@ pub fn create_bcx<'a, 'cfg>(
     )?;
 
     if let Some(args) = target_rustc_crate_types {
-        override_rustc_crate_types(&mut units, args, interner)?;
+        override_rustc_crate_types(&mut units, args, interner, &target_data)?;
     }
 
     let mut scrape_units = match rustdoc_scrape_examples {
@@ -683,6 +683,8 @@ pub fn create_bcx<'a, 'cfg>(
         scrape_units,
     )?;
 
+    check_codegen_overrides(&bcx)?;
+
     Ok(bcx)
 }
 
@@ -1841,13 +1843,128 @@ fn remove_duplicate_doc(
     unit_graph.retain(|unit, _| visited.contains(unit));
 }
 
+/// Warns once per kind when the rustflags override `debug-assertions` or
+/// `overflow-checks` set in the profile, and reports each unit they are
+/// overridden for with `-vv`, whether the profile sets them or not.
+///
+/// Also warns once per kind and profile about rustflags that change
+/// something the profile explicitly sets, like `-Copt-level`, since they
+/// silently take precedence.
+///
+/// This goes over the whole unit graph, so the warning is given whether or
+/// not the units are fresh.
+fn check_codegen_overrides(bcx: &BuildContext<'_, '_>) -> CargoResult<()> {
+    let config = bcx.config;
+    let on_off = |value: bool| if value { "on" } else { "off" };
+    let mut units: Vec<&Unit> = bcx
+        .unit_graph
+        .keys()
+        .filter(|unit| {
+            matches!(
+                unit.mode,
+                CompileMode::Build
+                    | CompileMode::Check { .. }
+                    | CompileMode::Test
+                    | CompileMode::Bench
+            )
+        })
+        .collect();
+    units.sort();
+    let mut warned = HashSet::new();
+    for unit in units {
+        let checks = [
+            (
+                "debug-assertions",
+                "debug assertions",
+                unit.profile.debug_assertions,
+                bcx.debug_assertions(unit),
+            ),
+            (
+                "overflow-checks",
+                "overflow checks",
+                unit.profile.overflow_checks,
+                bcx.overflow_checks(unit),
+            ),
+        ];
+        for (option, what, profile_value, effective) in checks {
+            if effective == profile_value {
+                continue;
+            }
+            if config.extra_verbose() {
+                config.shell().note(format!(
+                    "{} are {} for `{}`",
+                    what,
+                    on_off(effective),
+                    unit.target.name()
+                ))?;
+            }
+            if bcx.profiles.sets(unit.profile.name, option)
+                && warned.insert((unit.kind, unit.profile.name, option))
+            {
+                config.shell().warn(format!(
+                    "profile `{}` sets `{} = {}`, but rustflags turn {} {} for `{}`",
+                    unit.profile.name,
+                    option,
+                    profile_value,
+                    what,
+                    on_off(effective),
+                    bcx.target_data.short_name(&unit.kind),
+                ))?;
+            }
+        }
+
+        let mut rustflags = bcx.rustflags_args(unit).to_vec();
+        rustflags.extend(bcx.crate_type_rustflags_args(unit));
+        for (key, flag, value) in profile_settings_in_rustflags(&rustflags) {
+            if bcx.profiles.sets(unit.profile.name, key)
+                && profile_setting(&unit.profile, key) != value
+                && warned.insert((unit.kind, unit.profile.name, key))
+            {
+                config.shell().warn(format!(
+                    "`{}` in rustflags for `{}` takes precedence over `profile.{}.{}`",
+                    flag,
+                    bcx.target_data.short_name(&unit.kind),
+                    unit.profile.name,
+                    key,
+                ))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The value of the profile setting `key`, spelled like the values returned
+/// by `profile_settings_in_rustflags`.
+fn profile_setting(profile: &Profile, key: &str) -> String {
+    match key {
+        "opt-level" => profile.opt_level.to_string(),
+        "debug" => profile.debuginfo.unwrap_or(0).to_string(),
+        "lto" => match profile.lto {
+            Lto::Off => "off".to_string(),
+            Lto::Bool(true) => "fat".to_string(),
+            // rustc's default, which no `-Clto` value selects.
+            Lto::Bool(false) => String::new(),
+            Lto::Named(name) => name.to_string(),
+        },
+        "codegen-units" => profile
+            .codegen_units
+            .map_or_else(String::new, |n| n.to_string()),
+        "panic" => profile.panic.to_string(),
+        _ => String::new(),
+    }
+}
+
 /// Override crate types for given units.
 ///
 /// This is primarily used by `cargo rustc --crate-type`.
+///
+/// Crate types the target can't produce are rejected here, rather than
+/// letting rustc fail once the build is underway.
 fn override_rustc_crate_types(
     units: &mut [Unit],
     args: &[String],
     interner: &UnitInterner,
+    target_data: &RustcTargetData<'_>,
 ) -> CargoResult<()> {
     if units.len() != 1 {
         anyhow::bail!(
@@ -1885,5 +2002,19 @@ fn override_rustc_crate_types(
         }
     };
 
+    let kind = units[0].kind;
+    let info = target_data.info(kind);
+    for arg in args {
+        if !info.supports_crate_type(&CrateType::from(arg))? {
+            anyhow::bail!(
+                "target `{}` does not support crate type `{}`\n\
+                 Supported crate types: {}",
+                target_data.short_name(&kind),
+                arg,
+                info.supported_crate_types()?.join(", ")
+            );
+        }
+    }
+
     Ok(())
 }
@@ -110,7 +110,9 @@ fn build_resolve_graph(
     // How should this work?
     let requested_kinds =
         CompileKind::from_requested_targets(ws.config(), &metadata_opts.filter_platforms)?;
-    let target_data = RustcTargetData::new(ws, &requested_kinds)?;
+    // Only the `--filter-platform` targets are needed up front, the host is
+    // queried if resolution turns out to need it.
+    let target_data = RustcTargetData::new_resolve_only(ws, &requested_kinds)?;
     // Resolve entire workspace.
     let specs = Packages::All.to_package_id_specs(ws)?;
     let force_all = if metadata_opts.filter_platforms.is_empty() {
@@ -151,6 +153,9 @@ fn build_resolve_graph(
             &requested_kinds,
         );
     }
+    // The resolve above is wrong if the host was needed but couldn't be
+    // queried.
+    target_data.host_probe_result()?;
     // Get a Vec of Packages.
     let actual_packages = package_map
         .into_iter()
@@ -62,7 +62,7 @@ use std::io::{self, SeekFrom};
 use std::mem;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
-use std::sync::Once;
+use std::sync::{Arc, Once};
 use std::time::Instant;
 
 use self::ConfigValue as CV;
@@ -73,7 +73,7 @@ use crate::ops;
 use crate::util::errors::CargoResult;
 use crate::util::toml as cargo_toml;
 use crate::util::validate_package_name;
-use crate::util::{FileLock, Filesystem, IntoUrl, IntoUrlWithBase, Rustc};
+use crate::util::{FileLock, Filesystem, IntoUrl, IntoUrlWithBase, Rustc, RustcCacheBackend};
 use anyhow::{anyhow, bail, format_err, Context as _};
 use cargo_util::paths;
 use curl::easy::Easy;
@@ -164,6 +164,8 @@ pub struct Config {
     crates_io_source_id: LazyCell<SourceId>,
     /// If false, don't cache `rustc --version --verbose` invocations
     cache_rustc_info: bool,
+    /// Additional store for `rustc` outputs, set by embedders.
+    rustc_cache_backend: Option<Arc<dyn RustcCacheBackend>>,
     /// Creation time of this config, used to output the total build time
     creation_time: Instant,
     /// Target Directory via resolved Cli parameter
@@ -174,6 +176,9 @@ pub struct Config {
     upper_case_env: HashMap<String, String>,
     /// Tracks which sources have been updated to avoid multiple updates.
     updated_sources: LazyCell<RefCell<HashSet<SourceId>>>,
+    /// Output of `target.<triple>.rustflags-command` programs, keyed by the
+    /// config key, so each one runs at most once per session.
+    rustflags_command_outputs: RefCell<HashMap<String, Vec<String>>>,
     /// Lock, if held, of the global package cache along with the number of
     /// acquisitions so far.
     package_cache_lock: RefCell<Option<(Option<FileLock>, usize)>>,
@@ -272,11 +277,13 @@ impl Config {
             easy: LazyCell::new(),
             crates_io_source_id: LazyCell::new(),
             cache_rustc_info,
+            rustc_cache_backend: None,
             creation_time: Instant::now(),
             target_dir: None,
             env,
             upper_case_env,
             updated_sources: LazyCell::new(),
+            rustflags_command_outputs: RefCell::new(HashMap::new()),
             package_cache_lock: RefCell::new(None),
             http_config: LazyCell::new(),
             future_incompat_config: LazyCell::new(),
@@ -359,18 +366,14 @@ impl Config {
 
     /// Gets the path to the `rustc` executable.
     pub fn load_global_rustc(&self, ws: Option<&Workspace<'_>>) -> CargoResult<Rustc> {
-        let cache_location = ws.map(|ws| {
-            ws.target_dir()
-                .join(".rustc_info.json")
-                .into_path_unlocked()
-        });
+        let cache_location = ws.map(rustc_info_cache_location);
         let wrapper = self.maybe_get_tool("rustc_wrapper", &self.build_config()?.rustc_wrapper);
         let rustc_workspace_wrapper = self.maybe_get_tool(
             "rustc_workspace_wrapper",
             &self.build_config()?.rustc_workspace_wrapper,
         );
 
-        Rustc::new(
+        let rustc = Rustc::new(
             self.get_tool("rustc", &self.build_config()?.rustc),
             wrapper,
             rustc_workspace_wrapper,
@@ -385,7 +388,37 @@ impl Config {
             } else {
                 None
             },
-        )
+        )?;
+        if let Some(backend) = &self.rustc_cache_backend {
+            rustc.set_cache_backend(Arc::clone(backend));
+        }
+        Ok(rustc)
+    }
+
+    /// Sets an additional store for `rustc` outputs used by every `Rustc`
+    /// loaded from this config, see [`RustcCacheBackend`].
+    pub fn set_rustc_cache_backend(&mut self, backend: Arc<dyn RustcCacheBackend>) {
+        self.rustc_cache_backend = Some(backend);
+    }
+
+    /// Removes the on-disk cache of `rustc` outputs for the given workspace,
+    /// along with the flags remembered from `target.<triple>.rustflags-command`
+    /// programs.
+    ///
+    /// The cache is normally invalidated automatically when the compiler
+    /// changes, but embedders that switch toolchains in-process can use this
+    /// to make sure nothing from the previous toolchain is reused.
+    pub fn clear_rustc_info_cache(&self, ws: &Workspace<'_>) -> CargoResult<()> {
+        self.rustflags_command_outputs().clear();
+        let path = rustc_info_cache_location(ws);
+        if path.exists() {
+            paths::remove_file(&path)?;
+        }
+        let path = target_info_cache_root(ws);
+        if path.exists() {
+            paths::remove_dir_all(&path)?;
+        }
+        Ok(())
     }
 
     /// Gets the path to the `cargo` executable.
@@ -432,6 +465,12 @@ impl Config {
             .borrow_mut()
     }
 
+    /// Flags produced by `target.<triple>.rustflags-command` programs that
+    /// have already been run during this session.
+    pub fn rustflags_command_outputs(&self) -> RefMut<'_, HashMap<String, Vec<String>>> {
+        self.rustflags_command_outputs.borrow_mut()
+    }
+
     /// Gets all config values from disk.
     ///
     /// This will lazy-load the values as necessary. Callers are responsible
@@ -1532,8 +1571,28 @@ impl Config {
     }
 
     pub fn build_config(&self) -> CargoResult<&CargoBuildConfig> {
-        self.build_config
-            .try_borrow_with(|| self.get::<CargoBuildConfig>("build"))
+        self.build_config.try_borrow_with(|| {
+            self.check_flags_key("build.rustflags")?;
+            self.check_flags_key("build.rustdocflags")?;
+            self.check_flags_key("build.rustdoc-test-flags")?;
+            self.get::<CargoBuildConfig>("build")
+        })
+    }
+
+    /// Checks that a flags key like `target.<triple>.rustflags` is a string or
+    /// a list of strings, to report a clearer error than deserialization would.
+    pub fn check_flags_key(&self, key: &str) -> CargoResult<()> {
+        if let Some(cv) = self.get_cv(&ConfigKey::from_str(key))? {
+            if !matches!(cv, CV::String(..) | CV::List(..)) {
+                bail!(
+                    "expected a list of strings for `{}`, found {} in {}",
+                    key,
+                    cv.desc(),
+                    cv.definition()
+                );
+            }
+        }
+        Ok(())
     }
 
     pub fn progress_config(&self) -> &ProgressConfig {
@@ -1980,6 +2039,21 @@ pub fn homedir(cwd: &Path) -> Option<PathBuf> {
     ::home::cargo_home_with_cwd(cwd).ok()
 }
 
+/// Location of the on-disk cache of `rustc` outputs for a workspace.
+fn rustc_info_cache_location(ws: &Workspace<'_>) -> PathBuf {
+    ws.target_dir()
+        .join(".rustc_info.json")
+        .into_path_unlocked()
+}
+
+/// Root of the on-disk target information cache for a workspace, see
+/// `Rustc::target_info_cache_file`.
+fn target_info_cache_root(ws: &Workspace<'_>) -> PathBuf {
+    ws.target_dir()
+        .join(crate::util::rustc::TARGET_INFO_CACHE_DIR)
+        .into_path_unlocked()
+}
+
 pub fn save_credentials(
     cfg: &Config,
     token: Option<String>,
@@ -2197,9 +2271,19 @@ pub struct CargoBuildConfig {
     pub target: Option<BuildTargetConfig>,
     pub jobs: Option<u32>,
     pub rustflags: Option<StringList>,
+    pub rustflags_file: Option<ConfigRelativePath>,
     pub rustdocflags: Option<StringList>,
+    pub rustdoc_test_flags: Option<StringList>,
+    pub rustflags_merge: Option<bool>,
+    pub rustflags_dedup: Option<bool>,
     pub rustc_wrapper: Option<ConfigRelativePath>,
     pub rustc_workspace_wrapper: Option<ConfigRelativePath>,
+    pub probe_bypass_wrapper: Option<bool>,
+    pub probe_source_file: Option<bool>,
+    pub cfg_snapshot: Option<ConfigRelativePath>,
+    pub probe_trace: Option<ConfigRelativePath>,
+    pub sysroot: Option<ConfigRelativePath>,
+    pub import_lib: Option<bool>,
     pub rustc: Option<ConfigRelativePath>,
     pub rustdoc: Option<ConfigRelativePath>,
     pub out_dir: Option<ConfigRelativePath>,
@@ -1,9 +1,11 @@
 use super::{Config, ConfigKey, ConfigRelativePath, OptValue, PathAndArgs, StringList, CV};
 use crate::core::compiler::{BuildOutput, LinkType};
 use crate::util::CargoResult;
+use cargo_platform::Platform;
 use serde::Deserialize;
 use std::collections::{BTreeMap, HashMap};
 use std::path::PathBuf;
+use std::str::FromStr;
 use toml_edit::easy as toml;
 
 /// Config definition of a `[target.'cfg(…)']` table.
@@ -13,6 +15,7 @@ use toml_edit::easy as toml;
 pub struct TargetCfgConfig {
     pub runner: OptValue<PathAndArgs>,
     pub rustflags: OptValue<StringList>,
+    pub linker: OptValue<ConfigRelativePath>,
     // This is here just to ignore fields from normal `TargetConfig` because
     // all `[target]` tables are getting deserialized, whether they start with
     // `cfg(` or not.
@@ -49,11 +52,25 @@ pub(super) fn load_target_cfgs(config: &Config) -> CargoResult<Vec<(String, Targ
     log::debug!("Got all targets {:#?}", target);
     for (key, cfg) in target {
         if key.starts_with("cfg(") {
+            // A table that can never match is dropped, like a
+            // `[target.'cfg(...)'.dependencies]` table would be an error.
+            if let Err(e) = Platform::from_str(&key) {
+                config
+                    .shell()
+                    .warn(format!("ignoring [target] config table `{}`: {}", key, e))?;
+                continue;
+            }
             // Unfortunately this is not able to display the location of the
             // unused key. Using config::Value<toml::Value> doesn't work. One
             // solution might be to create a special "Any" type, but I think
             // that will be quite difficult with the current design.
-            for other_key in cfg.other.keys() {
+            let mut unused: Vec<&str> = cfg.other.keys().map(|k| k.as_str()).collect();
+            // `linker` is only used with -Zcfg-linker.
+            if cfg.linker.is_some() && !config.cli_unstable().cfg_linker {
+                unused.push("linker");
+                unused.sort_unstable();
+            }
+            for other_key in unused {
                 config.shell().warn(format!(
                     "unused key `{}` in [target] config table `{}`",
                     other_key, key
@@ -146,7 +163,15 @@ fn parse_links_overrides(
         // Skip these keys, it shares the namespace with `TargetConfig`.
         match lib_name.as_str() {
             // `ar` is a historical thing.
-            "ar" | "linker" | "runner" | "rustflags" => continue,
+            "ar"
+            | "crate-type"
+            | "host-libdir-name"
+            | "linker"
+            | "runner"
+            | "rustflags"
+            | "rustflags-command"
+            | "rustflags-when-building"
+            | "rustlib-dir-name" => continue,
             _ => {}
         }
         let mut output = BuildOutput::default();
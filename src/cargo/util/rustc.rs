@@ -1,8 +1,9 @@
 use std::collections::hash_map::HashMap;
 use std::env;
+use std::fmt;
 use std::hash::{Hash, Hasher};
 use std::path::{Path, PathBuf};
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
 
 use anyhow::Context as _;
 use cargo_util::{paths, ProcessBuilder, ProcessError};
@@ -12,6 +13,10 @@ use serde::{Deserialize, Serialize};
 use crate::util::interning::InternedString;
 use crate::util::{profile, CargoResult, StableHasher};
 
+/// Name of the directory next to the `rustc` output cache that holds what
+/// Cargo learned about each target, see `Rustc::target_info_cache_file`.
+pub const TARGET_INFO_CACHE_DIR: &str = ".target-info";
+
 /// Information on the `rustc` executable
 #[derive(Debug)]
 pub struct Rustc {
@@ -28,7 +33,7 @@ pub struct Rustc {
     pub version: semver::Version,
     /// The host triple (arch-platform-OS), this comes from verbose_version.
     pub host: InternedString,
-    cache: Mutex<Cache>,
+    cache: Arc<Mutex<Cache>>,
 }
 
 impl Rustc {
@@ -87,7 +92,7 @@ impl Rustc {
             verbose_version,
             version,
             host,
-            cache: Mutex::new(cache),
+            cache: Arc::new(Mutex::new(cache)),
         })
     }
 
@@ -128,13 +133,109 @@ impl Rustc {
         cmd: &ProcessBuilder,
         extra_fingerprint: u64,
     ) -> CargoResult<(String, String)> {
-        self.cache
-            .lock()
-            .unwrap()
-            .cached_output(cmd, extra_fingerprint)
+        cached_output(&self.cache, cmd, extra_fingerprint)
+    }
+
+    /// A handle to the cache used by [`Rustc::cached_output`], for running
+    /// `rustc` later without a reference to this `Rustc`.
+    pub fn output_cache(&self) -> RustcOutputCache {
+        RustcOutputCache {
+            cache: Arc::clone(&self.cache),
+        }
+    }
+
+    /// Sets an additional store consulted for outputs that are not in the
+    /// cache yet, see [`RustcCacheBackend`].
+    pub fn set_cache_backend(&self, backend: Arc<dyn RustcCacheBackend>) {
+        self.cache.lock().unwrap().backend = Some(backend);
+    }
+
+    /// Location of the on-disk cache of what Cargo learned about `triple`
+    /// from this compiler, as
+    /// `<target-dir>/.target-info/<rustc-fingerprint>/<triple>.json`.
+    ///
+    /// Returns `None` if caching `rustc` information is disabled.
+    pub fn target_info_cache_file(&self, triple: &str) -> Option<PathBuf> {
+        let cache = self.cache.lock().unwrap();
+        let dir = cache.cache_location.as_ref()?.parent()?;
+        Some(
+            dir.join(TARGET_INFO_CACHE_DIR)
+                .join(format!("{:016x}", cache.data.rustc_fingerprint))
+                .join(format!("{}.json", triple)),
+        )
+    }
+
+    /// Discards all cached outputs held in memory.
+    ///
+    /// Nothing is written back to the on-disk cache when this `Rustc` is
+    /// dropped afterwards, see [`Config::clear_rustc_info_cache`] for
+    /// removing that.
+    ///
+    /// [`Config::clear_rustc_info_cache`]: crate::util::Config::clear_rustc_info_cache
+    pub fn clear_cache(&self) {
+        self.cache.lock().unwrap().clear();
+    }
+}
+
+/// A shared handle to the output cache of a [`Rustc`], see
+/// [`Rustc::output_cache`].
+#[derive(Clone, Debug)]
+pub struct RustcOutputCache {
+    cache: Arc<Mutex<Cache>>,
+}
+
+impl RustcOutputCache {
+    /// Same as [`Rustc::cached_output`].
+    pub fn cached_output(
+        &self,
+        cmd: &ProcessBuilder,
+        extra_fingerprint: u64,
+    ) -> CargoResult<(String, String)> {
+        cached_output(&self.cache, cmd, extra_fingerprint)
     }
 }
 
+fn cached_output(
+    cache: &Mutex<Cache>,
+    cmd: &ProcessBuilder,
+    extra_fingerprint: u64,
+) -> CargoResult<(String, String)> {
+    // The lock isn't held while `rustc` runs or the backend is asked, so
+    // several targets can be probed at once from different threads.
+    let key = process_fingerprint(cmd, extra_fingerprint);
+    let backend = {
+        let cache = cache.lock().unwrap();
+        if cache.contains(key) {
+            return cache.result(key, cmd);
+        }
+        cache.backend(key)
+    };
+    let output = fetch_output(backend.as_ref(), cmd)?;
+    let mut cache = cache.lock().unwrap();
+    cache.insert(key, output);
+    cache.result(key, cmd)
+}
+
+/// Gets the output of `cmd` from the backend, if it has it under the given
+/// key, or runs `cmd` and gives its output to the backend.
+fn fetch_output(
+    backend: Option<&(Arc<dyn RustcCacheBackend>, u64)>,
+    cmd: &ProcessBuilder,
+) -> CargoResult<CachedOutput> {
+    if let Some((backend, key)) = backend {
+        if let Some(output) = backend.get(*key) {
+            debug!("rustc info cache backend hit");
+            return Ok(output);
+        }
+    }
+    debug!("rustc info cache miss");
+    let output = run_uncached(cmd)?;
+    if let Some((backend, key)) = backend {
+        backend.put(*key, &output);
+    }
+    Ok(output)
+}
+
 /// It is a well known fact that `rustc` is not the fastest compiler in the
 /// world.  What is less known is that even `rustc --version --verbose` takes
 /// about a hundred milliseconds! Because we need compiler version info even
@@ -148,22 +249,47 @@ struct Cache {
     cache_location: Option<PathBuf>,
     dirty: bool,
     data: CacheData,
+    /// Whether `data.rustc_fingerprint` identifies the compiler, which the
+    /// backend relies on.
+    fingerprinted: bool,
+    backend: Option<Arc<dyn RustcCacheBackend>>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Default)]
 struct CacheData {
     rustc_fingerprint: u64,
-    outputs: HashMap<u64, Output>,
+    outputs: HashMap<u64, CachedOutput>,
     successes: HashMap<u64, bool>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-struct Output {
-    success: bool,
-    status: String,
-    code: Option<i32>,
-    stdout: String,
-    stderr: String,
+/// The result of running a `rustc` command, as stored in the cache.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CachedOutput {
+    pub success: bool,
+    /// Description of the exit status, empty if the command succeeded.
+    pub status: String,
+    pub code: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+/// An additional store for `rustc` outputs, for example one shared across
+/// machines by a distributed build system.
+///
+/// Cargo's own cache is always consulted first. The backend is only asked
+/// for outputs Cargo does not already know about, and is given every output
+/// that Cargo had to compute by running `rustc`.
+///
+/// Keys are fingerprints of the compiler (and any wrappers), the command
+/// line, its environment, and any extra fingerprint given to
+/// [`Rustc::cached_output`]. The backend is not used if the compiler can't
+/// be fingerprinted.
+pub trait RustcCacheBackend: fmt::Debug + Send + Sync {
+    /// Returns the output stored for `key`, if any.
+    fn get(&self, key: u64) -> Option<CachedOutput>;
+
+    /// Stores the output of the command identified by `key`.
+    fn put(&self, key: u64, output: &CachedOutput);
 }
 
 impl Cache {
@@ -205,6 +331,8 @@ impl Cache {
                     cache_location: Some(cache_location),
                     dirty,
                     data,
+                    fingerprinted: true,
+                    backend: None,
                 };
 
                 fn read(path: &Path) -> CargoResult<CacheData> {
@@ -213,14 +341,23 @@ impl Cache {
                 }
             }
             (_, fingerprint) => {
-                if let Err(e) = fingerprint {
-                    warn!("failed to calculate rustc fingerprint: {}", e);
-                }
+                let rustc_fingerprint = match fingerprint {
+                    Ok(fingerprint) => Some(fingerprint),
+                    Err(e) => {
+                        warn!("failed to calculate rustc fingerprint: {}", e);
+                        None
+                    }
+                };
                 debug!("rustc info cache disabled");
                 Cache {
                     cache_location: None,
                     dirty: false,
-                    data: CacheData::default(),
+                    data: CacheData {
+                        rustc_fingerprint: rustc_fingerprint.unwrap_or_default(),
+                        ..CacheData::default()
+                    },
+                    fingerprinted: rustc_fingerprint.is_some(),
+                    backend: None,
                 }
             }
         }
@@ -232,34 +369,43 @@ impl Cache {
         extra_fingerprint: u64,
     ) -> CargoResult<(String, String)> {
         let key = process_fingerprint(cmd, extra_fingerprint);
-        if self.data.outputs.contains_key(&key) {
+        if !self.contains(key) {
+            let output = fetch_output(self.backend(key).as_ref(), cmd)?;
+            self.insert(key, output);
+        }
+        self.result(key, cmd)
+    }
+
+    /// Returns whether an output for `key` is cached already.
+    fn contains(&self, key: u64) -> bool {
+        let hit = self.data.outputs.contains_key(&key);
+        if hit {
             debug!("rustc info cache hit");
-        } else {
-            debug!("rustc info cache miss");
-            debug!("running {}", cmd);
-            let output = cmd.output()?;
-            let stdout = String::from_utf8(output.stdout)
-                .map_err(|e| anyhow::anyhow!("{}: {:?}", e, e.as_bytes()))
-                .with_context(|| format!("`{}` didn't return utf8 output", cmd))?;
-            let stderr = String::from_utf8(output.stderr)
-                .map_err(|e| anyhow::anyhow!("{}: {:?}", e, e.as_bytes()))
-                .with_context(|| format!("`{}` didn't return utf8 output", cmd))?;
-            self.data.outputs.insert(
-                key,
-                Output {
-                    success: output.status.success(),
-                    status: if output.status.success() {
-                        String::new()
-                    } else {
-                        cargo_util::exit_status_to_string(output.status)
-                    },
-                    code: output.status.code(),
-                    stdout,
-                    stderr,
-                },
-            );
-            self.dirty = true;
         }
+        hit
+    }
+
+    fn insert(&mut self, key: u64, output: CachedOutput) {
+        self.data.outputs.insert(key, output);
+        self.dirty = true;
+    }
+
+    /// The backend along with the key for `key` in it, unless the compiler
+    /// couldn't be fingerprinted.
+    ///
+    /// Unlike Cargo's own cache, the backend may be shared between
+    /// compilers, so its keys include the compiler fingerprint.
+    fn backend(&self, key: u64) -> Option<(Arc<dyn RustcCacheBackend>, u64)> {
+        let backend = self.backend.as_ref().filter(|_| self.fingerprinted)?;
+        let mut hasher = StableHasher::new();
+        self.data.rustc_fingerprint.hash(&mut hasher);
+        key.hash(&mut hasher);
+        Some((Arc::clone(backend), hasher.finish()))
+    }
+
+    /// The result of running `cmd`, whose output must already be cached
+    /// under `key`.
+    fn result(&self, key: u64, cmd: &ProcessBuilder) -> CargoResult<(String, String)> {
         let output = &self.data.outputs[&key];
         if output.success {
             Ok((output.stdout.clone(), output.stderr.clone()))
@@ -274,6 +420,12 @@ impl Cache {
             .into())
         }
     }
+
+    fn clear(&mut self) {
+        self.data.outputs.clear();
+        self.data.successes.clear();
+        self.dirty = false;
+    }
 }
 
 impl Drop for Cache {
@@ -351,7 +503,30 @@ fn rustc_fingerprint(
     Ok(hasher.finish())
 }
 
-fn process_fingerprint(cmd: &ProcessBuilder, extra_fingerprint: u64) -> u64 {
+/// Runs `cmd` to produce an entry for the cache.
+fn run_uncached(cmd: &ProcessBuilder) -> CargoResult<CachedOutput> {
+    debug!("running {}", cmd);
+    let output = cmd.output()?;
+    let stdout = String::from_utf8(output.stdout)
+        .map_err(|e| anyhow::anyhow!("{}: {:?}", e, e.as_bytes()))
+        .with_context(|| format!("`{}` didn't return utf8 output", cmd))?;
+    let stderr = String::from_utf8(output.stderr)
+        .map_err(|e| anyhow::anyhow!("{}: {:?}", e, e.as_bytes()))
+        .with_context(|| format!("`{}` didn't return utf8 output", cmd))?;
+    Ok(CachedOutput {
+        success: output.status.success(),
+        status: if output.status.success() {
+            String::new()
+        } else {
+            cargo_util::exit_status_to_string(output.status)
+        },
+        code: output.status.code(),
+        stdout,
+        stderr,
+    })
+}
+
+pub(crate) fn process_fingerprint(cmd: &ProcessBuilder, extra_fingerprint: u64) -> u64 {
     let mut hasher = StableHasher::new();
     extra_fingerprint.hash(&mut hasher);
     cmd.get_args().for_each(|arg| arg.hash(&mut hasher));
@@ -360,3 +535,96 @@ fn process_fingerprint(cmd: &ProcessBuilder, extra_fingerprint: u64) -> u64 {
     env.hash(&mut hasher);
     hasher.finish()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{cached_output, process_fingerprint};
+    use super::{Cache, CacheData, CachedOutput, RustcCacheBackend};
+    use cargo_util::ProcessBuilder;
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+
+    /// A backend that keeps outputs in memory and counts the lookups.
+    #[derive(Debug, Default)]
+    struct MemoryBackend {
+        outputs: Mutex<HashMap<u64, CachedOutput>>,
+        gets: Mutex<usize>,
+    }
+
+    impl MemoryBackend {
+        fn gets(&self) -> usize {
+            *self.gets.lock().unwrap()
+        }
+    }
+
+    impl RustcCacheBackend for MemoryBackend {
+        fn get(&self, key: u64) -> Option<CachedOutput> {
+            *self.gets.lock().unwrap() += 1;
+            self.outputs.lock().unwrap().get(&key).cloned()
+        }
+
+        fn put(&self, key: u64, output: &CachedOutput) {
+            self.outputs.lock().unwrap().insert(key, output.clone());
+        }
+    }
+
+    /// An in-memory cache for the compiler with `rustc_fingerprint`.
+    fn cache(rustc_fingerprint: u64, backend: &Arc<MemoryBackend>) -> Mutex<Cache> {
+        let backend: Arc<dyn RustcCacheBackend> = backend.clone();
+        Mutex::new(Cache {
+            cache_location: None,
+            dirty: false,
+            data: CacheData {
+                rustc_fingerprint,
+                ..CacheData::default()
+            },
+            fingerprinted: true,
+            backend: Some(backend),
+        })
+    }
+
+    #[test]
+    fn cache_backend() {
+        let backend = Arc::new(MemoryBackend::default());
+
+        // A miss everywhere runs the command and gives the output to the
+        // backend.
+        let mut version = ProcessBuilder::new("rustc");
+        version.arg("-V");
+        let (stdout, _) = cached_output(&cache(1, &backend), &version, 0).unwrap();
+        assert!(stdout.starts_with("rustc "));
+        assert_eq!(backend.gets(), 1);
+        assert_eq!(backend.outputs.lock().unwrap().len(), 1);
+
+        // Another cache for the same compiler gets the output from the
+        // backend, without running the command, which would fail.
+        let missing = ProcessBuilder::new("cargo-test-rustc-does-not-exist");
+        let other = cache(1, &backend);
+        let (_, key) = other
+            .lock()
+            .unwrap()
+            .backend(process_fingerprint(&missing, 0))
+            .unwrap();
+        backend.put(
+            key,
+            &CachedOutput {
+                success: true,
+                status: String::new(),
+                code: Some(0),
+                stdout: "from the backend".to_string(),
+                stderr: String::new(),
+            },
+        );
+        let (stdout, _) = cached_output(&other, &missing, 0).unwrap();
+        assert_eq!(stdout, "from the backend");
+        // It is in the cache itself now, so the backend isn't asked again.
+        let gets = backend.gets();
+        cached_output(&other, &missing, 0).unwrap();
+        assert_eq!(backend.gets(), gets);
+
+        // Another compiler has different keys, so it misses and runs the
+        // command.
+        assert!(cached_output(&cache(2, &backend), &missing, 0).is_err());
+        assert_eq!(backend.gets(), gets + 1);
+    }
+}
@@ -0,0 +1,114 @@
+//! Tests for the `--artifact-map` option.
+
+use cargo_test_support::{basic_bin_manifest, project};
+
+#[cargo_test]
+fn artifact_map_requires_unstable() {
+    let p = project()
+        .file("Cargo.toml", &basic_bin_manifest("foo"))
+        .file("src/main.rs", "fn main() {}")
+        .build();
+
+    p.cargo("build --artifact-map map.json")
+        .masquerade_as_nightly_cargo()
+        .with_status(101)
+        .with_stderr(
+            "\
+[ERROR] the `--artifact-map` flag is unstable, pass `-Z unstable-options` to enable it
+",
+        )
+        .run();
+}
+
+#[cargo_test]
+fn artifact_map_lists_outputs() {
+    let p = project()
+        .file("Cargo.toml", &basic_bin_manifest("foo"))
+        .file("src/main.rs", "fn main() {}")
+        .build();
+
+    p.cargo("build -Z unstable-options --artifact-map map.json")
+        .masquerade_as_nightly_cargo()
+        .run();
+
+    let map: serde_json::Value = serde_json::from_str(&p.read_file("map.json")).unwrap();
+    assert_eq!(map["version"], 1);
+    let units = map["units"].as_array().unwrap();
+    assert_eq!(units.len(), 1);
+    assert_eq!(units[0]["target"]["name"], "foo");
+    assert_eq!(units[0]["mode"], "build");
+    let files = units[0]["files"].as_array().unwrap();
+    let bin = files
+        .iter()
+        .find(|f| f["flavor"] == "normal")
+        .expect("no normal output");
+    assert_eq!(bin["produced"], true);
+    let uplifted = bin["uplifted"].as_str().unwrap();
+    assert!(uplifted.ends_with(&format!("foo{}", std::env::consts::EXE_SUFFIX)));
+}
+
+#[cargo_test]
+fn artifact_map_lists_asm_per_codegen_unit() {
+    let p = project()
+        .file("Cargo.toml", &basic_bin_manifest("foo"))
+        .file("src/main.rs", "fn main() {}")
+        .build();
+
+    p.cargo("build -Z unstable-options --artifact-map map.json")
+        .masquerade_as_nightly_cargo()
+        .env("RUSTFLAGS", "--emit=asm,link -Ccodegen-units=4")
+        .env("CARGO_INCREMENTAL", "0")
+        .run();
+
+    let map: serde_json::Value = serde_json::from_str(&p.read_file("map.json")).unwrap();
+    let files = map["units"][0]["files"].as_array().unwrap();
+    let asm: Vec<_> = files
+        .iter()
+        .filter(|f| f["path"].as_str().unwrap().ends_with(".rcgu.s"))
+        .collect();
+    assert!(!asm.is_empty());
+    for file in asm {
+        assert_eq!(file["flavor"], "auxiliary");
+        assert_eq!(file["produced"], true);
+        let uplifted = file["uplifted"].as_str().unwrap();
+        assert!(std::path::Path::new(uplifted).is_file());
+    }
+}
+
+#[cargo_test]
+#[cfg(target_os = "linux")]
+fn artifact_map_lists_unpacked_debuginfo() {
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [package]
+                name = "foo"
+                version = "0.0.1"
+
+                [profile.dev]
+                split-debuginfo = "unpacked"
+                incremental = false
+            "#,
+        )
+        .file("src/main.rs", "fn main() {}")
+        .build();
+
+    p.cargo("build -Z unstable-options --artifact-map map.json")
+        .masquerade_as_nightly_cargo()
+        .run();
+
+    let map: serde_json::Value = serde_json::from_str(&p.read_file("map.json")).unwrap();
+    let files = map["units"][0]["files"].as_array().unwrap();
+    let dwo: Vec<_> = files
+        .iter()
+        .filter(|f| f["path"].as_str().unwrap().ends_with(".rcgu.dwo"))
+        .collect();
+    assert!(!dwo.is_empty());
+    for file in dwo {
+        assert_eq!(file["flavor"], "debuginfo");
+        assert_eq!(file["produced"], true);
+        // The executable finds them where rustc wrote them.
+        assert!(file.get("uplifted").is_none());
+    }
+}
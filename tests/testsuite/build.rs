@@ -1,7 +1,7 @@
 //! Tests for the `cargo build` command.
 
 use cargo::{
-    core::compiler::CompileMode,
+    core::compiler::{CompileKind, CompileMode, RustcTargetData},
     core::{Shell, Workspace},
     ops::CompileOptions,
     Config,
@@ -417,6 +417,46 @@ Caused by:
         .run();
 }
 
+#[cargo_test]
+fn target_data_produces_executable() {
+    let produces_executable = |p: ProjectBuilder| {
+        let p = p.build();
+        let shell = Shell::from_write(Box::new(Vec::new()));
+        let config = Config::new(shell, p.root(), paths::home());
+        let ws = Workspace::new(&p.root().join("Cargo.toml"), &config).unwrap();
+        let target_data = RustcTargetData::new(&ws, &[CompileKind::Host]).unwrap();
+        target_data
+            .produces_executable(&ws, CompileKind::Host)
+            .unwrap()
+    };
+
+    assert!(produces_executable(
+        project().file("src/main.rs", "fn main() {}")
+    ));
+    assert!(!produces_executable(project().file("src/lib.rs", "")));
+    // An example binary counts, even when the package is only a library.
+    assert!(produces_executable(
+        project()
+            .file("src/lib.rs", "")
+            .file("examples/ex.rs", "fn main() {}")
+    ));
+    // Any workspace member's binary counts.
+    assert!(produces_executable(
+        project()
+            .file(
+                "Cargo.toml",
+                r#"
+                    [workspace]
+                    members = ["lib", "bin"]
+                "#,
+            )
+            .file("lib/Cargo.toml", &basic_lib_manifest("lib"))
+            .file("lib/src/lib.rs", "")
+            .file("bin/Cargo.toml", &basic_bin_manifest("bin"))
+            .file("bin/src/main.rs", "fn main() {}")
+    ));
+}
+
 #[cargo_test]
 fn cargo_compile_api_exposes_artifact_paths() {
     let p = project()
@@ -4697,6 +4737,194 @@ fn rustc_wrapper_from_path() {
         .run();
 }
 
+#[cargo_test]
+fn rustc_wrapper_breaks_probe() {
+    // A wrapper that mangles the output of `--print=sysroot`, but otherwise
+    // runs rustc as usual.
+    let wrapper = project()
+        .at("wrapper")
+        .file("Cargo.toml", &basic_manifest("wrapper", "1.0.0"))
+        .file(
+            "src/main.rs",
+            r#"
+                use std::process::Command;
+
+                fn main() {
+                    let mut args = std::env::args().skip(1);
+                    let rustc = args.next().unwrap();
+                    let args: Vec<String> = args.collect();
+                    if args.iter().any(|a| a == "--print=sysroot") {
+                        return;
+                    }
+                    let status = Command::new(rustc).args(&args).status().unwrap();
+                    std::process::exit(status.code().unwrap());
+                }
+            "#,
+        )
+        .build();
+    wrapper.cargo("build").run();
+    let wrapper = wrapper.bin("wrapper");
+
+    let p = project().file("src/lib.rs", "").build();
+    p.cargo("build")
+        .env("RUSTC_WRAPPER", &wrapper)
+        .with_status(101)
+        .with_stderr_contains(
+            "[ERROR] `rustc` was run through the wrapper `[..]wrapper[EXE]`, \
+             which may have changed its output",
+        )
+        .with_stderr_contains(
+            "Try setting `build.probe-bypass-wrapper = true` with `-Zprobe-bypass-wrapper` \
+             to query `rustc` directly.",
+        )
+        .run();
+
+    // The key is ignored without -Zprobe-bypass-wrapper.
+    p.cargo("build")
+        .env("RUSTC_WRAPPER", &wrapper)
+        .env("CARGO_BUILD_PROBE_BYPASS_WRAPPER", "true")
+        .with_status(101)
+        .with_stderr_contains("[ERROR] `rustc` was run through the wrapper [..]")
+        .run();
+
+    p.cargo("build -Zprobe-bypass-wrapper")
+        .env("RUSTC_WRAPPER", &wrapper)
+        .env("CARGO_BUILD_PROBE_BYPASS_WRAPPER", "true")
+        .masquerade_as_nightly_cargo()
+        .run();
+}
+
+#[cargo_test]
+fn probe_trace() {
+    let p = project().file("src/lib.rs", "").build();
+    // The key is ignored without -Zprobe-trace.
+    p.cargo("build")
+        .env("CARGO_BUILD_PROBE_TRACE", "trace.jsonl")
+        .run();
+    assert!(!p.root().join("trace.jsonl").exists());
+
+    p.cargo("build -Zprobe-trace")
+        .masquerade_as_nightly_cargo()
+        .env("CARGO_BUILD_PROBE_TRACE", "trace.jsonl")
+        .run();
+    let trace = p.read_file("trace.jsonl");
+    assert!(trace.contains(r#""reason":"probe""#), "{}", trace);
+    assert!(trace.contains("--print=cfg"), "{}", trace);
+    assert!(trace.contains(r#""reason":"parsed""#), "{}", trace);
+    assert!(trace.contains(r#""success":true"#), "{}", trace);
+
+    // Failing probes are recorded too.
+    p.cargo("build --target does-not-exist -Zprobe-trace")
+        .masquerade_as_nightly_cargo()
+        .env("CARGO_BUILD_PROBE_TRACE", "trace.jsonl")
+        .with_status(101)
+        .with_stderr_contains(
+            "[ERROR] failed to run `rustc` to learn about target-specific information",
+        )
+        .run();
+    let trace = p.read_file("trace.jsonl");
+    assert!(trace.contains(r#""target":"does-not-exist""#), "{}", trace);
+    assert!(trace.contains(r#""success":false"#), "{}", trace);
+}
+
+#[cargo_test]
+fn probe_records_rustc_bootstrap() {
+    let p = project().file("src/lib.rs", "").build();
+    let parsed = || -> serde_json::Value {
+        p.read_file("trace.jsonl")
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+            .filter(|record| record["reason"] == "parsed")
+            .last()
+            .unwrap()
+    };
+
+    p.cargo("check -Zprobe-trace")
+        .masquerade_as_nightly_cargo()
+        .env_remove("RUSTC_BOOTSTRAP")
+        .env("CARGO_BUILD_PROBE_TRACE", "trace.jsonl")
+        .run();
+    let record = parsed();
+    assert!(record["rustc_bootstrap"].is_null(), "{}", record);
+
+    p.cargo("check -Zprobe-trace")
+        .masquerade_as_nightly_cargo()
+        .env("RUSTC_BOOTSTRAP", "1")
+        .env("CARGO_BUILD_PROBE_TRACE", "trace.jsonl")
+        .run();
+    let record = parsed();
+    assert_eq!(record["rustc_bootstrap"], "1", "{}", record);
+}
+
+#[cargo_test]
+fn sysroot_self_contained_libdir() {
+    let p = project().file("src/lib.rs", "").build();
+    let sysroot = p.root().join("sysroot");
+    let libdir = sysroot.join("lib/rustlib").join(rustc_host()).join("lib");
+    fs::create_dir_all(&libdir).unwrap();
+
+    let parsed = |trace: &str| -> serde_json::Value {
+        trace
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+            .filter(|record| record["reason"] == "parsed")
+            .last()
+            .unwrap()
+    };
+
+    p.cargo("check -Zprobe-trace -Zsysroot")
+        .masquerade_as_nightly_cargo()
+        .env("CARGO_BUILD_SYSROOT", &sysroot)
+        .env("CARGO_BUILD_PROBE_TRACE", "trace.jsonl")
+        .run();
+    let record = parsed(&p.read_file("trace.jsonl"));
+    assert_eq!(
+        record["sysroot_target_libdir"],
+        libdir.to_str().unwrap(),
+        "{}",
+        record
+    );
+    assert!(
+        record["sysroot_target_libdir_self_contained"].is_null(),
+        "{}",
+        record
+    );
+
+    fs::create_dir(libdir.join("self-contained")).unwrap();
+    p.cargo("check -Zprobe-trace -Zsysroot")
+        .masquerade_as_nightly_cargo()
+        .env("CARGO_BUILD_SYSROOT", &sysroot)
+        .env("CARGO_BUILD_PROBE_TRACE", "trace.jsonl")
+        .run();
+    let record = parsed(&p.read_file("trace.jsonl"));
+    assert_eq!(
+        record["sysroot_target_libdir_self_contained"],
+        libdir.join("self-contained").to_str().unwrap(),
+        "{}",
+        record
+    );
+}
+
+#[cargo_test]
+fn probe_source_file() {
+    let p = project().file("src/lib.rs", "").build();
+    let source = cargo_test_support::paths::home().join(".cargo/probe/___.rs");
+    // The key is ignored without -Zprobe-source-file.
+    p.cargo("build")
+        .env("CARGO_BUILD_PROBE_SOURCE_FILE", "true")
+        .run();
+    assert!(!source.exists());
+
+    p.cargo("build -Zprobe-trace -Zprobe-source-file")
+        .masquerade_as_nightly_cargo()
+        .env("CARGO_BUILD_PROBE_SOURCE_FILE", "true")
+        .env("CARGO_BUILD_PROBE_TRACE", "trace.jsonl")
+        .run();
+    assert_eq!(fs::read_to_string(&source).unwrap(), "");
+    let trace = p.read_file("trace.jsonl");
+    assert!(trace.contains("___.rs"), "{}", trace);
+}
+
 #[cargo_test]
 fn cdylib_not_lifted() {
     let p = project()
@@ -6227,3 +6455,99 @@ fn primary_package_env_var() {
 
     foo.cargo("test").run();
 }
+
+#[cargo_test]
+fn build_emit_asm_single_codegen_unit() {
+    let p = project()
+        .file("Cargo.toml", &basic_bin_manifest("foo"))
+        .file("src/main.rs", "fn main() {}")
+        .build();
+
+    p.cargo("build")
+        .env("RUSTFLAGS", "--emit=asm,link -Ccodegen-units=1")
+        .env("CARGO_INCREMENTAL", "0")
+        .run();
+    assert!(p.bin("foo").is_file());
+    assert!(p.target_debug_dir().join("foo.s").is_file());
+
+    p.cargo("clean -p foo").run();
+    assert!(!p.target_debug_dir().join("foo.s").exists());
+}
+
+#[cargo_test]
+fn build_emit_asm_many_codegen_units() {
+    let p = project()
+        .file("Cargo.toml", &basic_bin_manifest("foo"))
+        .file("src/main.rs", "fn main() {}")
+        .build();
+
+    // rustc writes one `.s` file per codegen unit, named after it.
+    p.cargo("build")
+        .env("RUSTFLAGS", "--emit=asm,link -Ccodegen-units=4")
+        .env("CARGO_INCREMENTAL", "0")
+        .run();
+    assert!(p.bin("foo").is_file());
+    assert!(!p.target_debug_dir().join("foo.s").exists());
+
+    let asm_files = |pattern: &str| -> Vec<String> {
+        glob::glob(p.target_debug_dir().join(pattern).to_str().unwrap())
+            .unwrap()
+            .map(|path| {
+                let path = path.unwrap();
+                path.file_name().unwrap().to_str().unwrap().to_string()
+            })
+            .collect()
+    };
+    let built = asm_files("deps/foo-*.rcgu.s");
+    assert!(!built.is_empty());
+    // Each one is uplifted without the hash.
+    let uplifted: Vec<String> = built
+        .iter()
+        .map(|name| format!("foo{}", &name[name.find('.').unwrap()..]))
+        .collect();
+    assert_eq!(asm_files("foo.*.rcgu.s"), uplifted);
+
+    p.cargo("clean -p foo").run();
+    assert_eq!(asm_files("deps/foo-*.s"), Vec::<String>::new());
+    assert_eq!(asm_files("foo.*.s"), Vec::<String>::new());
+}
+
+#[cargo_test]
+fn warns_when_rustc_outputs_differ() {
+    // The wrapper makes rustc write a static library next to the rlib, like
+    // a rustc whose outputs have drifted from what Cargo expects.
+    let wrapper = project()
+        .at("wrapper")
+        .file("Cargo.toml", &basic_bin_manifest("wrapper"))
+        .file(
+            "src/main.rs",
+            r#"
+                fn main() {
+                    let mut args = std::env::args().skip(1);
+                    let rustc = args.next().unwrap();
+                    let mut args: Vec<String> = args.collect();
+                    if args.iter().any(|arg| arg == "src/lib.rs") {
+                        args.push("--crate-type".to_string());
+                        args.push("staticlib".to_string());
+                    }
+                    let status = std::process::Command::new(rustc).args(&args).status().unwrap();
+                    std::process::exit(status.code().unwrap_or(1));
+                }
+            "#,
+        )
+        .build();
+    wrapper.cargo("build").run();
+
+    let p = project().file("src/lib.rs", "").build();
+    p.cargo("build")
+        .env("RUSTC_WRAPPER", wrapper.bin("wrapper"))
+        .with_stderr(
+            "\
+[COMPILING] foo v0.0.1 ([CWD])
+[WARNING] the files rustc wrote for `foo` do not match the ones Cargo expected
+written but not expected: libfoo-[..].a
+[FINISHED] [..]
+",
+        )
+        .run();
+}
@@ -364,10 +364,9 @@ fn custom_build_env_var_rustc_linker_bad_host_target() {
         .with_status(101)
         .with_stderr_contains(
             "\
-[COMPILING] foo v0.0.1 ([CWD])
-[RUNNING] `rustc --crate-name build_script_build build.rs [..]--crate-type bin [..]-C linker=[..]/path/to/linker [..]`
-[ERROR] linker `[..]/path/to/linker` not found
-"
+[ERROR] configured linker `[..]/path/to/linker` not found
+It is set for `[..]` in [CWD]/.cargo/config.
+",
         )
         .run();
 }
@@ -497,17 +496,16 @@ fn custom_build_linker_host_target_with_bad_host_config() {
 
     // build.rs should fail due to bad host linker being set
     p.cargo("build -Z target-applies-to-host -Z host-config --verbose --target")
-            .arg(&target)
-            .masquerade_as_nightly_cargo()
-            .with_status(101)
-            .with_stderr_contains(
-                "\
-[COMPILING] foo v0.0.1 ([CWD])
-[RUNNING] `rustc --crate-name build_script_build build.rs [..]--crate-type bin [..]-C linker=[..]/path/to/host/linker [..]`
-[ERROR] linker `[..]/path/to/host/linker` not found
-"
-            )
-            .run();
+        .arg(&target)
+        .masquerade_as_nightly_cargo()
+        .with_status(101)
+        .with_stderr_contains(
+            "\
+[ERROR] configured linker `[..]/path/to/host/linker` not found
+It is set for `[..]` in [CWD]/.cargo/config.
+",
+        )
+        .run();
 }
 
 #[cargo_test]
@@ -532,17 +530,16 @@ fn custom_build_linker_bad_host() {
 
     // build.rs should fail due to bad host linker being set
     p.cargo("build -Z target-applies-to-host -Z host-config --verbose --target")
-            .arg(&target)
-            .masquerade_as_nightly_cargo()
-            .with_status(101)
-            .with_stderr_contains(
-                "\
-[COMPILING] foo v0.0.1 ([CWD])
-[RUNNING] `rustc --crate-name build_script_build build.rs [..]--crate-type bin [..]-C linker=[..]/path/to/host/linker [..]`
-[ERROR] linker `[..]/path/to/host/linker` not found
-"
-            )
-            .run();
+        .arg(&target)
+        .masquerade_as_nightly_cargo()
+        .with_status(101)
+        .with_stderr_contains(
+            "\
+[ERROR] configured linker `[..]/path/to/host/linker` not found
+It is set for `[..]` in [CWD]/.cargo/config.
+",
+        )
+        .run();
 }
 
 #[cargo_test]
@@ -569,17 +566,16 @@ fn custom_build_linker_bad_host_with_arch() {
 
     // build.rs should fail due to bad host linker being set
     p.cargo("build -Z target-applies-to-host -Z host-config --verbose --target")
-            .arg(&target)
-            .masquerade_as_nightly_cargo()
-            .with_status(101)
-            .with_stderr_contains(
-                "\
-[COMPILING] foo v0.0.1 ([CWD])
-[RUNNING] `rustc --crate-name build_script_build build.rs [..]--crate-type bin [..]-C linker=[..]/path/to/host/arch/linker [..]`
-[ERROR] linker `[..]/path/to/host/arch/linker` not found
-"
-            )
-            .run();
+        .arg(&target)
+        .masquerade_as_nightly_cargo()
+        .with_status(101)
+        .with_stderr_contains(
+            "\
+[ERROR] configured linker `[..]/path/to/host/arch/linker` not found
+It is set for `[..]` in [CWD]/.cargo/config.
+",
+        )
+        .run();
 }
 
 #[cargo_test]
@@ -645,17 +641,16 @@ fn custom_build_linker_bad_cross_arch_host() {
 
     // build.rs should fail due to bad host linker being set
     p.cargo("build -Z target-applies-to-host -Z host-config --verbose --target")
-            .arg(&target)
-            .masquerade_as_nightly_cargo()
-            .with_status(101)
-            .with_stderr_contains(
-                "\
-[COMPILING] foo v0.0.1 ([CWD])
-[RUNNING] `rustc --crate-name build_script_build build.rs [..]--crate-type bin [..]-C linker=[..]/path/to/host/linker [..]`
-[ERROR] linker `[..]/path/to/host/linker` not found
-"
-            )
-            .run();
+        .arg(&target)
+        .masquerade_as_nightly_cargo()
+        .with_status(101)
+        .with_stderr_contains(
+            "\
+[ERROR] configured linker `[..]/path/to/host/linker` not found
+It is set for `[..]` in [CWD]/.cargo/config.
+",
+        )
+        .run();
 }
 
 #[cargo_test]
@@ -1,6 +1,6 @@
 //! Tests for `[env]` config.
 
-use cargo_test_support::{basic_bin_manifest, project};
+use cargo_test_support::{basic_bin_manifest, basic_manifest, project};
 
 #[cargo_test]
 fn env_basic() {
@@ -153,3 +153,44 @@ fn env_no_override() {
         .with_stdout_contains("CARGO_PKG_NAME:unchanged")
         .run();
 }
+
+#[cargo_test]
+fn env_applies_to_rustc_probe() {
+    // A wrapper that insists on seeing `[env]` when rustc is probed.
+    let wrapper = project()
+        .at("wrapper")
+        .file("Cargo.toml", &basic_manifest("wrapper", "1.0.0"))
+        .file(
+            "src/main.rs",
+            r#"
+            fn main() {
+                let args: Vec<_> = std::env::args().skip(1).collect();
+                if args.iter().any(|arg| arg.starts_with("--print")) {
+                    assert_eq!(std::env::var("PROBE_SDK").unwrap(), "sdk");
+                }
+                let status = std::process::Command::new(&args[0])
+                    .args(&args[1..])
+                    .status()
+                    .unwrap();
+                std::process::exit(status.code().unwrap_or(1));
+            }
+            "#,
+        )
+        .build();
+    wrapper.cargo("build").run();
+
+    let p = project()
+        .file("src/lib.rs", "")
+        .file(
+            ".cargo/config",
+            r#"
+                [env]
+                PROBE_SDK = "sdk"
+            "#,
+        )
+        .build();
+
+    p.cargo("check")
+        .env("RUSTC_WRAPPER", wrapper.bin("wrapper"))
+        .run();
+}
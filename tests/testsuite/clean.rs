@@ -123,6 +123,58 @@ fn get_build_artifacts(path: &PathBuf) -> Vec<Result<PathBuf, GlobError>> {
         .collect::<Vec<Result<PathBuf, GlobError>>>()
 }
 
+#[cargo_test]
+#[cfg(all(target_os = "windows", target_env = "msvc"))]
+fn clean_example_pdb() {
+    // Examples are `bin` crates, so their `.pdb` is tracked like a binary's,
+    // named by the linker with underscores.
+    let p = project()
+        .file("src/lib.rs", "")
+        .file("examples/ex-1.rs", "fn main() {}")
+        .build();
+
+    p.cargo("build --examples").run();
+    let pdb = p.target_debug_dir().join("examples/ex_1.pdb");
+    assert!(pdb.is_file());
+
+    p.cargo("clean -p foo").run();
+    assert!(!pdb.exists());
+}
+
+#[cargo_test]
+#[cfg(target_os = "linux")]
+fn clean_split_debuginfo_unpacked() {
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [package]
+                name = "foo"
+                version = "0.0.1"
+
+                [profile.dev]
+                split-debuginfo = "unpacked"
+                incremental = false
+            "#,
+        )
+        .file("src/main.rs", "fn main() {}")
+        .build();
+
+    p.cargo("build").run();
+    let deps = p.target_debug_dir().join("deps");
+    let split_files = || -> Vec<PathBuf> {
+        glob::glob(deps.join("foo-*.*.dwo").to_str().unwrap())
+            .unwrap()
+            .chain(glob::glob(deps.join("foo-*.*.o").to_str().unwrap()).unwrap())
+            .map(Result::unwrap)
+            .collect()
+    };
+    assert!(!split_files().is_empty());
+
+    p.cargo("clean -p foo").run();
+    assert_eq!(split_files(), Vec::<PathBuf>::new());
+}
+
 #[cargo_test]
 fn clean_release() {
     let p = project()
@@ -475,6 +527,10 @@ fn assert_all_clean(build_dir: &Path) {
         {
             continue;
         }
+        // Cached target information is kept like `.rustc_info.json`.
+        if path.components().any(|c| c.as_os_str() == ".target-info") {
+            continue;
+        }
         if path.is_symlink() || path.is_file() {
             panic!("{:?} was not cleaned", path);
         }
@@ -2,7 +2,10 @@
 //!
 //! See `cargo_test_support::cross_compile` for more detail.
 
-use cargo_test_support::{basic_bin_manifest, basic_manifest, cross_compile, project};
+use cargo::core::compiler::{CompileKind, CompileTarget, RustcTargetData};
+use cargo::core::{Shell, Workspace};
+use cargo::Config;
+use cargo_test_support::{basic_bin_manifest, basic_manifest, cross_compile, paths, project};
 use cargo_test_support::{is_nightly, rustc_host};
 
 #[cargo_test]
@@ -389,20 +392,14 @@ fn linker() {
         )
         .build();
 
+    // The binary is being built and links, so the missing linker is an error.
     p.cargo("build -v --target")
         .arg(&target)
         .with_status(101)
-        .with_stderr_contains(&format!(
+        .with_stderr(&format!(
             "\
-[COMPILING] foo v0.5.0 ([CWD])
-[RUNNING] `rustc --crate-name foo src/foo.rs [..]--crate-type bin \
-    --emit=[..]link[..]-C debuginfo=2 \
-    -C metadata=[..] \
-    --out-dir [CWD]/target/{target}/debug/deps \
-    --target {target} \
-    -C linker=my-linker-tool \
-    -L dependency=[CWD]/target/{target}/debug/deps \
-    -L dependency=[CWD]/target/debug/deps`
+[ERROR] configured linker `my-linker-tool` not found
+It is set for `{target}` in [CWD]/.cargo/config.
 ",
             target = target,
         ))
@@ -1344,3 +1341,379 @@ fn doctest_xcompile_linker() {
         ))
         .run();
 }
+
+#[cargo_test]
+fn warn_missing_target_std() {
+    // A built-in target whose standard library is unlikely to be installed.
+    let target = "sparc64-unknown-linux-gnu";
+    let output = std::process::Command::new("rustc")
+        .arg("--print=sysroot")
+        .output()
+        .unwrap();
+    let sysroot = String::from_utf8(output.stdout).unwrap();
+    if std::path::Path::new(sysroot.trim())
+        .join("lib/rustlib")
+        .join(target)
+        .exists()
+    {
+        return;
+    }
+    let p = project().file("src/lib.rs", "").build();
+
+    p.cargo("check --target")
+        .arg(target)
+        .with_status(101)
+        .with_stderr_contains(&format!(
+            "[WARNING] the standard library for target `{}` was not found in `[..]`",
+            target
+        ))
+        .with_stderr_contains("[..]rustup target add[..]")
+        .run();
+
+    // Nothing is built when only resolving.
+    p.cargo("metadata --format-version=1 --filter-platform")
+        .arg(target)
+        .with_stderr_does_not_contain("[..]standard library for target[..]")
+        .run();
+
+    p.cargo("check --target")
+        .arg(target)
+        .masquerade_as_nightly_cargo()
+        .with_status(101)
+        .with_stderr_does_not_contain("[..]standard library for target[..]")
+        .run();
+}
+
+#[cargo_test]
+fn simulated_host_triple() {
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [package]
+                name = "foo"
+                version = "0.0.1"
+
+                [target.fake-host-triple.dependencies]
+                bar = { path = "bar" }
+            "#,
+        )
+        .file("src/lib.rs", "extern crate bar;")
+        .file("bar/Cargo.toml", &basic_manifest("bar", "0.0.1"))
+        .file("bar/src/lib.rs", "")
+        .build();
+
+    p.cargo("check")
+        .env(
+            "__CARGO_TEST_HOST_OVERRIDE_DO_NOT_USE_THIS",
+            "fake-host-triple",
+        )
+        .with_stderr(
+            "\
+[CHECKING] bar v0.0.1 ([CWD]/bar)
+[CHECKING] foo v0.0.1 ([CWD])
+[FINISHED] [..]
+",
+        )
+        .run();
+
+    // Without the override the dependency is for some other platform.
+    p.cargo("check")
+        .with_status(101)
+        .with_stderr_contains("[..]can't find crate for `bar`[..]")
+        .run();
+}
+
+#[cargo_test]
+fn host_and_target_probed_in_parallel() {
+    if cross_compile::disabled() {
+        return;
+    }
+
+    // The wrapper logs when each probe of rustc started and ended, and slows
+    // them down enough for any overlap to show.
+    let wrapper = project()
+        .at("wrapper")
+        .file("Cargo.toml", &basic_bin_manifest("wrapper"))
+        .file(
+            "src/main.rs",
+            r#"
+                use std::io::Write;
+                use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+                fn now() -> u128 {
+                    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis()
+                }
+
+                fn main() {
+                    let mut args = std::env::args().skip(1);
+                    let rustc = args.next().unwrap();
+                    let args: Vec<_> = args.collect();
+                    let probe = args.iter().any(|arg| arg.starts_with("--print"));
+                    let start = now();
+                    if probe {
+                        std::thread::sleep(Duration::from_millis(500));
+                    }
+                    let status = std::process::Command::new(rustc).args(&args).status().unwrap();
+                    if probe {
+                        let target = args
+                            .iter()
+                            .position(|arg| arg == "--target")
+                            .map_or("host", |i| &args[i + 1]);
+                        let line = format!("{} {} {}\n", target, start, now());
+                        std::fs::OpenOptions::new()
+                            .create(true)
+                            .append(true)
+                            .open(std::env::var("WRAPPER_LOG").unwrap())
+                            .unwrap()
+                            .write_all(line.as_bytes())
+                            .unwrap();
+                    }
+                    std::process::exit(status.code().unwrap_or(1));
+                }
+            "#,
+        )
+        .build();
+    wrapper.cargo("build").run();
+
+    let p = project()
+        .file("Cargo.toml", &basic_manifest("foo", "0.1.0"))
+        .file("src/lib.rs", "")
+        .build();
+    let log = p.root().join("probes.log");
+    p.cargo("check --target")
+        .arg(cross_compile::alternate())
+        .env("RUSTC_WRAPPER", wrapper.bin("wrapper"))
+        .env("WRAPPER_LOG", &log)
+        .run();
+
+    let probes: Vec<(String, u128, u128)> = std::fs::read_to_string(&log)
+        .unwrap()
+        .lines()
+        .map(|line| {
+            let mut parts = line.split(' ');
+            let target = parts.next().unwrap().to_string();
+            let start = parts.next().unwrap().parse().unwrap();
+            let end = parts.next().unwrap().parse().unwrap();
+            (target, start, end)
+        })
+        .collect();
+    let (host, target): (Vec<_>, Vec<_>) = probes.iter().partition(|p| p.0 == "host");
+    assert!(!host.is_empty() && !target.is_empty(), "{:?}", probes);
+    assert!(
+        host.iter()
+            .any(|h| target.iter().any(|t| h.1 < t.2 && t.1 < h.2)),
+        "host and target probes did not overlap: {:?}",
+        probes
+    );
+}
+
+#[cargo_test]
+fn rustflags_skipped_for_host_warning() {
+    if cross_compile::disabled() {
+        return;
+    }
+
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [package]
+                name = "foo"
+                version = "0.0.1"
+                build = "build.rs"
+            "#,
+        )
+        .file("build.rs", "fn main() {}")
+        .file("src/lib.rs", "")
+        .build();
+    let target = cross_compile::alternate();
+
+    // Only with `--verbose`.
+    p.cargo("check --target")
+        .arg(&target)
+        .env("RUSTFLAGS", "--cfg foo")
+        .with_stderr_does_not_contain("[WARNING] `RUSTFLAGS`[..]")
+        .run();
+
+    let output = p
+        .cargo("check -v --target")
+        .arg(&target)
+        .env("RUSTFLAGS", "--cfg foo")
+        .exec_with_output()
+        .unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let warning = format!(
+        "warning: `RUSTFLAGS` is not passed to build scripts and proc macros when cross compiling\n\
+         They are built for the host `{}`, while `RUSTFLAGS` only applies to `--target {}`, \
+         and `build.rustflags` doesn't reach them either. Flags for host artifacts can be set \
+         with `HOST_RUSTFLAGS` or `host.rustflags` (see `-Zhost-config`).\n",
+        rustc_host(),
+        target
+    );
+    assert_eq!(stderr.matches(&warning).count(), 1, "{}", stderr);
+
+    // Nothing to warn about without `RUSTFLAGS`.
+    p.cargo("check -v --target")
+        .arg(&target)
+        .with_stderr_does_not_contain("[WARNING] `RUSTFLAGS`[..]")
+        .run();
+}
+
+#[cargo_test]
+fn cross_run_warns_about_runner() {
+    if cross_compile::disabled() {
+        return;
+    }
+
+    let p = project().file("src/main.rs", "fn main() {}").build();
+    let target = cross_compile::alternate();
+    let warning = format!(
+        "[WARNING] executables built for `{}` probably cannot run on the host `{}`\n\
+         configure a runner with `target.{}.runner` to run them[..]",
+        target,
+        rustc_host(),
+        target
+    );
+
+    let mut execs = p.cargo("run --target");
+    execs.arg(target);
+    if cross_compile::alternate_arch() == "x86" {
+        // 64-bit x86 hosts run the 32-bit x86 alternate natively.
+        execs.with_stderr_does_not_contain("[..]probably cannot run on the host[..]");
+    } else {
+        execs.with_stderr_contains(&warning);
+        if !cross_compile::can_run_on_host() {
+            execs.with_status(101);
+        }
+    }
+    execs.run();
+
+    // Configuring a runner silences the warning.
+    p.change_file(
+        ".cargo/config",
+        &format!(
+            r#"
+                [target.{}]
+                runner = "nonexistent-runner"
+            "#,
+            target
+        ),
+    );
+    p.cargo("run --target")
+        .arg(target)
+        .with_status(101)
+        .with_stderr_does_not_contain("[..]probably cannot run on the host[..]")
+        .run();
+}
+
+#[cargo_test]
+fn explicit_host_target_needs_no_runner() {
+    // `--target <host>` builds for the host, so its executables run as is.
+    let p = project()
+        .file("src/main.rs", r#"fn main() { println!("hello"); }"#)
+        .build();
+
+    p.cargo("run --target")
+        .arg(rustc_host())
+        .with_stdout("hello")
+        .with_stderr_does_not_contain("[..]probably cannot run on the host[..]")
+        .run();
+}
+
+#[cargo_test]
+fn target_data_order_ignores_requested_order() {
+    if cross_compile::disabled() {
+        return;
+    }
+
+    let p = project().file("src/lib.rs", "").build();
+    let config = Config::new(
+        Shell::from_write(Box::new(Vec::new())),
+        p.root(),
+        paths::home(),
+    );
+    let ws = Workspace::new(&p.root().join("Cargo.toml"), &config).unwrap();
+    let kind = |name: &str| CompileKind::Target(CompileTarget::new(name).unwrap());
+    let host = kind(&rustc_host());
+    let alternate = kind(cross_compile::alternate());
+
+    let forward = RustcTargetData::new(&ws, &[host, alternate]).unwrap();
+    let backward = RustcTargetData::new(&ws, &[alternate, host]).unwrap();
+
+    let kinds: Vec<CompileKind> = forward.target_kinds().collect();
+    assert_eq!(kinds, backward.target_kinds().collect::<Vec<_>>());
+    let mut sorted = vec![host, alternate];
+    sorted.sort_by_key(|kind| forward.short_name(kind).to_string());
+    assert_eq!(kinds, sorted);
+    for kind in kinds {
+        assert_eq!(forward.cfg(kind), backward.cfg(kind));
+        assert_eq!(
+            forward.info(kind).sysroot_target_libdir,
+            backward.info(kind).sysroot_target_libdir
+        );
+    }
+}
+
+#[cargo_test]
+fn target_data_dep_activated_kinds() {
+    if cross_compile::disabled() {
+        return;
+    }
+
+    // Only the alternate target differs from the host in both ways.
+    let p = project()
+        .file(
+            "Cargo.toml",
+            &format!(
+                r#"
+                    [package]
+                    name = "foo"
+                    version = "0.1.0"
+
+                    [dependencies]
+                    baz = {{ path = "baz" }}
+
+                    [target.'cfg(not(all(target_arch = "{}", target_os = "{}")))'.dependencies]
+                    bar = {{ path = "bar" }}
+                "#,
+                std::env::consts::ARCH,
+                std::env::consts::OS
+            ),
+        )
+        .file("src/lib.rs", "")
+        .file("bar/Cargo.toml", &basic_manifest("bar", "0.1.0"))
+        .file("bar/src/lib.rs", "")
+        .file("baz/Cargo.toml", &basic_manifest("baz", "0.1.0"))
+        .file("baz/src/lib.rs", "")
+        .build();
+    let config = Config::new(
+        Shell::from_write(Box::new(Vec::new())),
+        p.root(),
+        paths::home(),
+    );
+    let ws = Workspace::new(&p.root().join("Cargo.toml"), &config).unwrap();
+    let kind = |name: &str| CompileKind::Target(CompileTarget::new(name).unwrap());
+    let host = kind(&rustc_host());
+    let alternate = kind(cross_compile::alternate());
+    let target_data = RustcTargetData::new(&ws, &[host, alternate]).unwrap();
+    let dep = |name: &str| {
+        ws.current()
+            .unwrap()
+            .dependencies()
+            .iter()
+            .find(|dep| dep.name_in_toml() == name)
+            .unwrap()
+            .clone()
+    };
+
+    let bar = dep("bar");
+    assert!(!target_data.dep_platform_activated(&bar, host));
+    assert!(target_data.dep_platform_activated(&bar, alternate));
+    assert_eq!(target_data.dep_activated_kinds(&bar), [alternate]);
+    assert!(!target_data.dep_activation_is_uniform(&bar));
+
+    let baz = dep("baz");
+    assert_eq!(target_data.dep_activated_kinds(&baz), [host, alternate]);
+    assert!(target_data.dep_activation_is_uniform(&baz));
+}
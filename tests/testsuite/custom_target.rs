@@ -1,7 +1,8 @@
 //! Tests for custom json target specifications.
 
+use cargo::core::compiler::CompileTarget;
 use cargo_test_support::is_nightly;
-use cargo_test_support::{basic_manifest, project};
+use cargo_test_support::{basic_manifest, project, rustc_host};
 use std::fs;
 
 const MINIMAL_LIB: &str = r#"
@@ -273,3 +274,89 @@ fn custom_target_ignores_filepath() {
         .with_stderr("[FINISHED] dev [unoptimized + debuginfo] target(s) in [..]")
         .run();
 }
+
+#[cargo_test]
+fn missing_target_spec() {
+    let p = project().file("src/lib.rs", "").build();
+
+    p.cargo("build --target ./nope.json")
+        .with_status(101)
+        .with_stderr(
+            "\
+[ERROR] target specification file `./nope.json` does not exist
+A target ending in `.json` must be the path to a target specification. \
+To use a custom target from a directory listed in `RUST_TARGET_PATH`, \
+pass its name without `.json`, like `--target nope`.
+",
+        )
+        .run();
+}
+
+#[cargo_test]
+fn config_keys_use_spec_file_stem() {
+    if !is_nightly() {
+        // Requires rustc to accept the target spec
+        return;
+    }
+    let p = project()
+        .file("Cargo.toml", &basic_manifest("foo", "0.0.1"))
+        .file("src/lib.rs", "")
+        .file("specs/custom-target.json", SIMPLE_SPEC)
+        .file(
+            ".cargo/config",
+            &format!(
+                r#"
+                    [target.custom-target]
+                    rustflags = ["--cfg", "from_spec_config"]
+
+                    [target.{}]
+                    rustflags = ["--cfg", "from_host_config"]
+                "#,
+                rustc_host()
+            ),
+        )
+        .build();
+
+    // A spec file is configured by its file stem, and passed to rustc by path.
+    p.cargo("rustc -Z unstable-options --target specs/custom-target.json --print cfg")
+        .masquerade_as_nightly_cargo()
+        .with_stdout_contains("from_spec_config")
+        .with_stdout_contains("target_os=\"none\"")
+        .with_stdout_does_not_contain("from_host_config")
+        .run();
+
+    p.cargo("rustc -Z unstable-options --print cfg")
+        .arg("--target")
+        .arg(rustc_host())
+        .masquerade_as_nightly_cargo()
+        .with_stdout_contains("from_host_config")
+        .with_stdout_does_not_contain("from_spec_config")
+        .run();
+}
+
+#[cargo_test]
+fn target_spec_file_is_parsed() {
+    let p = project()
+        .file(
+            "my-target.json",
+            r#"{"arch": "x86_64", "os": "none", "llvm-target": "x86_64-unknown-none"}"#,
+        )
+        .file("broken.json", "{")
+        .build();
+
+    let path = p.root().join("my-target.json");
+    let target = CompileTarget::new(path.to_str().unwrap()).unwrap();
+    let spec = target.load_spec().unwrap().unwrap();
+    assert_eq!(spec["os"], "none");
+    assert_eq!(target.short_name(), "my-target");
+
+    let builtin = CompileTarget::new("x86_64-unknown-linux-gnu").unwrap();
+    assert!(builtin.load_spec().unwrap().is_none());
+
+    let path = p.root().join("broken.json");
+    let broken = CompileTarget::new(path.to_str().unwrap()).unwrap();
+    let err = broken.load_spec().unwrap_err();
+    assert!(err
+        .to_string()
+        .starts_with("failed to parse target specification"));
+}
@@ -9,6 +9,7 @@ extern crate cargo_test_macro;
 mod advanced_env;
 mod alt_registry;
 mod artifact_dep;
+mod artifact_map;
 mod bad_config;
 mod bad_manifest_path;
 mod bench;
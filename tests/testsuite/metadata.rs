@@ -3983,3 +3983,110 @@ fn workspace_metadata_with_dependencies_no_deps_artifact() {
         )
         .run();
 }
+
+#[cargo_test]
+fn filter_platform_cfg_snapshot() {
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [package]
+                name = "foo"
+                version = "0.1.0"
+
+                [target.'cfg(target_os = "fake")'.dependencies]
+                bar = { path = "bar" }
+            "#,
+        )
+        .file("src/lib.rs", "")
+        .file("bar/Cargo.toml", &basic_lib_manifest("bar"))
+        .file("bar/src/lib.rs", "")
+        .build();
+
+    let output = std::process::Command::new("rustc")
+        .arg("-vV")
+        .output()
+        .unwrap();
+    let verbose_version = String::from_utf8(output.stdout).unwrap();
+    let snapshot = |rustc: &str| {
+        json!({
+            "rustc": rustc,
+            "targets": {
+                "fake-target": ["unix", "target_os=\"fake\""]
+            }
+        })
+        .to_string()
+    };
+    p.change_file("snapshot.json", &snapshot(&verbose_version));
+    p.change_file(".cargo/config.toml", "build.cfg-snapshot = 'snapshot.json'");
+
+    // `fake-target` is not a real target, so this only works if rustc is
+    // never asked about it.
+    p.cargo("metadata --format-version 1 --filter-platform fake-target -Zcfg-snapshot")
+        .masquerade_as_nightly_cargo()
+        .with_stdout_contains("[..]\"name\":\"bar\"[..]")
+        .run();
+
+    // The snapshot is ignored without -Zcfg-snapshot.
+    p.cargo("metadata --format-version 1 --filter-platform fake-target")
+        .with_status(101)
+        .run();
+
+    p.change_file("snapshot.json", &snapshot("rustc 0.0.0"));
+    p.cargo("metadata --format-version 1 --filter-platform fake-target -Zcfg-snapshot")
+        .masquerade_as_nightly_cargo()
+        .with_status(101)
+        .with_stderr_contains(
+            "[ERROR] the cfg snapshot `[..]snapshot.json` was captured with a different version of rustc",
+        )
+        .run();
+}
+
+#[cargo_test]
+fn filter_platform_probes_host_lazily() {
+    let p = project()
+        .file("Cargo.toml", &basic_lib_manifest("foo"))
+        .file("src/lib.rs", "")
+        .file("bar/Cargo.toml", &basic_lib_manifest("bar"))
+        .file("bar/src/lib.rs", "")
+        .build();
+
+    let output = std::process::Command::new("rustc")
+        .arg("-vV")
+        .output()
+        .unwrap();
+    let snapshot = json!({
+        "rustc": String::from_utf8(output.stdout).unwrap(),
+        "targets": {
+            "fake-target": ["unix", "target_os=\"fake\""]
+        }
+    });
+    p.change_file("snapshot.json", &snapshot.to_string());
+    p.change_file(".cargo/config.toml", "build.cfg-snapshot = 'snapshot.json'");
+
+    // Nothing depends on the host's cfg, so rustc is only asked for its version.
+    p.cargo("metadata --format-version 1 --filter-platform fake-target -Zcfg-snapshot")
+        .masquerade_as_nightly_cargo()
+        .env("CARGO_LOG", "cargo::util::rustc=debug")
+        .with_stderr_does_not_contain("[..]running `rustc - --crate-name ___[..]")
+        .run();
+
+    p.change_file(
+        "Cargo.toml",
+        r#"
+            [package]
+            name = "foo"
+            version = "0.5.0"
+            resolver = "2"
+
+            [target.'cfg(unix)'.build-dependencies]
+            bar = { path = "bar" }
+        "#,
+    );
+    // The v2 resolver checks build dependencies against the host's cfg.
+    p.cargo("metadata --format-version 1 --filter-platform fake-target -Zcfg-snapshot")
+        .masquerade_as_nightly_cargo()
+        .env("CARGO_LOG", "cargo::util::rustc=debug")
+        .with_stderr_contains("[..]running `rustc - --crate-name ___ --print=file-names [..]")
+        .run();
+}
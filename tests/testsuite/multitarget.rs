@@ -76,6 +76,54 @@ fn simple_build() {
     assert!(p.target_bin(t2, "foo").is_file());
 }
 
+#[cargo_test]
+fn platform_dependency_per_target() {
+    // Each target's dependencies are resolved against that target alone.
+    if cross_compile::disabled() {
+        return;
+    }
+    let t1 = cross_compile::alternate();
+    let t2 = rustc_host();
+    let p = project()
+        .file(
+            "Cargo.toml",
+            &format!(
+                r#"
+                    [package]
+                    name = "foo"
+                    version = "1.0.0"
+
+                    [target.{}.dependencies]
+                    bar = {{ path = "bar" }}
+                "#,
+                t1
+            ),
+        )
+        .file("src/main.rs", "fn main() {}")
+        .file("bar/Cargo.toml", &basic_manifest("bar", "1.0.0"))
+        .file("bar/src/lib.rs", "")
+        .build();
+
+    p.cargo("build -v -Z multitarget")
+        .arg("--target")
+        .arg(&t1)
+        .arg("--target")
+        .arg(&t2)
+        .masquerade_as_nightly_cargo()
+        .with_stderr_contains(&format!(
+            "[RUNNING] `rustc --crate-name bar [..]--target {}[..]",
+            t1
+        ))
+        .with_stderr_does_not_contain(&format!(
+            "[RUNNING] `rustc --crate-name bar [..]--target {}[..]",
+            t2
+        ))
+        .run();
+
+    assert!(p.target_bin(t1, "foo").is_file());
+    assert!(p.target_bin(t2, "foo").is_file());
+}
+
 #[cargo_test]
 fn simple_build_with_config() {
     if cross_compile::disabled() {
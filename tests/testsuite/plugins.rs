@@ -325,13 +325,13 @@ fn native_plugin_dependency_with_custom_linker() {
 
     bar.cargo("build --verbose")
         .with_status(101)
-        .with_stderr_contains(
+        .with_stderr(&format!(
             "\
-[COMPILING] foo v0.0.1 ([..])
-[RUNNING] `rustc [..] -C linker=nonexistent-linker [..]`
-[ERROR] [..]linker[..]
+[ERROR] configured linker `nonexistent-linker` not found
+It is set for `{}` in [..]/bar/.cargo/config.
 ",
-        )
+            target
+        ))
         .run();
 }
 
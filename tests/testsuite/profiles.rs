@@ -671,6 +671,47 @@ fn rustflags_works_with_env() {
         .run();
 }
 
+#[cargo_test]
+fn rustflags_only_for_matching_profile() {
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+            cargo-features = ["profile-rustflags"]
+
+            [profile.release]
+            rustflags = ["-C", "force-frame-pointers=yes"]
+
+            [package]
+            name = "foo"
+            version = "0.0.1"
+            "#,
+        )
+        .file("src/main.rs", "fn main() {}")
+        .build();
+
+    p.cargo("build -v")
+        .masquerade_as_nightly_cargo()
+        .with_stderr_does_not_contain("[..]force-frame-pointers[..]")
+        .run();
+    p.cargo("build -v --release")
+        .masquerade_as_nightly_cargo()
+        .with_stderr_contains(
+            "[RUNNING] `rustc --crate-name foo [..] -C force-frame-pointers=yes [..]",
+        )
+        .run();
+
+    // Flags from the environment come after, so they win.
+    p.cargo("build -v --release")
+        .env("RUSTFLAGS", "-C force-frame-pointers=no")
+        .masquerade_as_nightly_cargo()
+        .with_stderr_contains(
+            "[RUNNING] `rustc --crate-name foo [..] -C force-frame-pointers=yes [..]\
+             -C force-frame-pointers=no[..]",
+        )
+        .run();
+}
+
 #[cargo_test]
 fn rustflags_requires_cargo_feature() {
     let p = project()
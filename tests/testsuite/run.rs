@@ -1,6 +1,8 @@
 //! Tests for the `cargo run` command.
 
-use cargo_test_support::{basic_bin_manifest, basic_lib_manifest, project, Project};
+use cargo_test_support::{
+    basic_bin_manifest, basic_lib_manifest, paths, project, rustc_host, Project,
+};
 use cargo_util::paths::dylib_path_envvar;
 
 #[cargo_test]
@@ -1166,6 +1168,68 @@ fn run_with_library_paths() {
     p.cargo("run").run();
 }
 
+#[cargo_test]
+fn sysroot_override_library_path() {
+    let sysroot = paths::root().join("sysroot");
+    let libdir = sysroot.join("lib/rustlib").join(rustc_host()).join("lib");
+    std::fs::create_dir_all(&libdir).unwrap();
+    let p = project()
+        .file(
+            "src/main.rs",
+            &format!(
+                r##"
+                    fn main() {{
+                        let search_path = std::env::var_os("{}").unwrap();
+                        let paths = std::env::split_paths(&search_path).collect::<Vec<_>>();
+                        println!("{{:#?}}", paths);
+                        assert!(paths.contains(&r#"{}"#.into()));
+                    }}
+                "##,
+                dylib_path_envvar(),
+                libdir.display(),
+            ),
+        )
+        .build();
+
+    p.cargo("run -Zsysroot")
+        .masquerade_as_nightly_cargo()
+        .env("CARGO_BUILD_SYSROOT", &sysroot)
+        .run();
+
+    // The key is ignored without -Zsysroot.
+    p.cargo("run")
+        .env("CARGO_BUILD_SYSROOT", &sysroot)
+        .with_status(101)
+        .run();
+}
+
+#[cargo_test]
+fn sysroot_override_must_have_rustlib() {
+    let p = project()
+        .file("src/main.rs", "fn main() {}")
+        .file("sysroot/lib/.keep", "")
+        .build();
+
+    p.cargo("run -Zsysroot")
+        .masquerade_as_nightly_cargo()
+        .env("CARGO_BUILD_SYSROOT", p.root().join("sysroot"))
+        .with_status(101)
+        .with_stderr(
+            "\
+[ERROR] the sysroot `[..]sysroot` from `build.sysroot` has no `lib/rustlib` directory
+It should be laid out like the directory printed by `rustc --print=sysroot`.
+",
+        )
+        .run();
+
+    p.cargo("run -Zsysroot")
+        .masquerade_as_nightly_cargo()
+        .env("CARGO_BUILD_SYSROOT", p.root().join("missing"))
+        .with_status(101)
+        .with_stderr("[ERROR] the sysroot `[..]missing` from `build.sysroot` does not exist")
+        .run();
+}
+
 #[cargo_test]
 fn library_paths_sorted_alphabetically() {
     let p = project();
@@ -1,6 +1,8 @@
 //! Tests for the `cargo rustc` command.
 
-use cargo_test_support::{basic_bin_manifest, basic_lib_manifest, basic_manifest, project};
+use cargo_test_support::{
+    basic_bin_manifest, basic_lib_manifest, basic_manifest, project, rustc_host, tools,
+};
 
 const CARGO_RUSTC_ERROR: &str =
     "[ERROR] extra arguments to `rustc` can only be passed to one target, consider filtering
@@ -215,6 +217,32 @@ Binaries, tests, and benchmarks are always the `bin` crate type",
         .run();
 }
 
+#[cargo_test]
+fn fails_with_unsupported_crate_type() {
+    // The wrapper makes rustc behave like it does for targets without
+    // `dylib` support, such as musl.
+    let wrapper = tools::probe_wrapper();
+
+    let p = project().file("src/lib.rs", "").build();
+
+    p.cargo("rustc --crate-type rlib,dylib -Zunstable-options")
+        .env("RUSTC_WRAPPER", &wrapper)
+        .env(
+            "WRAPPER_DROP_DYLIB",
+            "warning: dropping unsupported crate type `dylib` for target `fake`",
+        )
+        .masquerade_as_nightly_cargo()
+        .with_status(101)
+        .with_stderr(&format!(
+            "\
+[ERROR] target `{}` does not support crate type `dylib`
+Supported crate types: bin, rlib, cdylib, staticlib, proc-macro
+",
+            rustc_host()
+        ))
+        .run();
+}
+
 #[cargo_test]
 fn build_with_crate_type_for_foo() {
     let p = project().file("src/lib.rs", "").build();
@@ -1,7 +1,7 @@
 //! Tests for the cache file for the rustc version info.
 
 use cargo_test_support::{basic_bin_manifest, paths::CargoPathExt};
-use cargo_test_support::{basic_manifest, project};
+use cargo_test_support::{basic_manifest, project, rustc_host, tools};
 use std::env;
 
 const MISS: &str = "[..] rustc info cache miss[..]";
@@ -184,3 +184,141 @@ fn rustc_info_cache_with_wrappers() {
             .run();
     }
 }
+
+#[cargo_test]
+fn target_info_cache() {
+    let wrapper = tools::probe_wrapper();
+
+    let p = project()
+        .file("src/main.rs", r#"fn main() { println!("hello"); }"#)
+        .build();
+    let log = p.root().join("wrapper.log");
+
+    p.cargo("build")
+        .env("RUSTC_WRAPPER", &wrapper)
+        .env("WRAPPER_LOG", &log)
+        .run();
+    assert!(std::fs::read_to_string(&log).unwrap().contains("--print"));
+    assert!(p.root().join("target/.target-info").is_dir());
+
+    // Without the rustc output cache, the targets are still not probed again.
+    p.root().join("target/.rustc_info.json").rm_rf();
+    log.rm_rf();
+    p.change_file("src/main.rs", r#"fn main() { println!("hi"); }"#);
+    p.cargo("build")
+        .env("RUSTC_WRAPPER", &wrapper)
+        .env("WRAPPER_LOG", &log)
+        .run();
+    let calls = std::fs::read_to_string(&log).unwrap();
+    assert!(!calls.contains("--print"), "{}", calls);
+
+    // The cache follows `CARGO_CACHE_RUSTC_INFO`.
+    p.root().join("target/.target-info").rm_rf();
+    p.cargo("build").env("CARGO_CACHE_RUSTC_INFO", "0").run();
+    assert!(!p.root().join("target/.target-info").exists());
+}
+
+#[cargo_test]
+fn target_info_cache_follows_rustflags() {
+    // `--cfg` in RUSTFLAGS shows up in what rustc reports about the target,
+    // so the cached report must not outlive a change of flags.
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [package]
+                name = "a"
+                version = "0.0.1"
+
+                [target.'cfg(with_b)'.dependencies]
+                b = { path = "b" }
+            "#,
+        )
+        .file("src/lib.rs", "")
+        .file("b/Cargo.toml", &basic_manifest("b", "0.0.1"))
+        .file("b/src/lib.rs", "")
+        .build();
+
+    p.cargo("build").env("RUSTFLAGS", "--cfg with_b").run();
+    assert!(p.root().join("target/.target-info").is_dir());
+
+    for (rustflags, tree) in [
+        ("", "a v0.0.1 ([..]/foo)\n"),
+        (
+            "--cfg with_b",
+            "a v0.0.1 ([..]/foo)\n└── b v0.0.1 ([..]/foo/b)\n",
+        ),
+        ("", "a v0.0.1 ([..]/foo)\n"),
+    ] {
+        // Only the cache in the target directory is left to reuse.
+        p.root().join("target/.rustc_info.json").rm_rf();
+        p.cargo("tree")
+            .env("RUSTFLAGS", rustflags)
+            .with_stdout(tree)
+            .run();
+    }
+}
+
+#[cargo_test]
+fn discovered_crate_type_uses_rustc_info_cache() {
+    // Crate types outside the usual set are discovered lazily, and that run
+    // goes through the same cache as the probes in `TargetInfo::new`, failed
+    // runs included.
+    let wrapper = tools::probe_wrapper();
+
+    let p = project().file("src/lib.rs", "").build();
+    let log = p.root().join("wrapper.log");
+
+    for _ in 0..2 {
+        p.cargo("rustc --crate-type bogus -Zunstable-options")
+            .masquerade_as_nightly_cargo()
+            .env("RUSTC_WRAPPER", &wrapper)
+            .env("WRAPPER_LOG", &log)
+            .with_status(101)
+            .with_stderr_contains(
+                "[..]failed to run `rustc` to learn about crate-type bogus information[..]",
+            )
+            .run();
+    }
+    let calls = std::fs::read_to_string(&log).unwrap();
+    let probes = calls
+        .lines()
+        .filter(|line| line.contains("\"bogus\""))
+        .count();
+    assert_eq!(probes, 1, "{}", calls);
+}
+
+#[cargo_test]
+fn unsupported_crate_type_is_cached() {
+    // The wrapper makes rustc drop `dylib` like it does for targets without
+    // it, but with a message Cargo doesn't know.
+    let wrapper = tools::probe_wrapper();
+
+    let p = project().file("src/lib.rs", "").build();
+    let log = p.root().join("wrapper.log");
+
+    for _ in 0..2 {
+        p.cargo("rustc --crate-type rlib,dylib -Zunstable-options")
+            .masquerade_as_nightly_cargo()
+            .env("RUSTC_WRAPPER", &wrapper)
+            .env("WRAPPER_LOG", &log)
+            .env(
+                "WRAPPER_DROP_DYLIB",
+                "warning: `dylib` is not available here",
+            )
+            .with_status(101)
+            .with_stderr_contains(format!(
+                "[ERROR] target `{}` does not support crate type `dylib`",
+                rustc_host()
+            ))
+            .run();
+    }
+    // Probed once on its own, after the combined probe came up short.
+    let calls = std::fs::read_to_string(&log).unwrap();
+    let probes = calls
+        .lines()
+        .filter(|line| line.contains("--print") && line.contains(r#""--crate-type", "dylib""#))
+        .filter(|line| !line.contains(r#""--crate-type", "rlib""#))
+        .count();
+    assert_eq!(probes, 1, "{}", calls);
+}
@@ -122,3 +122,41 @@ fn whitespace() {
     let contents = p.read_file("target/doc/foo/index.html");
     assert!(contents.contains(SPACED_VERSION));
 }
+
+#[cargo_test]
+fn rustdoc_test_flags_only_for_doctests() {
+    let p = project()
+        .file(
+            "src/lib.rs",
+            r#"
+                /// ```
+                /// assert!(cfg!(doctest_only));
+                /// ```
+                pub fn f() {}
+            "#,
+        )
+        .file(
+            ".cargo/config",
+            r#"
+                [build]
+                rustdocflags = ["--cfg", "foo"]
+                rustdoc-test-flags = ["--cfg", "doctest_only"]
+            "#,
+        )
+        .build();
+
+    p.cargo("doc -v")
+        .with_stderr_contains("[RUNNING] `rustdoc [..] --cfg foo[..]`")
+        .with_stderr_does_not_contain("[..]doctest_only[..]")
+        .run();
+    p.cargo("test --doc -v -Zrustdoc-test-flags")
+        .masquerade_as_nightly_cargo()
+        .with_stderr_contains("[RUNNING] `rustdoc [..]--test [..]--cfg foo --cfg doctest_only[..]`")
+        .run();
+
+    // The key is ignored without -Zrustdoc-test-flags.
+    p.cargo("test --doc -v")
+        .with_status(101)
+        .with_stderr_does_not_contain("[RUNNING] `rustdoc [..]doctest_only[..]`")
+        .run();
+}
@@ -2,7 +2,8 @@
 
 use cargo_test_support::registry::Package;
 use cargo_test_support::{
-    basic_lib_manifest, basic_manifest, paths, project, project_in_home, rustc_host,
+    basic_lib_manifest, basic_manifest, cross_compile, paths, project, project_in_home, rustc_host,
+    tools,
 };
 use std::fs;
 
@@ -1328,6 +1329,111 @@ fn cfg_rustflags_normal_source() {
         .run();
 }
 
+#[cargo_test]
+fn cfg_rustflags_overlapping_tables() {
+    let p = project()
+        .file("src/lib.rs", "")
+        .file(
+            ".cargo/config",
+            r#"
+                [target.'cfg(not(foo))']
+                rustflags = ["--cfg", "not_foo"]
+
+                [target.'cfg(debug_assertions)']
+                rustflags = ["--cfg", "debug"]
+
+                [target.'cfg(foo)']
+                rustflags = ["--cfg", "foo"]
+            "#,
+        )
+        .build();
+
+    p.cargo("build -v")
+        .with_stderr_contains(
+            "\
+[WARNING] rustflags from several `target.'cfg(...)'` tables apply to `[..]`: \
+`cfg(debug_assertions)`, `cfg(not(foo))`
+All of their flags are passed, in this order.
+",
+        )
+        .with_stderr_contains("[RUNNING] `rustc [..]--cfg debug --cfg not_foo[..]`")
+        .with_stderr_does_not_contain("[..]--cfg foo[..]")
+        .run();
+
+    // Only shown with `--verbose`.
+    p.cargo("build")
+        .with_stderr_does_not_contain("[WARNING] rustflags from several[..]")
+        .run();
+}
+
+#[cargo_test]
+fn rustflags_dedup() {
+    let p = project()
+        .file("src/lib.rs", "")
+        .file(
+            ".cargo/config",
+            r#"
+                [build]
+                rustflags-merge = true
+                rustflags = ["--cfg", "foo", "-C", "target-cpu=native"]
+            "#,
+        )
+        .build();
+
+    p.cargo("build -v -Zrustflags-merge")
+        .masquerade_as_nightly_cargo()
+        .env("RUSTFLAGS", "--cfg foo -Ctarget-cpu=native")
+        .with_stderr_contains(
+            "[RUNNING] `rustc [..]--cfg foo -Ctarget-cpu=native --cfg foo -C target-cpu=native[..]`",
+        )
+        .run();
+
+    // The key is ignored without -Zrustflags-dedup.
+    p.cargo("build -v -Zrustflags-merge")
+        .masquerade_as_nightly_cargo()
+        .env("RUSTFLAGS", "--cfg foo -Ctarget-cpu=native")
+        .env("CARGO_BUILD_RUSTFLAGS_DEDUP", "true")
+        .with_stderr_contains(
+            "[RUNNING] `rustc [..]--cfg foo -Ctarget-cpu=native --cfg foo -C target-cpu=native[..]`",
+        )
+        .run();
+
+    p.cargo("build -v -Zrustflags-merge -Zrustflags-dedup")
+        .masquerade_as_nightly_cargo()
+        .env("RUSTFLAGS", "--cfg foo -Ctarget-cpu=native")
+        .env("CARGO_BUILD_RUSTFLAGS_DEDUP", "true")
+        .with_stderr_contains("[RUNNING] `rustc [..]--cfg foo -C target-cpu=native[..]`")
+        .with_stderr_does_not_contain("[..]-Ctarget-cpu=native[..]")
+        .run();
+}
+
+#[cargo_test]
+fn cfg_rustflags_invalid_expression() {
+    let p = project()
+        .file("src/lib.rs", "")
+        .file(
+            ".cargo/config",
+            r#"
+                [target.'cfg(bar =)']
+                rustflags = ["--cfg", "bar"]
+            "#,
+        )
+        .build();
+
+    p.cargo("build -v")
+        .with_stderr(
+            "\
+[WARNING] ignoring [target] config table `cfg(bar =)`: failed to parse `bar =` as a cfg \
+expression: expected a string, but cfg expression ended
+[COMPILING] foo v0.0.1 ([CWD])
+[RUNNING] `rustc [..]`
+[FINISHED] [..]
+",
+        )
+        .with_stderr_does_not_contain("[..]--cfg bar[..]")
+        .run();
+}
+
 // target.'cfg(...)'.rustflags takes precedence over build.rustflags
 #[cargo_test]
 fn cfg_rustflags_precedence() {
@@ -1415,6 +1521,37 @@ fn cfg_rustflags_precedence() {
         .run();
 }
 
+#[cargo_test]
+fn cfg_rustflags_nested_expressions() {
+    let p = project()
+        .file("src/lib.rs", "")
+        .file(
+            ".cargo/config",
+            r#"
+                [target.'cfg(all(target_os = "linux", target_arch = "x86_64"))']
+                rustflags = ["--cfg", "linux_x86_64"]
+
+                [target.'cfg(any(not(windows), all(windows, not(unix))))']
+                rustflags = ["--cfg", "always"]
+
+                [target.'cfg(not(any(unix, windows)))']
+                rustflags = ["--cfg", "never"]
+            "#,
+        )
+        .build();
+
+    let mut execs = p.cargo("build -v");
+    execs
+        .with_stderr_contains("[RUNNING] `rustc [..]--cfg always[..]`")
+        .with_stderr_does_not_contain("[..]--cfg never[..]");
+    if cfg!(all(target_os = "linux", target_arch = "x86_64")) {
+        execs.with_stderr_contains("[RUNNING] `rustc [..]--cfg linux_x86_64[..]`");
+    } else {
+        execs.with_stderr_does_not_contain("[..]--cfg linux_x86_64[..]");
+    }
+    execs.run();
+}
+
 #[cargo_test]
 fn target_rustflags_string_and_array_form1() {
     let p1 = project()
@@ -1671,3 +1808,656 @@ fn host_config_rustflags_with_target() {
         .arg("host.rustflags=[\"--cfg=foo\"]")
         .run();
 }
+
+#[cargo_test]
+fn host_rustflags_env_when_cross_compiling() {
+    if cross_compile::disabled() {
+        return;
+    }
+    let p = project()
+        .file(
+            "src/lib.rs",
+            "#[cfg(foo)] compile_error!(\"foo is for the host\");",
+        )
+        .file("build.rs", "fn main() { assert!(cfg!(foo)); }")
+        .build();
+
+    p.cargo("build -Zhost-config -Ztarget-applies-to-host --target")
+        .arg(cross_compile::alternate())
+        .env("HOST_RUSTFLAGS", "--cfg foo")
+        .masquerade_as_nightly_cargo()
+        .run();
+}
+
+#[cargo_test]
+fn host_rustflags_env_precedence() {
+    let p = project()
+        .file("src/lib.rs", "")
+        .file(
+            "build.rs",
+            "fn main() { assert!(cfg!(from_env)); assert!(!cfg!(from_config)); }",
+        )
+        .file(
+            ".cargo/config.toml",
+            "
+            [host]
+            rustflags = [\"--cfg=from_config\"]
+            ",
+        )
+        .build();
+
+    // Only with `-Zhost-config`.
+    p.cargo("build -Ztarget-applies-to-host --target")
+        .arg(rustc_host())
+        .env("HOST_RUSTFLAGS", "--cfg from_env")
+        .masquerade_as_nightly_cargo()
+        .with_status(101)
+        .with_stderr_contains("[..]assertion failed[..]")
+        .run();
+
+    p.cargo("build -Zhost-config -Ztarget-applies-to-host --target")
+        .arg(rustc_host())
+        .env("HOST_RUSTFLAGS", "--cfg from_env")
+        .masquerade_as_nightly_cargo()
+        .run();
+}
+
+#[cargo_test]
+fn target_rustflags_when_building_other_target() {
+    if cross_compile::disabled() {
+        return;
+    }
+    let alt = cross_compile::alternate();
+    let p = project()
+        .file("src/lib.rs", "")
+        .file(
+            ".cargo/config",
+            &format!(
+                "
+            [target.{}.rustflags-when-building]
+            {} = [\"-Z\", \"bogus\"]
+            ",
+                rustc_host(),
+                alt
+            ),
+        )
+        .build();
+
+    // The flags don't apply if the other target isn't part of the build.
+    p.cargo("build --target").arg(rustc_host()).run();
+
+    // The table is ignored without -Zrustflags-when-building.
+    p.cargo("build -Zmultitarget")
+        .arg("--target")
+        .arg(rustc_host())
+        .arg("--target")
+        .arg(alt)
+        .masquerade_as_nightly_cargo()
+        .run();
+
+    p.cargo("build -Zmultitarget -Zrustflags-when-building")
+        .arg("--target")
+        .arg(rustc_host())
+        .arg("--target")
+        .arg(alt)
+        .masquerade_as_nightly_cargo()
+        .with_status(101)
+        .with_stderr_contains("[..]bogus[..]")
+        .run();
+}
+
+#[cargo_test]
+fn target_crate_type_rustflags() {
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [package]
+                name = "foo"
+                version = "0.0.1"
+
+                [lib]
+                crate-type = ["cdylib"]
+            "#,
+        )
+        .file("src/lib.rs", "")
+        .file("src/main.rs", "fn main() {}")
+        .file(
+            ".cargo/config",
+            &format!(
+                "
+            [target.{}.crate-type.cdylib]
+            rustflags = ['--cfg', 'only_cdylib']
+            ",
+                rustc_host()
+            ),
+        )
+        .build();
+
+    p.cargo("build -v")
+        .with_stderr_contains(
+            "[RUNNING] `rustc --crate-name foo [..]--crate-type cdylib [..]--cfg only_cdylib[..]",
+        )
+        .with_stderr_line_without(
+            &["[RUNNING] `rustc --crate-name foo [..]--crate-type bin"],
+            &["only_cdylib"],
+        )
+        .run();
+}
+
+#[cargo_test]
+fn overflow_checks_conflict_with_profile() {
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [package]
+                name = "foo"
+                version = "0.0.1"
+
+                [profile.dev]
+                overflow-checks = true
+            "#,
+        )
+        .file("src/lib.rs", "")
+        .file("src/main.rs", "fn main() {}")
+        .build();
+
+    // Warned once, even though two units are built.
+    p.cargo("build")
+        .env("RUSTFLAGS", "-C overflow-checks=off")
+        .with_stderr(&format!(
+            "\
+[WARNING] profile `dev` sets `overflow-checks = true`, but rustflags turn \
+overflow checks off for `{}`
+[COMPILING] foo v0.0.1 ([CWD])
+[FINISHED] [..]
+",
+            rustc_host()
+        ))
+        .run();
+
+    // Still warned when nothing is rebuilt.
+    p.cargo("build")
+        .env("RUSTFLAGS", "-C overflow-checks=off")
+        .with_stderr(&format!(
+            "\
+[WARNING] profile `dev` sets `overflow-checks = true`, but rustflags turn \
+overflow checks off for `{}`
+[FINISHED] [..]
+",
+            rustc_host()
+        ))
+        .run();
+
+    p.cargo("build -vv")
+        .env("RUSTFLAGS", "-C overflow-checks=off")
+        .with_stderr_contains("[NOTE] overflow checks are off for `foo`")
+        .run();
+
+    // Nothing to report when the rustflags agree with the profile.
+    p.cargo("build -vv")
+        .env("RUSTFLAGS", "-C overflow-checks=on")
+        .with_stderr_does_not_contain("[NOTE] overflow checks[..]")
+        .with_stderr_does_not_contain("[WARNING] profile `dev`[..]")
+        .run();
+
+    // The profile's default is not something the user set.
+    p.change_file("Cargo.toml", &basic_manifest("foo", "0.0.1"));
+    p.cargo("build -vv")
+        .env("RUSTFLAGS", "-C overflow-checks=off")
+        .with_stderr_contains("[NOTE] overflow checks are off for `foo`")
+        .with_stderr_does_not_contain("[WARNING] profile `dev`[..]")
+        .run();
+}
+
+#[cargo_test]
+fn debug_assertions_conflict_with_profile() {
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [package]
+                name = "foo"
+                version = "0.0.1"
+
+                [profile.dev]
+                debug-assertions = true
+                overflow-checks = true
+
+                [profile.release]
+                debug-assertions = false
+            "#,
+        )
+        .file("src/lib.rs", "")
+        .build();
+
+    // Overflow checks follow debug assertions unless set separately.
+    p.cargo("build")
+        .env("RUSTFLAGS", "-C debug-assertions=off")
+        .with_stderr(&format!(
+            "\
+[WARNING] profile `dev` sets `debug-assertions = true`, but rustflags turn \
+debug assertions off for `{host}`
+[WARNING] profile `dev` sets `overflow-checks = true`, but rustflags turn \
+overflow checks off for `{host}`
+[COMPILING] foo v0.0.1 ([CWD])
+[FINISHED] [..]
+",
+            host = rustc_host()
+        ))
+        .run();
+
+    p.cargo("build --release -vv")
+        .env("RUSTFLAGS", "-C debug-assertions")
+        .with_stderr_contains("[NOTE] debug assertions are on for `foo`")
+        .with_stderr_contains("[NOTE] overflow checks are on for `foo`")
+        .with_stderr_contains(
+            "[WARNING] profile `release` sets `debug-assertions = false`, \
+             but rustflags turn debug assertions on for `[..]`",
+        )
+        // The release profile leaves overflow checks to follow debug
+        // assertions.
+        .with_stderr_does_not_contain("[WARNING] profile `release` sets `overflow-checks[..]")
+        .run();
+}
+
+#[cargo_test]
+fn target_rustflags_command() {
+    let p = project()
+        .file(
+            "src/lib.rs",
+            r#"
+                #[cfg(not(from_command))]
+                compile_error!("flags from rustflags-command were not passed");
+            "#,
+        )
+        .file(
+            ".cargo/config",
+            &format!(
+                "
+            [target.{}]
+            rustflags-command = ['{}', '--cfg', 'from_command']
+            ",
+                rustc_host(),
+                tools::echo().display()
+            ),
+        )
+        .build();
+
+    // The key is ignored without -Zrustflags-command.
+    p.cargo("build")
+        .with_status(101)
+        .with_stderr_contains("[..]flags from rustflags-command were not passed[..]")
+        .run();
+
+    p.cargo("build -Zrustflags-command")
+        .masquerade_as_nightly_cargo()
+        .run();
+
+    // RUSTFLAGS takes precedence, so the command's flags are dropped.
+    p.cargo("build -Zrustflags-command")
+        .masquerade_as_nightly_cargo()
+        .env("RUSTFLAGS", "--cfg foo")
+        .with_status(101)
+        .with_stderr_contains("[..]flags from rustflags-command were not passed[..]")
+        .run();
+}
+
+#[cargo_test]
+fn target_rustflags_command_quoted() {
+    let p = project()
+        .file(
+            "src/lib.rs",
+            r#"
+                #[cfg(not(feature = "a b"))]
+                compile_error!("quoted flag from rustflags-command was split");
+            "#,
+        )
+        .file(
+            ".cargo/config",
+            &format!(
+                r#"
+            [target.{}]
+            rustflags-command = ['{}', '--cfg', "'feature=\"a b\"'"]
+            "#,
+                rustc_host(),
+                tools::echo().display()
+            ),
+        )
+        .build();
+
+    p.cargo("build -Zrustflags-command")
+        .masquerade_as_nightly_cargo()
+        .run();
+}
+
+#[cargo_test]
+fn target_rustflags_command_fails() {
+    let p = project()
+        .file("src/lib.rs", "")
+        .file(
+            ".cargo/config",
+            &format!(
+                "
+            [target.{}]
+            rustflags-command = 'does-not-exist'
+            ",
+                rustc_host()
+            ),
+        )
+        .build();
+
+    p.cargo("build -Zrustflags-command")
+        .masquerade_as_nightly_cargo()
+        .with_status(101)
+        .with_stderr_contains(&format!(
+            "[ERROR] failed to run `does-not-exist` configured in \
+             `target.{}.rustflags-command` (from [..])",
+            rustc_host()
+        ))
+        .run();
+}
+
+#[cargo_test]
+fn target_rustflags_wrong_type() {
+    let p = project()
+        .file("src/lib.rs", "")
+        .file(
+            ".cargo/config",
+            &format!(
+                "
+            [target.{}]
+            rustflags = 1
+            ",
+                rustc_host()
+            ),
+        )
+        .build();
+
+    p.cargo("build")
+        .with_status(101)
+        .with_stderr_contains(&format!(
+            "[ERROR] expected a list of strings for `target.{}.rustflags`, \
+             found integer in [..]/foo/.cargo/config",
+            rustc_host()
+        ))
+        .run();
+}
+
+#[cargo_test]
+fn build_rustflags_wrong_type() {
+    let p = project()
+        .file("src/lib.rs", "")
+        .file(
+            ".cargo/config",
+            "
+            [build.rustflags]
+            foo = 'bar'
+            ",
+        )
+        .build();
+
+    p.cargo("build")
+        .with_status(101)
+        .with_stderr_contains(
+            "[ERROR] expected a list of strings for `build.rustflags`, \
+             found table in [..]/foo/.cargo/config",
+        )
+        .run();
+}
+
+#[cargo_test]
+#[cfg(unix)]
+fn env_rustflags_quoted_argument() {
+    let p = project().file("src/lib.rs", "").build();
+
+    p.cargo("build -vv")
+        .env("RUSTFLAGS", "\"-Clink-arg=-Wl,-rpath,/a b\" --cfg foo")
+        .with_stderr_contains(
+            "[NOTE] resolved rustflags for `[..]`: '-Clink-arg=-Wl,-rpath,/a b' --cfg foo",
+        )
+        .run();
+}
+
+#[cargo_test]
+#[cfg(unix)]
+fn encoded_rustflags_with_space() {
+    let p = project().file("src/lib.rs", "").build();
+
+    p.cargo("build -vv")
+        .env("CARGO_ENCODED_RUSTFLAGS", "-L\x1f/path with space")
+        .with_stderr_contains("[NOTE] resolved rustflags for `[..]`: -L '/path with space'")
+        .run();
+}
+
+#[cargo_test]
+fn encoded_and_plain_rustflags_conflict() {
+    let p = project().file("src/lib.rs", "").build();
+
+    p.cargo("build")
+        .env("CARGO_ENCODED_RUSTFLAGS", "--cfg\x1ffoo")
+        .env("RUSTFLAGS", "--cfg foo")
+        .with_status(101)
+        .with_stderr_contains(
+            "[ERROR] both `CARGO_ENCODED_RUSTFLAGS` and `RUSTFLAGS` are set in the environment",
+        )
+        .run();
+
+    p.cargo("doc")
+        .env("CARGO_ENCODED_RUSTDOCFLAGS", "--cfg\x1ffoo")
+        .env("RUSTDOCFLAGS", "--cfg foo")
+        .with_status(101)
+        .with_stderr_contains(
+            "[ERROR] both `CARGO_ENCODED_RUSTDOCFLAGS` and `RUSTDOCFLAGS` are set in the environment",
+        )
+        .run();
+}
+
+#[cargo_test]
+#[cfg(unix)]
+fn resolved_rustflags_shell_quoted() {
+    let p = project().file("src/lib.rs", "").build();
+
+    p.cargo("build -vv")
+        .env("CARGO_ENCODED_RUSTFLAGS", "--cfg\x1ffoo=\"a b\"")
+        .with_stderr_contains("[NOTE] resolved rustflags for `[..]`: --cfg 'foo=\"a b\"'")
+        .run();
+}
+
+#[cargo_test]
+fn inherited_encoded_rustflags() {
+    let p = project()
+        .file("src/lib.rs", "")
+        .file(
+            "build.rs",
+            r#"
+                fn main() {
+                    assert_eq!(
+                        std::env::var("__CARGO_PARENT_ENCODED_RUSTFLAGS").unwrap(),
+                        std::env::var("CARGO_ENCODED_RUSTFLAGS").unwrap(),
+                    );
+                }
+            "#,
+        )
+        .build();
+
+    p.cargo("build")
+        .env("CARGO_ENCODED_RUSTFLAGS", "--cfg\x1ffoo")
+        .with_stderr(
+            "\
+[COMPILING] foo v0.0.1 ([CWD])
+[FINISHED] [..]
+",
+        )
+        .run();
+
+    // As seen by `cargo` run from a build script of another build. The note
+    // is shown once, even though both the library and the build script get
+    // the rustflags.
+    p.cargo("build")
+        .env("CARGO_ENCODED_RUSTFLAGS", "--cfg\x1ffoo")
+        .env("__CARGO_PARENT_ENCODED_RUSTFLAGS", "--cfg\x1ffoo")
+        .with_stderr(
+            "\
+[NOTE] using `CARGO_ENCODED_RUSTFLAGS` inherited from a parent Cargo process, \
+so rustflags from config are ignored
+Remove `CARGO_ENCODED_RUSTFLAGS` from the environment of this `cargo` invocation \
+to use its own rustflags.
+[FINISHED] [..]
+",
+        )
+        .run();
+}
+
+#[cargo_test]
+fn rustflags_merge() {
+    let host = rustc_host();
+    let p = project()
+        .file("src/lib.rs", "")
+        .file(
+            ".cargo/config.toml",
+            &format!(
+                "
+                [target.{}]
+                rustflags = ['--cfg', 'from_target']
+
+                [build]
+                rustflags = ['--cfg', 'from_build']
+                ",
+                host
+            ),
+        )
+        .build();
+    let note = |flags: &str| format!("[NOTE] resolved rustflags for `{}`: {}", host, flags);
+
+    // env + target
+    p.cargo("build -vv")
+        .env("RUSTFLAGS", "--cfg from_env")
+        .with_stderr_contains(note("--cfg from_env"))
+        .run();
+    p.cargo("build -vv -Zrustflags-merge")
+        .masquerade_as_nightly_cargo()
+        .env("RUSTFLAGS", "--cfg from_env")
+        .env("CARGO_BUILD_RUSTFLAGS_MERGE", "true")
+        .with_stderr_contains(note("--cfg from_env --cfg from_target --cfg from_build"))
+        .run();
+
+    // target + build
+    p.cargo("build -vv")
+        .with_stderr_contains(note("--cfg from_target"))
+        .run();
+    p.cargo("build -vv -Zrustflags-merge")
+        .masquerade_as_nightly_cargo()
+        .env("CARGO_BUILD_RUSTFLAGS_MERGE", "true")
+        .with_stderr_contains(note("--cfg from_target --cfg from_build"))
+        .run();
+
+    // The key is ignored without -Zrustflags-merge.
+    p.cargo("build -vv")
+        .env("CARGO_BUILD_RUSTFLAGS_MERGE", "true")
+        .with_stderr_contains(note("--cfg from_target"))
+        .run();
+}
+
+#[cargo_test]
+fn rustflags_override_profile_settings() {
+    let p = project()
+        .file(
+            "Cargo.toml",
+            r#"
+                [package]
+                name = "foo"
+                version = "0.0.1"
+
+                [profile.dev]
+                opt-level = 1
+            "#,
+        )
+        .file("src/lib.rs", "")
+        .file("src/main.rs", "fn main() {}")
+        .build();
+
+    // Warned once, even though two units are built. `codegen-units` is not
+    // set by the profile, so overriding it is fine.
+    p.cargo("build")
+        .env("RUSTFLAGS", "-C opt-level=3 -Ccodegen-units=4")
+        .with_stderr(&format!(
+            "\
+[WARNING] `-Copt-level=3` in rustflags for `{host}` takes precedence over `profile.dev.opt-level`
+[COMPILING] foo v0.0.1 ([CWD])
+[FINISHED] [..]
+",
+            host = rustc_host()
+        ))
+        .run();
+
+    // Still warned when nothing needs to be rebuilt.
+    p.cargo("build")
+        .env("RUSTFLAGS", "-C opt-level=3 -Ccodegen-units=4")
+        .with_stderr(&format!(
+            "\
+[WARNING] `-Copt-level=3` in rustflags for `{host}` takes precedence over `profile.dev.opt-level`
+[FINISHED] [..]
+",
+            host = rustc_host()
+        ))
+        .run();
+
+    // The same value as the profile is not worth a warning.
+    p.cargo("build")
+        .env("RUSTFLAGS", "-Copt-level=1")
+        .with_stderr(
+            "\
+[COMPILING] foo v0.0.1 ([CWD])
+[FINISHED] [..]
+",
+        )
+        .run();
+}
+
+#[cargo_test]
+fn build_rustflags_file() {
+    let p = project()
+        .file(
+            "src/lib.rs",
+            r#"
+                #[cfg(not(all(a, b, c = "with space")))]
+                compile_error!("missing cfg");
+            "#,
+        )
+        .file(
+            ".cargo/config",
+            r#"
+                [build]
+                rustflags = ["--cfg", "a"]
+                rustflags-file = "flags.txt"
+            "#,
+        )
+        .file(
+            "flags.txt",
+            "--cfg b\n--cfg 'c=\"with space\"'\n-Copt-level=1\n",
+        )
+        .build();
+
+    p.cargo("check -v -Zrustflags-file")
+        .masquerade_as_nightly_cargo()
+        .with_stderr_contains("[RUNNING] `rustc [..]--cfg a --cfg b [..]-Copt-level=1[..]`")
+        .run();
+
+    // A missing file is an error naming it.
+    fs::remove_file(p.root().join("flags.txt")).unwrap();
+    p.cargo("check -Zrustflags-file")
+        .masquerade_as_nightly_cargo()
+        .with_status(101)
+        .with_stderr_contains(
+            "[ERROR] failed to read the rustflags file `[CWD]/flags.txt` set by `build.rustflags-file`",
+        )
+        .run();
+
+    // The key is ignored without -Zrustflags-file.
+    p.cargo("check")
+        .with_status(101)
+        .with_stderr_contains("[..]missing cfg[..]")
+        .run();
+}
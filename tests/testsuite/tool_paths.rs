@@ -1,6 +1,6 @@
 //! Tests for configuration values that point to programs.
 
-use cargo_test_support::{basic_lib_manifest, project, rustc_host, rustc_host_env};
+use cargo_test_support::{basic_lib_manifest, cross_compile, project, rustc_host, rustc_host_env};
 
 #[cargo_test]
 fn pathless_tools() {
@@ -336,7 +336,14 @@ fn custom_linker_env() {
     p.cargo("build -v")
         .env(&key, "nonexistent-linker")
         .with_status(101)
-        .with_stderr_contains("[RUNNING] `rustc [..]-C linker=nonexistent-linker [..]")
+        .with_stderr(&format!(
+            "\
+[ERROR] configured linker `nonexistent-linker` not found
+It is set for `{}` in environment variable `{}`.
+",
+            rustc_host(),
+            key
+        ))
         .run();
 }
 
@@ -400,3 +407,196 @@ fn cfg_ignored_fields() {
         )
         .run();
 }
+
+#[cargo_test]
+fn target_linker_not_used_for_host() {
+    if cross_compile::disabled() {
+        return;
+    }
+    let target = cross_compile::alternate();
+    let p = project()
+        .file("Cargo.toml", &basic_lib_manifest("foo"))
+        .file("src/lib.rs", "")
+        .file("build.rs", "fn main() {}")
+        .file(
+            ".cargo/config",
+            &format!(
+                r#"
+                    [target.{}]
+                    linker = "nonexistent-linker"
+                "#,
+                target
+            ),
+        )
+        .build();
+
+    // The library is built for the target, the build script for the host.
+    p.cargo("build -v --target")
+        .arg(&target)
+        .with_stderr_contains(
+            "[RUNNING] `rustc --crate-name foo [..]-C linker=nonexistent-linker [..]`",
+        )
+        .with_stderr_line_without(
+            &["[RUNNING] `rustc --crate-name build_script_build"],
+            &["-C linker"],
+        )
+        .run();
+}
+
+#[cargo_test]
+fn missing_linker_only_for_linking_units() {
+    let target = rustc_host();
+    let p = project()
+        .file("src/lib.rs", "")
+        .file("src/main.rs", "fn main() {}")
+        .file(
+            ".cargo/config",
+            &format!(
+                r#"
+                    [target.{}]
+                    linker = "./tools/nonexistent-linker"
+                "#,
+                target
+            ),
+        )
+        .build();
+
+    // The library doesn't link, so it doesn't need the linker.
+    p.cargo("build --lib")
+        .with_stderr_does_not_contain("[..]configured linker[..]")
+        .run();
+
+    // Nothing is linked when checking.
+    p.cargo("check")
+        .with_stderr_does_not_contain("[..]configured linker[..]")
+        .run();
+
+    p.cargo("build")
+        .with_status(101)
+        .with_stderr(&format!(
+            "\
+[ERROR] configured linker `[CWD]/./tools/nonexistent-linker` not found
+It is set for `{}` in [CWD]/.cargo/config.
+",
+            target
+        ))
+        .run();
+}
+
+#[cargo_test]
+fn missing_linker_error() {
+    let target = rustc_host();
+    let p = project()
+        .file("src/main.rs", "fn main() {}")
+        .file(
+            ".cargo/config",
+            &format!(
+                r#"
+                    [target.{}]
+                    linker = "./tools/nonexistent-linker"
+                "#,
+                target
+            ),
+        )
+        .build();
+
+    p.cargo("build")
+        .with_status(101)
+        .with_stderr(&format!(
+            "\
+[ERROR] configured linker `[CWD]/./tools/nonexistent-linker` not found
+It is set for `{}` in [CWD]/.cargo/config.
+",
+            target
+        ))
+        .run();
+}
+
+#[cargo_test]
+#[cfg(target_os = "linux")]
+fn cfg_linker() {
+    let p = project()
+        .file("Cargo.toml", &basic_lib_manifest("foo"))
+        .file("src/lib.rs", "")
+        .file(
+            ".cargo/config",
+            r#"
+                [target.'cfg(target_os = "linux")']
+                linker = "nonexistent-linker"
+
+                [target.'cfg(windows)']
+                linker = "other-linker"
+            "#,
+        )
+        .build();
+
+    // The tables are ignored without -Zcfg-linker.
+    p.cargo("build --verbose")
+        .with_stderr_contains(
+            "[WARNING] unused key `linker` in [target] config table `cfg(target_os = \"linux\")`",
+        )
+        .with_stderr_does_not_contain("[..]-C linker=[..]")
+        .run();
+
+    p.cargo("build --verbose -Zcfg-linker")
+        .masquerade_as_nightly_cargo()
+        .with_stderr(
+            "\
+[COMPILING] foo v0.5.0 ([CWD])
+[RUNNING] `rustc [..] -C linker=nonexistent-linker [..]`
+[FINISHED] dev [unoptimized + debuginfo] target(s) in [..]
+",
+        )
+        .run();
+
+    // A linker for the exact triple takes precedence.
+    p.change_file(
+        ".cargo/config",
+        &format!(
+            r#"
+                [target.'cfg(target_os = "linux")']
+                linker = "nonexistent-linker"
+
+                [target.{}]
+                linker = "triple-linker"
+            "#,
+            rustc_host()
+        ),
+    );
+    p.cargo("build --verbose -Zcfg-linker")
+        .masquerade_as_nightly_cargo()
+        .with_stderr_contains("[RUNNING] `rustc [..] -C linker=triple-linker [..]`")
+        .run();
+}
+
+#[cargo_test]
+#[cfg(target_os = "linux")]
+fn cfg_linker_conflict() {
+    let p = project()
+        .file("Cargo.toml", &basic_lib_manifest("foo"))
+        .file("src/lib.rs", "")
+        .file(
+            ".cargo/config",
+            r#"
+                [target.'cfg(target_os = "linux")']
+                linker = "linker-a"
+
+                [target.'cfg(unix)']
+                linker = "linker-b"
+            "#,
+        )
+        .build();
+
+    p.cargo("build -Zcfg-linker")
+        .masquerade_as_nightly_cargo()
+        .with_status(101)
+        .with_stderr(
+            "\
+[ERROR] conflicting linkers for target `[..]`
+`target.'cfg(target_os = \"linux\")'.linker` is `linker-a` in [CWD]/.cargo/config
+`target.'cfg(unix)'.linker` is `linker-b` in [CWD]/.cargo/config
+Set `target.[..].linker` to choose one.
+",
+        )
+        .run();
+}